@@ -0,0 +1,42 @@
+//! Measures overhead the fuse adds to reads of a fused reader.
+//!
+//! Run with `cargo bench --bench fuse`.
+use fused_reader::{fuse, fuse_with, FusePolicy};
+use std::io::{self, Read};
+use std::time::{Duration, Instant};
+
+const TOTAL: u64 = 256 * 1024 * 1024;
+
+fn read_all<R: Read>(reader: R, read_size: usize) -> Duration {
+    let mut reader = reader.take(TOTAL);
+    let mut buf = vec![0; read_size];
+    let start = Instant::now();
+    let mut received = 0;
+    loop {
+        match reader.read(&mut buf).unwrap() {
+            0 => break,
+            bytes => received += bytes as u64,
+        }
+    }
+    assert_eq!(received, TOTAL);
+    start.elapsed()
+}
+
+fn report(name: &str, reads: u64, elapsed: Duration) {
+    println!("{:<32} {:>8.2?} {:>8.2} ns/read", name, elapsed, elapsed.as_nanos() as f64 / reads as f64);
+}
+
+fn main() {
+    for &read_size in &[64, 1024, 16 * 1024] {
+        let reads = TOTAL / read_size as u64;
+        report(&format!("plain {} B", read_size), reads, read_all(io::repeat(1), read_size));
+
+        let (reader, fuse) = fuse(io::repeat(1));
+        let _guard = fuse.arm().unwrap();
+        report(&format!("fused {} B", read_size), reads, read_all(reader, read_size));
+
+        let (reader, fuse) = fuse_with(io::repeat(1), FusePolicy::FailFast);
+        let _guard = fuse.arm().unwrap();
+        report(&format!("fused fail-fast {} B", read_size), reads, read_all(reader, read_size));
+    }
+}
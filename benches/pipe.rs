@@ -0,0 +1,48 @@
+//! Compares throughput of the default ring pipe with double-buffered pipe.
+//!
+//! Run with `cargo bench --bench pipe`.
+use fused_reader::ring_pipe::{double_buffered_pipe, pipe_with_capacity};
+use std::io::{Read, Write};
+use std::thread;
+use std::time::{Duration, Instant};
+
+const TOTAL: usize = 1024 * 1024 * 1024;
+const WRITE_SIZE: usize = 16 * 1024;
+
+fn transfer<R: Read, W: Write + Send + 'static>(mut reader: R, mut writer: W) -> Duration {
+    let start = Instant::now();
+    let producer = thread::spawn(move || {
+        let chunk = vec![1; WRITE_SIZE];
+        for _ in 0..TOTAL / WRITE_SIZE {
+            writer.write_all(&chunk).unwrap();
+        }
+        writer.flush().unwrap();
+    });
+
+    let mut buf = vec![0; WRITE_SIZE];
+    let mut received = 0;
+    loop {
+        match reader.read(&mut buf).unwrap() {
+            0 => break,
+            bytes => received += bytes,
+        }
+    }
+    producer.join().unwrap();
+    assert_eq!(received, TOTAL);
+    start.elapsed()
+}
+
+fn report(name: &str, elapsed: Duration) {
+    let throughput = TOTAL as f64 / elapsed.as_secs_f64() / (1024.0 * 1024.0 * 1024.0);
+    println!("{:<32} {:>8.2?} {:>8.2} GiB/s", name, elapsed, throughput);
+}
+
+fn main() {
+    for &buffer_size in &[64 * 1024, 1024 * 1024, 8 * 1024 * 1024] {
+        let (reader, writer) = pipe_with_capacity(buffer_size);
+        report(&format!("ring pipe {} KiB", buffer_size / 1024), transfer(reader, writer));
+
+        let (reader, writer) = double_buffered_pipe(buffer_size);
+        report(&format!("double-buffered pipe {} KiB", buffer_size / 1024), transfer(reader, writer));
+    }
+}
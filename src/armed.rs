@@ -0,0 +1,138 @@
+use std::io::{IoSlice, Write, Error as IoError, ErrorKind};
+
+use crate::{FuseError, FuseGuard};
+
+impl<'a> FuseGuard<'a> {
+    /// Wraps writer of the stream so that its failures blow the fuse with the error.
+    ///
+    /// The writer is dropped after the fuse is released so the reader sees the outcome at EOF.
+    pub fn wrap_writer<W: Write>(self, writer: W) -> ArmedWriter<'a, W> {
+        ArmedWriter {
+            guard: self,
+            writer,
+        }
+    }
+}
+
+/// Writer holding armed fuse that gets blown with the first error of the writer; see
+/// `FuseGuard::wrap_writer`.
+#[derive(Debug)]
+pub struct ArmedWriter<'a, W: Write> {
+    // released before the writer is dropped
+    guard: FuseGuard<'a>,
+    writer: W,
+}
+
+impl<'a, W: Write> ArmedWriter<'a, W> {
+    /// Returns the fuse guard, e.g. to check if the stream was cancelled or send heartbeat.
+    pub fn guard(&self) -> &FuseGuard<'a> {
+        &self.guard
+    }
+
+    /// Returns reference to the inner writer.
+    pub fn get_ref(&self) -> &W {
+        &self.writer
+    }
+
+    /// Returns mutable reference to the inner writer.
+    ///
+    /// Errors of writes done through it do not blow the fuse.
+    pub fn get_mut(&mut self) -> &mut W {
+        &mut self.writer
+    }
+
+    /// Disarms the fuse signalling that the stream was written in full and returns the writer.
+    pub fn complete(self) -> W {
+        let (writer, guard) = self.into_parts();
+        guard.complete();
+        writer
+    }
+
+    /// Returns the writer and the fuse guard.
+    pub fn into_parts(self) -> (W, FuseGuard<'a>) {
+        (self.writer, self.guard)
+    }
+
+    fn blow<T>(&mut self, result: Result<T, IoError>) -> Result<T, IoError> {
+        if let Err(err) = &result {
+            if err.kind() != ErrorKind::Interrupted && self.guard.result.is_ok() {
+                self.guard.result = Err(FuseError::explicit(FuseError::duplicate(err)));
+            }
+        }
+        result
+    }
+}
+
+impl<W: Write> Write for ArmedWriter<'_, W> {
+    fn write(&mut self, buf: &[u8]) -> Result<usize, IoError> {
+        let result = self.writer.write(buf);
+        self.blow(result)
+    }
+
+    fn write_vectored(&mut self, bufs: &[IoSlice<'_>]) -> Result<usize, IoError> {
+        let result = self.writer.write_vectored(bufs);
+        self.blow(result)
+    }
+
+    fn write_all(&mut self, buf: &[u8]) -> Result<(), IoError> {
+        let result = self.writer.write_all(buf);
+        self.blow(result)
+    }
+
+    fn flush(&mut self) -> Result<(), IoError> {
+        let result = self.writer.flush();
+        self.blow(result)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{fuse, FuseStatus};
+    use std::io::{self, Read, Write, Cursor, Error as IoError, ErrorKind};
+    use std::thread;
+
+    /// Writes up to `left` bytes to the pipe and fails afterwards.
+    struct DiskFull {
+        writer: pipe::PipeWriter,
+        left: usize,
+    }
+
+    impl Write for DiskFull {
+        fn write(&mut self, buf: &[u8]) -> Result<usize, IoError> {
+            if self.left == 0 {
+                return Err(IoError::new(ErrorKind::WriteZero, "disk full"))
+            }
+            let bytes = self.writer.write(&buf[..buf.len().min(self.left)])?;
+            self.left -= bytes;
+            Ok(bytes)
+        }
+
+        fn flush(&mut self) -> Result<(), IoError> {
+            self.writer.flush()
+        }
+    }
+
+    #[test]
+    fn test_armed_writer_blows_on_error() {
+        let (reader, writer) = pipe::pipe();
+        let (mut reader, fuse) = fuse(reader);
+
+        thread::spawn(move || {
+            let mut writer = fuse.arm().unwrap().wrap_writer(DiskFull { writer, left: 2 });
+            assert_eq!(io::copy(&mut Cursor::new(vec![1, 2, 3]), &mut writer).unwrap_err().kind(), ErrorKind::WriteZero);
+        });
+
+        let mut data = Vec::new();
+        assert_eq!(reader.read_to_end(&mut data).unwrap_err().kind(), ErrorKind::WriteZero);
+        assert_eq!(data, vec![1, 2]);
+    }
+
+    #[test]
+    fn test_armed_writer_complete() {
+        let (reader, fuse) = fuse(Cursor::new(Vec::new()));
+        let mut writer = fuse.arm().unwrap().wrap_writer(Vec::new());
+        writer.write_all(&[1, 2]).unwrap();
+        assert_eq!(writer.complete(), vec![1, 2]);
+        assert!(matches!(reader.check_fuse(), FuseStatus::Completed));
+    }
+}
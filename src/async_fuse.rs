@@ -0,0 +1,203 @@
+use std::cell::RefCell;
+use std::io::{Error as IoError, ErrorKind};
+use std::rc::Rc;
+use std::sync::{Arc, Mutex};
+use std::thread;
+
+use crate::{BlowReason, CodedError, FuseError, FuseStatus};
+use crate::panic::{take_panic, PanicInfo};
+
+#[derive(Debug)]
+pub enum State {
+    Unarmed,
+    Armed,
+    Blown(IoError),
+    Poisoned(PanicInfo),
+}
+
+mod sealed {
+    pub trait Cell: Clone {
+        fn new() -> Self;
+        fn with<T>(&self, f: impl FnOnce(&mut super::State) -> T) -> T;
+    }
+}
+
+/// Storage of the async fuse state shared between the fuse and the fused async type.
+pub trait FuseCell: sealed::Cell {}
+
+/// Async fuse state that can be shared between threads; the default.
+#[derive(Debug, Clone)]
+pub struct SyncState(Arc<Mutex<State>>);
+
+impl sealed::Cell for SyncState {
+    fn new() -> SyncState {
+        SyncState(Arc::new(Mutex::new(State::Unarmed)))
+    }
+
+    fn with<T>(&self, f: impl FnOnce(&mut State) -> T) -> T {
+        f(&mut self.0.lock().unwrap_or_else(|err| err.into_inner()))
+    }
+}
+
+impl FuseCell for SyncState {}
+
+/// Async fuse state local to a single thread.
+///
+/// Fuses and fused types using it are not `Send`, which suits thread-per-core runtimes like
+/// monoio or glommio running all tasks of a shard on one thread.
+#[derive(Debug, Clone)]
+pub struct LocalState(Rc<RefCell<State>>);
+
+impl sealed::Cell for LocalState {
+    fn new() -> LocalState {
+        LocalState(Rc::new(RefCell::new(State::Unarmed)))
+    }
+
+    fn with<T>(&self, f: impl FnOnce(&mut State) -> T) -> T {
+        f(&mut self.0.borrow_mut())
+    }
+}
+
+impl FuseCell for LocalState {}
+
+/// Callback run when the fuse gets blown, e.g. to cancel outstanding I/O of the reader.
+#[cfg(all(feature = "iocp", windows))]
+#[derive(Clone)]
+pub(crate) struct FailHook(Arc<dyn Fn() + Send + Sync>);
+
+#[cfg(all(feature = "iocp", windows))]
+impl std::fmt::Debug for FailHook {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str("FailHook")
+    }
+}
+
+/// Reader side of the async fuse shared with fused async types.
+#[derive(Debug, Clone)]
+pub(crate) struct AsyncFuseState<C = SyncState> {
+    cell: C,
+    #[cfg(all(feature = "iocp", windows))]
+    on_fail: Option<FailHook>,
+}
+
+impl<C: FuseCell> AsyncFuseState<C> {
+    pub(crate) fn new() -> AsyncFuseState<C> {
+        AsyncFuseState {
+            cell: C::new(),
+            #[cfg(all(feature = "iocp", windows))]
+            on_fail: None,
+        }
+    }
+
+    /// Creates state calling `on_fail` when the fuse gets blown or the writer panics.
+    #[cfg(all(feature = "iocp", windows))]
+    pub(crate) fn with_fail_hook(on_fail: impl Fn() + Send + Sync + 'static) -> AsyncFuseState<C> {
+        AsyncFuseState {
+            cell: C::new(),
+            on_fail: Some(FailHook(Arc::new(on_fail))),
+        }
+    }
+
+    /// Checks status of the fuse; the error it was blown with stays until taken with `take_error`.
+    pub(crate) fn check_fuse(&self) -> FuseStatus {
+        self.cell.with(|state| match state {
+            State::Unarmed => FuseStatus::Unarmed,
+            State::Armed => FuseStatus::Armed,
+            State::Blown(err) => FuseStatus::Blown(FuseError::duplicate(err)),
+            State::Poisoned(panic) => FuseStatus::Poisoned(panic.clone()),
+        })
+    }
+
+    /// Takes the error the fuse was blown with.
+    pub(crate) fn take_error(&self) -> Option<IoError> {
+        self.cell.with(|state| match std::mem::replace(state, State::Unarmed) {
+            State::Blown(err) => Some(err),
+            other => {
+                *state = other;
+                None
+            }
+        })
+    }
+
+    /// Error that reader should fail with at EOF, if any.
+    pub(crate) fn eof_error(&self) -> Option<IoError> {
+        self.check_fuse().into_eof_error()
+    }
+
+    fn set(&self, new: State) {
+        self.cell.with(|state| *state = new);
+    }
+
+    fn fail(&self, failed: State) {
+        self.set(failed);
+        #[cfg(all(feature = "iocp", windows))]
+        if let Some(FailHook(on_fail)) = &self.on_fail {
+            on_fail();
+        }
+    }
+}
+
+/// Fuse that can be armed inside of an async task.
+///
+/// Unlike `Fuse` the armed guard is owned and `Send` so it can be held across `.await` points.
+/// Use `LocalAsyncFuse` with runtimes that do not require `Send` futures.
+#[derive(Debug)]
+pub struct AsyncFuse<C: FuseCell = SyncState>(pub(crate) AsyncFuseState<C>);
+
+/// Async fuse that is not `Send`; see `LocalState`.
+pub type LocalAsyncFuse = AsyncFuse<LocalState>;
+
+impl<C: FuseCell> AsyncFuse<C> {
+    /// Arms the fuse.
+    pub fn arm(self) -> AsyncFuseGuard<C> {
+        self.0.set(State::Armed);
+        AsyncFuseGuard(Some(self.0))
+    }
+}
+
+/// Armed async fuse.
+///
+/// If dropped without calling `disarm` (e.g. task was cancelled or panicked) the reader end will
+/// fail with `BrokenPipe` error.
+#[derive(Debug)]
+pub struct AsyncFuseGuard<C: FuseCell = SyncState>(Option<AsyncFuseState<C>>);
+
+/// Armed async fuse that is not `Send`; see `LocalState`.
+pub type LocalAsyncFuseGuard = AsyncFuseGuard<LocalState>;
+
+impl<C: FuseCell> AsyncFuseGuard<C> {
+    /// Disarms the fuse signalling that the writer finished successfully.
+    pub fn disarm(mut self) {
+        if let Some(state) = self.0.take() {
+            state.set(State::Unarmed);
+        }
+    }
+
+    /// Blows the fuse with given error.
+    ///
+    /// The reader end will fail with this error after reaching EOF.
+    pub fn blow(mut self, err: IoError) {
+        if let Some(state) = self.0.take() {
+            state.fail(State::Blown(FuseError::explicit(err)));
+        }
+    }
+
+    /// Blows the fuse with numeric failure code and message.
+    ///
+    /// The reader end will fail with `ErrorKind::Other` error; the code can be retrieved with `error_code`.
+    pub fn blow_code(self, code: i32, msg: impl Into<String>) {
+        self.blow(CodedError::into_io_error(code, msg.into()))
+    }
+}
+
+impl<C: FuseCell> Drop for AsyncFuseGuard<C> {
+    fn drop(&mut self) {
+        if let Some(state) = self.0.take() {
+            if thread::panicking() {
+                state.fail(State::Poisoned(take_panic()))
+            } else {
+                state.fail(State::Blown(FuseError::new_io(ErrorKind::BrokenPipe, BlowReason::Cancelled, "writer task dropped while fuse was armed")))
+            }
+        }
+    }
+}
@@ -0,0 +1,260 @@
+use std::io::Error as IoError;
+use std::pin::Pin;
+use std::task::{Context, Poll};
+
+use crate::async_fuse::{AsyncFuse, AsyncFuseState, FuseCell, LocalState, SyncState};
+use crate::FuseStatus;
+
+/// Fuses async reader so that if the task writing the data dies while holding armed fuse the
+/// reader will get `BrokenPipe` error.
+///
+/// Works with readers implementing `futures::io::AsyncRead`, which is also the `Read` trait of
+/// async-std, with the `futures-io` feature and `tokio::io::AsyncRead` with the `tokio` feature.
+pub fn fuse_async_read<R>(reader: R) -> (FusedAsyncReader<R>, AsyncFuse) {
+    fuse_async_read_with(reader)
+}
+
+/// Like `fuse_async_read` but the fused reader and the fuse are not `Send`; see `LocalState`.
+pub fn fuse_async_read_local<R>(reader: R) -> (FusedAsyncReader<R, LocalState>, AsyncFuse<LocalState>) {
+    fuse_async_read_with(reader)
+}
+
+fn fuse_async_read_with<R, C: FuseCell>(reader: R) -> (FusedAsyncReader<R, C>, AsyncFuse<C>) {
+    fuse_async_read_state(reader, AsyncFuseState::new())
+}
+
+pub(crate) fn fuse_async_read_state<R, C: FuseCell>(reader: R, state: AsyncFuseState<C>) -> (FusedAsyncReader<R, C>, AsyncFuse<C>) {
+    (FusedAsyncReader {
+            reader,
+            fuse: state.clone(),
+        },
+        AsyncFuse(state),
+    )
+}
+
+/// Async reader that will fail with I/O error if fuse was blown.
+#[derive(Debug)]
+pub struct FusedAsyncReader<R, C: FuseCell = SyncState> {
+    reader: R,
+    fuse: AsyncFuseState<C>,
+}
+
+impl<R, C: FuseCell> FusedAsyncReader<R, C> {
+    /// Checks status of the fuse.
+    ///
+    /// Once blown the fuse is reported as `FuseStatus::Blown` with a copy of its error until the
+    /// error is taken with `take_error`.
+    pub fn check_fuse(&self) -> FuseStatus {
+        self.fuse.check_fuse()
+    }
+
+    /// Takes the error the fuse was blown with, if any.
+    ///
+    /// The reader no longer fails with the error at EOF afterwards.
+    pub fn take_error(&mut self) -> Option<IoError> {
+        self.fuse.take_error()
+    }
+
+    /// Returns inner reader.
+    pub fn into_inner(self) -> R {
+        self.reader
+    }
+
+    /// Converts into stream of chunks of `size` bytes; see `FusedReader::chunks`.
+    ///
+    /// Panics if `size` is 0.
+    #[cfg(feature = "futures-io")]
+    pub fn chunks(self, size: usize) -> AsyncChunks<R, C> {
+        assert!(size > 0, "chunk size must be non-zero");
+        AsyncChunks {
+            reader: self,
+            chunk: vec![0; size],
+            filled: 0,
+            error: None,
+            done: false,
+        }
+    }
+
+    fn project(self: Pin<&mut Self>) -> (Pin<&mut R>, &mut AsyncFuseState<C>) {
+        // Safety: `reader` is structurally pinned and never moved out of pinned `self`
+        let this = unsafe { self.get_unchecked_mut() };
+        (unsafe { Pin::new_unchecked(&mut this.reader) }, &mut this.fuse)
+    }
+}
+
+#[cfg(feature = "futures-io")]
+impl<R: futures_io::AsyncRead, C: FuseCell> futures_io::AsyncRead for FusedAsyncReader<R, C> {
+    fn poll_read(self: Pin<&mut Self>, cx: &mut Context<'_>, buf: &mut [u8]) -> Poll<Result<usize, IoError>> {
+        let (reader, fuse) = self.project();
+
+        // let it read to end before checking fuse
+        match reader.poll_read(cx, buf) {
+            Poll::Ready(Ok(0)) if !buf.is_empty() => Poll::Ready(fuse.eof_error().map_or(Ok(0), Err)),
+            other => other,
+        }
+    }
+}
+
+/// Stream of chunks read from fused async reader; see `FusedAsyncReader::chunks`.
+#[cfg(feature = "futures-io")]
+#[derive(Debug)]
+pub struct AsyncChunks<R, C: FuseCell = SyncState> {
+    reader: FusedAsyncReader<R, C>,
+    chunk: Vec<u8>,
+    filled: usize,
+    // delivered after the chunk read before it
+    error: Option<IoError>,
+    done: bool,
+}
+
+#[cfg(feature = "futures-io")]
+impl<R, C: FuseCell> AsyncChunks<R, C> {
+    /// Checks status of the fuse; see `FusedAsyncReader::check_fuse`.
+    pub fn check_fuse(&self) -> FuseStatus {
+        self.reader.check_fuse()
+    }
+
+    /// Returns inner fused reader.
+    pub fn into_inner(self) -> FusedAsyncReader<R, C> {
+        self.reader
+    }
+}
+
+#[cfg(feature = "futures-io")]
+impl<R: futures_io::AsyncRead, C: FuseCell> futures_core::Stream for AsyncChunks<R, C> {
+    type Item = Result<Vec<u8>, IoError>;
+
+    fn poll_next(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        // Safety: `reader` is structurally pinned and never moved out of pinned `self`
+        let this = unsafe { self.get_unchecked_mut() };
+        if this.done {
+            return Poll::Ready(None)
+        }
+        if let Some(err) = this.error.take() {
+            this.done = true;
+            return Poll::Ready(Some(Err(err)))
+        }
+
+        let mut reader = unsafe { Pin::new_unchecked(&mut this.reader) };
+        while this.filled < this.chunk.len() {
+            match futures_io::AsyncRead::poll_read(reader.as_mut(), cx, &mut this.chunk[this.filled..]) {
+                Poll::Pending => return Poll::Pending,
+                Poll::Ready(Ok(0)) => break,
+                Poll::Ready(Ok(bytes)) => this.filled += bytes,
+                Poll::Ready(Err(err)) if err.kind() == std::io::ErrorKind::Interrupted => (),
+                Poll::Ready(Err(err)) => {
+                    this.error = Some(err);
+                    break
+                }
+            }
+        }
+
+        let size = this.chunk.len();
+        let mut chunk = std::mem::replace(&mut this.chunk, vec![0; size]);
+        chunk.truncate(std::mem::take(&mut this.filled));
+        if !chunk.is_empty() {
+            return Poll::Ready(Some(Ok(chunk)))
+        }
+        this.done = true;
+        Poll::Ready(this.error.take().map(Err))
+    }
+}
+
+#[cfg(feature = "tokio")]
+impl<R: tokio::io::AsyncRead, C: FuseCell> tokio::io::AsyncRead for FusedAsyncReader<R, C> {
+    fn poll_read(self: Pin<&mut Self>, cx: &mut Context<'_>, buf: &mut tokio::io::ReadBuf<'_>) -> Poll<Result<(), IoError>> {
+        let (reader, fuse) = self.project();
+        let filled = buf.filled().len();
+
+        // let it read to end before checking fuse
+        match reader.poll_read(cx, buf) {
+            Poll::Ready(Ok(())) if buf.filled().len() == filled && buf.remaining() > 0 => Poll::Ready(fuse.eof_error().map_or(Ok(()), Err)),
+            other => other,
+        }
+    }
+}
+
+#[cfg(all(test, feature = "futures-io"))]
+mod tests {
+    use super::*;
+    use futures::executor::block_on;
+    use futures::io::AsyncReadExt;
+    use std::io::ErrorKind;
+
+    #[test]
+    fn test_fused_async_reader_complete() {
+        let (mut reader, fuse) = fuse_async_read(&[1, 2, 3][..]);
+        fuse.arm().disarm();
+
+        let mut data = Vec::new();
+        block_on(reader.read_to_end(&mut data)).unwrap();
+        assert_eq!(data, vec![1, 2, 3]);
+    }
+
+    #[test]
+    fn test_fused_async_reader_chunks() {
+        use futures::stream::StreamExt;
+
+        let (reader, fuse) = fuse_async_read(&[1, 2, 3][..]);
+        fuse.arm().blow(IoError::new(ErrorKind::InvalidData, "uh! oh!"));
+
+        let items = block_on(reader.chunks(2).collect::<Vec<_>>());
+        assert_eq!(items.len(), 3);
+        assert_eq!(items[0].as_ref().unwrap(), &vec![1, 2]);
+        assert_eq!(items[1].as_ref().unwrap(), &vec![3]);
+        assert_eq!(items[2].as_ref().unwrap_err().kind(), ErrorKind::InvalidData);
+    }
+
+    #[test]
+    fn test_fused_async_reader_blow() {
+        let (mut reader, fuse) = fuse_async_read(&[1, 2, 3][..]);
+        fuse.arm().blow(IoError::new(ErrorKind::UnexpectedEof, "uh! oh!"));
+
+        let mut data = Vec::new();
+        assert_eq!(block_on(reader.read_to_end(&mut data)).unwrap_err().kind(), ErrorKind::UnexpectedEof);
+        assert_eq!(data, vec![1, 2, 3]);
+    }
+}
+
+#[cfg(all(test, feature = "tokio"))]
+mod tokio_tests {
+    use super::*;
+    use futures::executor::block_on;
+    use futures::future::{poll_fn, FutureExt};
+    use std::io::ErrorKind;
+    use tokio::io::{AsyncRead, ReadBuf};
+
+    fn read_to_end<R: AsyncRead + Unpin>(reader: &mut R, data: &mut Vec<u8>) -> Result<(), IoError> {
+        let mut chunk = [0; 2];
+        loop {
+            let mut buf = ReadBuf::new(&mut chunk);
+            block_on(poll_fn(|cx| Pin::new(&mut *reader).poll_read(cx, &mut buf)))?;
+            if buf.filled().is_empty() {
+                return Ok(())
+            }
+            data.extend_from_slice(buf.filled());
+        }
+    }
+
+    #[test]
+    fn test_fused_async_reader_tokio() {
+        let (mut reader, fuse) = fuse_async_read(&[1, 2, 3][..]);
+        fuse.arm().blow(IoError::new(ErrorKind::UnexpectedEof, "uh! oh!"));
+
+        let mut data = Vec::new();
+        assert_eq!(read_to_end(&mut reader, &mut data).unwrap_err().kind(), ErrorKind::UnexpectedEof);
+        assert_eq!(data, vec![1, 2, 3]);
+    }
+
+    #[test]
+    fn test_fused_async_reader_tokio_task_dropped() {
+        let (mut reader, fuse) = fuse_async_read(&[1][..]);
+        let task = async move {
+            let _fuse = fuse.arm();
+            futures::future::pending::<()>().await;
+        };
+        assert!(task.now_or_never().is_none());
+
+        assert_eq!(read_to_end(&mut reader, &mut Vec::new()).unwrap_err().kind(), ErrorKind::BrokenPipe);
+    }
+}
@@ -0,0 +1,126 @@
+use std::io::{Read, Error as IoError};
+use std::thread;
+
+use crate::{fuse, panic, panic_error, CodedError, Fuse, FuseError, FuseGuard, FusedReader, POISONED};
+
+/// Fuses each of `readers` so that a single writer feeding all of them fails every one of them.
+///
+/// This suits a producer that tees its output into several consumers, each reading its own pipe.
+/// Readers are returned in order of `readers`.
+pub fn fuse_bundle<R: Read>(readers: impl IntoIterator<Item = R>) -> (Vec<FusedReader<R>>, FuseBundle) {
+    let (readers, fuses) = readers.into_iter().map(fuse).unzip();
+    (readers, FuseBundle { fuses })
+}
+
+/// Fuses of a fan-out of fused readers armed together by their writer.
+#[derive(Debug, Clone)]
+pub struct FuseBundle {
+    fuses: Vec<Fuse>,
+}
+
+impl FuseBundle {
+    /// Arms fuses of all the readers.
+    ///
+    /// Returns `BrokenPipe` error if any of the readers was dropped due to panic.
+    pub fn arm(&self) -> Result<BundleGuard<'_>, IoError> {
+        Ok(BundleGuard {
+            guards: self.fuses.iter().map(Fuse::arm).collect::<Result<_, _>>()?,
+        })
+    }
+
+    /// Returns fuses of the readers in order of the readers.
+    pub fn fuses(&self) -> &[Fuse] {
+        &self.fuses
+    }
+}
+
+/// Armed fuses of all the readers of `FuseBundle`.
+///
+/// Blowing it or dropping it due to panic makes every reader fail with the same error.
+#[derive(Debug)]
+pub struct BundleGuard<'a> {
+    guards: Vec<FuseGuard<'a>>,
+}
+
+impl<'a> BundleGuard<'a> {
+    /// Blows fuses of all the readers with given error.
+    pub fn blow(mut self, err: IoError) {
+        for guard in self.guards.drain(..) {
+            guard.blow(FuseError::duplicate(&err));
+        }
+    }
+
+    /// Blows fuses of all the readers with numeric failure code and message; see `FuseGuard::blow_code`.
+    pub fn blow_code(self, code: i32, msg: impl Into<String>) {
+        self.blow(CodedError::into_io_error(code, msg.into()))
+    }
+
+    /// Disarms fuses of all the readers signalling that their streams were written in full.
+    pub fn complete(mut self) {
+        for guard in self.guards.drain(..) {
+            guard.complete();
+        }
+    }
+
+    /// Returns `true` if all the readers cancelled their streams; see `FuseGuard::is_cancelled`.
+    pub fn is_cancelled(&self) -> bool {
+        self.guards.iter().all(FuseGuard::is_cancelled)
+    }
+}
+
+impl Drop for BundleGuard<'_> {
+    fn drop(&mut self) {
+        if thread::panicking() {
+            // the panic can be taken only once
+            let panic = panic::take_panic();
+            for guard in &self.guards {
+                guard.shared.set_error(guard.generation, panic_error(panic.clone()), POISONED);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{install_panic_hook, ring_pipe, FuseStatus};
+    use std::io::{ErrorKind, Write};
+
+    #[test]
+    fn test_fuse_bundle_blow() {
+        let (pipes, writers): (Vec<_>, Vec<_>) = (0..2).map(|_| ring_pipe::pipe()).unzip();
+        let (readers, bundle) = fuse_bundle(pipes);
+
+        thread::spawn(move || {
+            let mut writers = writers;
+            let guard = bundle.arm().unwrap();
+            for writer in &mut writers {
+                writer.write_all(&[1, 2]).unwrap();
+            }
+            guard.blow_code(3, "uh! oh!");
+        });
+
+        for mut reader in readers {
+            let mut data = Vec::new();
+            let err = reader.read_to_end(&mut data).unwrap_err();
+            assert_eq!(crate::error_code(&err), Some(3));
+            assert_eq!(data, vec![1, 2]);
+        }
+    }
+
+    #[test]
+    fn test_fuse_bundle_panic() {
+        install_panic_hook();
+        let (readers, bundle) = fuse_bundle(vec![&[1][..], &[2][..]]);
+
+        thread::spawn(move || {
+            let _guard = bundle.arm().unwrap();
+            panic!("boom");
+        }).join().unwrap_err();
+
+        for mut reader in readers {
+            assert!(matches!(reader.check_fuse(), FuseStatus::Poisoned(panic) if panic.msg.as_deref() == Some("boom")));
+            assert_eq!(reader.read_to_end(&mut Vec::new()).unwrap_err().kind(), ErrorKind::BrokenPipe);
+        }
+    }
+}
@@ -0,0 +1,224 @@
+use std::io::{Error as IoError, ErrorKind};
+use std::time::Duration;
+
+use crossbeam_channel::{Receiver, RecvError, RecvTimeoutError, TryRecvError};
+
+use crate::{check_fuse, eof_error, new_fuse_state, take_fuse_error, Fuse, FuseState, FuseStatus};
+
+/// Fuses `crossbeam_channel::Receiver` so that if sender thread dies while holding armed fuse the receiver will get `BrokenPipe` error after disconnect.
+pub fn fuse_receiver<T>(receiver: Receiver<T>) -> (FusedReceiver<T>, Fuse) {
+    let receiver_fuse = new_fuse_state();
+    let sender_fuse = receiver_fuse.clone();
+    (FusedReceiver {
+            receiver,
+            fuse: receiver_fuse,
+        },
+        Fuse::from_state(sender_fuse),
+    )
+}
+
+/// Receiver that will fail with I/O error on disconnect if fuse was blown.
+///
+/// Methods return `Ok(None)` only when channel got disconnected cleanly; `recv_timeout` and
+/// `try_recv` fail with `TimedOut` and `WouldBlock` error when no message is ready yet.
+#[derive(Debug)]
+pub struct FusedReceiver<T> {
+    receiver: Receiver<T>,
+    fuse: FuseState,
+}
+
+impl<T> FusedReceiver<T> {
+    /// Checks status of the fuse.
+    ///
+    /// Once blown the fuse is reported as `FuseStatus::Blown` with a copy of its error until the
+    /// error is taken with `take_error`.
+    pub fn check_fuse(&self) -> FuseStatus {
+        check_fuse(&self.fuse)
+    }
+
+    /// Takes the error the fuse was blown with, if any.
+    ///
+    /// The receiver no longer fails with the error on disconnect afterwards.
+    pub fn take_error(&mut self) -> Option<IoError> {
+        take_fuse_error(&self.fuse)
+    }
+
+    /// Blocks waiting for a message.
+    pub fn recv(&mut self) -> Result<Option<T>, IoError> {
+        let res = self.receiver.recv();
+        self.fused(res)
+    }
+
+    /// Waits for a message for given duration failing with `TimedOut` error on timeout.
+    pub fn recv_timeout(&mut self, timeout: Duration) -> Result<Option<T>, IoError> {
+        match self.receiver.recv_timeout(timeout) {
+            Ok(msg) => Ok(Some(msg)),
+            Err(RecvTimeoutError::Timeout) => Err(IoError::new(ErrorKind::TimedOut, "timed out waiting for message")),
+            Err(RecvTimeoutError::Disconnected) => self.fused(Err(RecvError)),
+        }
+    }
+
+    /// Attempts to receive a message without blocking failing with `WouldBlock` error if channel is empty.
+    pub fn try_recv(&mut self) -> Result<Option<T>, IoError> {
+        match self.receiver.try_recv() {
+            Ok(msg) => Ok(Some(msg)),
+            Err(TryRecvError::Empty) => Err(IoError::new(ErrorKind::WouldBlock, "channel is empty")),
+            Err(TryRecvError::Disconnected) => self.fused(Err(RecvError)),
+        }
+    }
+
+    /// Applies fuse to the result of receive operation done on inner receiver.
+    ///
+    /// Use with `select!` on `receiver()`:
+    ///
+    /// ```rust
+    /// use crossbeam_channel::{unbounded, select};
+    /// use fused_reader::fuse_receiver;
+    ///
+    /// let (sender, receiver) = unbounded();
+    /// let (mut receiver, _fuse) = fuse_receiver(receiver);
+    /// sender.send(1).unwrap();
+    /// drop(sender);
+    ///
+    /// let mut data = Vec::new();
+    /// loop {
+    ///     select! {
+    ///         recv(receiver.receiver()) -> msg => match receiver.fused(msg).unwrap() {
+    ///             Some(msg) => data.push(msg),
+    ///             None => break,
+    ///         }
+    ///     }
+    /// }
+    ///
+    /// assert_eq!(&data, &[1]);
+    /// ```
+    pub fn fused(&mut self, res: Result<T, RecvError>) -> Result<Option<T>, IoError> {
+        match res {
+            Ok(msg) => Ok(Some(msg)),
+            Err(RecvError) => eof_error(&self.fuse).map_or(Ok(None), Err),
+        }
+    }
+
+    /// Returns reference to inner receiver for use with `select!`.
+    pub fn receiver(&self) -> &Receiver<T> {
+        &self.receiver
+    }
+
+    /// Blocking iterator over messages that yields final error if fuse was blown.
+    pub fn iter(&mut self) -> Iter<'_, T> {
+        Iter {
+            receiver: self,
+            done: false,
+        }
+    }
+
+    /// Returns inner receiver.
+    pub fn into_inner(self) -> Receiver<T> {
+        self.receiver
+    }
+}
+
+/// Blocking iterator over messages of `FusedReceiver`.
+#[derive(Debug)]
+pub struct Iter<'a, T> {
+    receiver: &'a mut FusedReceiver<T>,
+    done: bool,
+}
+
+impl<'a, T> Iterator for Iter<'a, T> {
+    type Item = Result<T, IoError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.done {
+            return None
+        }
+        match self.receiver.recv() {
+            Ok(Some(msg)) => Some(Ok(msg)),
+            Ok(None) => {
+                self.done = true;
+                None
+            }
+            Err(err) => {
+                self.done = true;
+                Some(Err(err))
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crossbeam_channel::{unbounded, select};
+    use std::thread;
+
+    #[test]
+    fn test_fused_receiver_nopanic() {
+        let (sender, receiver) = unbounded();
+        let (mut receiver, fuse) = fuse_receiver(receiver);
+
+        thread::spawn(move || {
+            let _fuse = fuse.arm().unwrap();
+            sender.send(1).unwrap();
+        });
+
+        assert_eq!(receiver.iter().collect::<Result<Vec<_>, _>>().unwrap(), vec![1]);
+    }
+
+    #[test]
+    fn test_fused_receiver_panic() {
+        let (sender, receiver) = unbounded();
+        let (mut receiver, fuse) = fuse_receiver(receiver);
+
+        thread::spawn(move || {
+            let _fuse = fuse.arm().unwrap();
+            sender.send(1).unwrap();
+            panic!("boom");
+        });
+
+        assert_eq!(receiver.recv().unwrap(), Some(1));
+        assert_eq!(receiver.recv().unwrap_err().kind(), ErrorKind::BrokenPipe);
+    }
+
+    #[test]
+    fn test_fused_receiver_blow_select() {
+        let (sender, receiver) = unbounded();
+        let (mut receiver, fuse) = fuse_receiver(receiver);
+
+        thread::spawn(move || {
+            let fuse = fuse.arm().unwrap();
+            sender.send(1).unwrap();
+            fuse.blow(IoError::new(ErrorKind::UnexpectedEof, "uh! oh!"))
+        });
+
+        let mut data = Vec::new();
+        let err = loop {
+            select! {
+                recv(receiver.receiver()) -> msg => match receiver.fused(msg) {
+                    Ok(Some(msg)) => data.push(msg),
+                    Ok(None) => panic!("unexpected clean disconnect"),
+                    Err(err) => break err,
+                }
+            }
+        };
+
+        assert_eq!(err.kind(), ErrorKind::UnexpectedEof);
+        assert_eq!(&data, &[1]);
+    }
+    #[test]
+    fn test_fused_receiver_poll() {
+        let (sender, receiver) = unbounded();
+        let (mut receiver, fuse) = fuse_receiver(receiver);
+        let guard = fuse.arm().unwrap();
+
+        assert_eq!(receiver.try_recv().unwrap_err().kind(), ErrorKind::WouldBlock);
+        assert_eq!(receiver.recv_timeout(Duration::from_millis(1)).unwrap_err().kind(), ErrorKind::TimedOut);
+        sender.send(1).unwrap();
+        assert_eq!(receiver.try_recv().unwrap(), Some(1));
+
+        drop(guard);
+        drop(sender);
+        assert_eq!(receiver.try_recv().unwrap(), None);
+        assert_eq!(receiver.recv_timeout(Duration::from_millis(1)).unwrap(), None);
+    }
+}
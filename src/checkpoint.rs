@@ -0,0 +1,132 @@
+use std::io::{Read, Seek, SeekFrom, Error as IoError, ErrorKind};
+
+use crate::{fuse, panic_error, BlowReason, Fuse, FuseError, FusedReader, BLOWN, POISONED};
+
+/// Snapshot of the logical position of `FusedReader` and its pending fuse state.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Checkpoint {
+    /// Number of bytes consumed from the stream.
+    pub position: u64,
+    /// Kind and message of the error the fuse was blown with but not yet delivered to the reader.
+    pub pending_error: Option<(ErrorKind, String)>,
+    /// Reason of the pending error if it was raised by the fuse.
+    pub pending_reason: Option<BlowReason>,
+}
+
+/// Backends which can resume streaming from given position.
+pub trait Resume {
+    /// Positions the backend so that next read returns data at `position` of the stream.
+    fn resume_from(&mut self, position: u64) -> Result<(), IoError>;
+}
+
+impl<T: Seek> Resume for T {
+    fn resume_from(&mut self, position: u64) -> Result<(), IoError> {
+        self.seek(SeekFrom::Start(position)).map(|_| ())
+    }
+}
+
+impl<R: Read> FusedReader<R> {
+    /// Number of bytes consumed from the stream.
+    pub fn position(&self) -> u64 {
+        self.position
+    }
+
+    /// Takes a snapshot of the reader's logical position and pending fuse state.
+    ///
+    /// This does not consume the pending fuse error.
+    pub fn checkpoint(&self) -> Checkpoint {
+        let pending = match self.fuse.status() {
+            _ if self.fuse.is_guarded() => None,
+            POISONED => Some(self.fuse.error().as_ref().map_or_else(|| panic_error(Default::default()), FuseError::duplicate)),
+            _ => self.fuse.error().as_ref().map(FuseError::duplicate),
+        };
+
+        Checkpoint {
+            position: self.position,
+            pending_error: pending.as_ref().map(|err| (err.kind(), err.to_string())),
+            pending_reason: pending.as_ref().and_then(FuseError::from_io).map(|err| err.reason().clone()),
+        }
+    }
+
+    /// Reattaches to resumable backend at the position recorded in the checkpoint.
+    ///
+    /// If the checkpoint has pending fuse error the returned fuse is already blown with it and the
+    /// reader will fail with it after reaching EOF.
+    pub fn restore(mut reader: R, checkpoint: &Checkpoint) -> Result<(FusedReader<R>, Fuse), IoError> where R: Resume {
+        reader.resume_from(checkpoint.position)?;
+
+        let (mut reader, fuse) = fuse(reader);
+        reader.advance(checkpoint.position);
+        if let Some((kind, message)) = &checkpoint.pending_error {
+            let err = match &checkpoint.pending_reason {
+                Some(reason) => FuseError::new_io(*kind, reason.clone(), message.as_str()),
+                None => IoError::new(*kind, message.as_str()),
+            };
+            reader.fuse.set_error(reader.fuse.generation(), err, BLOWN);
+        }
+        Ok((reader, fuse))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::fuse;
+    use std::io::Cursor;
+
+    #[test]
+    fn test_checkpoint_restore() {
+        let data = vec![1, 2, 3, 4];
+        let (mut reader, _fuse) = fuse(Cursor::new(data.clone()));
+
+        let mut buf = [0; 2];
+        reader.read_exact(&mut buf).unwrap();
+        let checkpoint = reader.checkpoint();
+        assert_eq!(checkpoint, Checkpoint { position: 2, pending_error: None, pending_reason: None });
+        drop(reader);
+
+        let (mut reader, _fuse) = FusedReader::restore(Cursor::new(data), &checkpoint).unwrap();
+        let mut rest = Vec::new();
+        reader.read_to_end(&mut rest).unwrap();
+        assert_eq!(&rest, &[3, 4]);
+        assert_eq!(reader.position(), 4);
+    }
+
+    #[test]
+    fn test_checkpoint_pending_error() {
+        let data = vec![1, 2, 3, 4];
+        let (mut reader, fuse) = fuse(Cursor::new(data.clone()));
+        fuse.arm().unwrap().blow(IoError::new(ErrorKind::UnexpectedEof, "uh! oh!"));
+
+        let mut buf = [0; 2];
+        reader.read_exact(&mut buf).unwrap();
+        let checkpoint = reader.checkpoint();
+        assert_eq!(checkpoint.pending_error, Some((ErrorKind::UnexpectedEof, "uh! oh!".to_owned())));
+        drop(reader);
+
+        let (mut reader, _fuse) = FusedReader::restore(Cursor::new(data), &checkpoint).unwrap();
+        let mut rest = Vec::new();
+        assert_eq!(reader.read_to_end(&mut rest).unwrap_err().kind(), ErrorKind::UnexpectedEof);
+        assert_eq!(&rest, &[3, 4]);
+    }
+
+    #[test]
+    fn test_checkpoint_poisoned() {
+        crate::install_panic_hook();
+        let data = vec![1, 2];
+        let (reader, writer_fuse) = fuse(Cursor::new(data.clone()));
+        std::thread::spawn(move || {
+            let _guard = writer_fuse.arm().unwrap();
+            panic!("boom");
+        }).join().unwrap_err();
+
+        let checkpoint = reader.checkpoint();
+        assert!(matches!(&checkpoint.pending_reason, Some(BlowReason::Panic { msg: Some(msg), .. }) if msg == "boom"));
+        drop(reader);
+
+        let (mut reader, _fuse) = FusedReader::restore(Cursor::new(data), &checkpoint).unwrap();
+        let err = reader.read_to_end(&mut Vec::new()).unwrap_err();
+        assert_eq!(err.kind(), ErrorKind::BrokenPipe);
+        assert_eq!(reader.blow_reason(), checkpoint.pending_reason.as_ref());
+    }
+}
@@ -0,0 +1,501 @@
+use std::collections::VecDeque;
+use std::io::{Read, Error as IoError, ErrorKind};
+use std::process::{Child, ChildStderr, ChildStdout, ExitStatus};
+use std::sync::{Arc, Condvar, Mutex, MutexGuard};
+use std::thread;
+use std::time::Duration;
+
+use crate::exit::decode_record;
+use crate::{fuse, Fuse, FuseError, FusedReader, OwnedFuseGuard};
+
+/// Number of trailing bytes of standard error of the child process kept by `fuse_child`.
+const STDERR_TAIL: usize = 64 * 1024;
+
+/// How long `fuse_child` waits for standard error to be closed after the child process exited.
+const STDERR_CLOSE_TIMEOUT: Duration = Duration::from_millis(100);
+
+/// What happens to the child process when `ChildReader` or `ChildOutput` is dropped.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum DropPolicy {
+    /// Kill the child process and reap it; the child process is killed already when the reader
+    /// cancels the stream if the fuse was attached.
+    #[default]
+    KillOnDrop,
+    /// Close its standard output and wait for the child process to exit.
+    WaitOnDrop,
+    /// Leave the child process running.
+    Detach,
+}
+
+/// Reader of child process standard output that manages the child process lifetime.
+///
+/// Wrap it with `fuse` and `attach_fuse` to get `FusedReader` that stops its producer process
+/// when abandoned or cancelled.
+#[derive(Debug)]
+pub struct ChildReader {
+    // closed before the drop policy is applied
+    stdout: Option<ChildStdout>,
+    process: Arc<ChildProcess>,
+}
+
+impl ChildReader {
+    /// Takes standard output of the child process applying given policy when dropped.
+    ///
+    /// Fails with `InvalidInput` error if standard output of the child process was not piped.
+    pub fn new(mut child: Child, policy: DropPolicy) -> Result<ChildReader, IoError> {
+        let stdout = child.stdout.take()
+            .ok_or_else(|| IoError::new(ErrorKind::InvalidInput, "child process standard output was not piped"))?;
+        let stderr = StderrTail::default();
+        stderr.closed();
+        Ok(ChildReader {
+            stdout: Some(stdout),
+            process: ChildProcess::new(child, policy, Arc::new(stderr)),
+        })
+    }
+
+    /// Attaches the fuse of the fused reader reading this child process so that the child process
+    /// is killed as soon as the reader cancels the stream if its drop policy is `KillOnDrop`.
+    pub fn attach_fuse(&self, fuse: &Fuse) {
+        self.process.attach_fuse(fuse)
+    }
+
+    /// Returns the child process.
+    pub fn child(&mut self) -> MutexGuard<'_, Child> {
+        self.process.child()
+    }
+
+    /// Changes the drop policy.
+    pub fn set_drop_policy(&mut self, policy: DropPolicy) {
+        self.process.set_policy(policy);
+    }
+}
+
+impl Read for ChildReader {
+    fn read(&mut self, buf: &mut [u8]) -> Result<usize, IoError> {
+        match self.stdout.as_mut() {
+            Some(stdout) => stdout.read(buf),
+            None => Ok(0),
+        }
+    }
+}
+
+impl Drop for ChildReader {
+    fn drop(&mut self) {
+        // so that the child does not block writing to a pipe nobody reads
+        drop(self.stdout.take());
+        self.process.release();
+    }
+}
+
+/// Exit status and captured standard error of a child process fused with `fuse_child`.
+#[derive(Debug, Clone)]
+pub struct ChildOutcome {
+    /// Exit status of the child process.
+    pub status: ExitStatus,
+    /// Last 64 KiB of standard error of the child process; empty if it was not piped.
+    pub stderr: Vec<u8>,
+}
+
+/// Standard error of the child process collected by its own thread.
+#[derive(Debug, Default)]
+struct StderrTail {
+    // trailing bytes and whether the pipe was closed
+    tail: Mutex<(VecDeque<u8>, bool)>,
+    closed: Condvar,
+}
+
+impl StderrTail {
+    fn lock(&self) -> MutexGuard<'_, (VecDeque<u8>, bool)> {
+        self.tail.lock().unwrap_or_else(|err| err.into_inner())
+    }
+
+    /// Reads the pipe to the end keeping only its last `STDERR_TAIL` bytes.
+    fn collect(&self, mut pipe: ChildStderr) {
+        let mut buf = [0; 4096];
+        loop {
+            match pipe.read(&mut buf) {
+                Ok(0) => break,
+                Ok(bytes) => {
+                    let mut tail = self.lock();
+                    tail.0.extend(&buf[..bytes]);
+                    let excess = tail.0.len().saturating_sub(STDERR_TAIL);
+                    tail.0.drain(..excess);
+                }
+                Err(err) if err.kind() == ErrorKind::Interrupted => (),
+                Err(_) => break,
+            }
+        }
+        self.closed();
+    }
+
+    fn closed(&self) {
+        self.lock().1 = true;
+        self.closed.notify_all();
+    }
+
+    /// Returns bytes collected once the pipe was closed or `timeout` elapsed.
+    fn wait(&self, timeout: Duration) -> Vec<u8> {
+        let tail = self.lock();
+        let (tail, _) = self.closed.wait_timeout_while(tail, timeout, |tail| !tail.1).unwrap_or_else(|err| err.into_inner());
+        tail.0.iter().copied().collect()
+    }
+}
+
+/// Child process of `ChildReader` or `ChildOutput` with its drop policy and outcome.
+#[derive(Debug)]
+struct ChildProcess {
+    child: Mutex<Child>,
+    policy: Mutex<DropPolicy>,
+    stderr: Arc<StderrTail>,
+    // released once the outcome was recorded
+    guard: Mutex<Option<OwnedFuseGuard>>,
+    outcome: Mutex<Option<Result<ChildOutcome, IoError>>>,
+    exited: Condvar,
+}
+
+impl ChildProcess {
+    fn new(child: Child, policy: DropPolicy, stderr: Arc<StderrTail>) -> Arc<ChildProcess> {
+        Arc::new(ChildProcess {
+            child: Mutex::new(child),
+            policy: Mutex::new(policy),
+            stderr,
+            guard: Mutex::new(None),
+            outcome: Mutex::new(None),
+            exited: Condvar::new(),
+        })
+    }
+
+    fn child(&self) -> MutexGuard<'_, Child> {
+        self.child.lock().unwrap_or_else(|err| err.into_inner())
+    }
+
+    fn policy(&self) -> DropPolicy {
+        *self.policy.lock().unwrap_or_else(|err| err.into_inner())
+    }
+
+    fn set_policy(&self, policy: DropPolicy) {
+        *self.policy.lock().unwrap_or_else(|err| err.into_inner()) = policy;
+    }
+
+    fn kill(&self) {
+        let _ = self.child().kill();
+    }
+
+    /// Kills the child process once the reader cancels the stream if it is to be killed on drop.
+    fn attach_fuse(self: &Arc<Self>, fuse: &Fuse) {
+        let process = Arc::downgrade(self);
+        fuse.0.on_cancel(Arc::new(move || {
+            if let Some(process) = process.upgrade() {
+                if process.policy() == DropPolicy::KillOnDrop {
+                    process.kill();
+                }
+            }
+        }));
+    }
+
+    /// Applies the drop policy once standard output of the child process was closed.
+    fn release(self: &Arc<Self>) {
+        match self.policy() {
+            DropPolicy::KillOnDrop => {
+                self.kill();
+                self.reap();
+            }
+            DropPolicy::WaitOnDrop => self.reap(),
+            DropPolicy::Detach => {
+                let process = self.clone();
+                let _ = thread::Builder::new().name("fused-child-reaper".to_owned()).spawn(move || process.reap());
+            }
+        }
+    }
+
+    /// Waits for the child process to exit and records its outcome unless it was recorded already.
+    fn reap(&self) {
+        let mut child = self.child();
+        if self.outcome.lock().unwrap_or_else(|err| err.into_inner()).is_some() {
+            return
+        }
+        let outcome = child.wait().map(|status| ChildOutcome {
+            status,
+            // processes spawned by the child may keep it open
+            stderr: self.stderr.wait(STDERR_CLOSE_TIMEOUT),
+        });
+
+        if let Some(guard) = self.guard.lock().unwrap_or_else(|err| err.into_inner()).take() {
+            match &outcome {
+                Ok(ChildOutcome { status, .. }) if status.success() => (),
+                Ok(ChildOutcome { status, stderr }) => guard.set_error(decode_record(stderr).unwrap_or_else(|| child_error(*status, stderr))),
+                Err(err) => guard.set_error(FuseError::duplicate(err)),
+            }
+            // the outcome is visible to the reader once the fuse got disarmed
+        }
+        *self.outcome.lock().unwrap_or_else(|err| err.into_inner()) = Some(outcome);
+        self.exited.notify_all();
+    }
+
+    /// Waits for the outcome of the child process to be recorded.
+    fn wait(&self) -> MutexGuard<'_, Option<Result<ChildOutcome, IoError>>> {
+        let mut outcome = self.outcome.lock().unwrap_or_else(|err| err.into_inner());
+        while outcome.is_none() {
+            outcome = self.exited.wait(outcome).unwrap_or_else(|err| err.into_inner());
+        }
+        outcome
+    }
+}
+
+/// Standard output of a child process fused with `fuse_child`.
+///
+/// At the end of the output it waits for the child process to exit so that the fused reader sees
+/// its outcome. If dropped before that the drop policy is applied to the child process.
+#[derive(Debug)]
+pub struct ChildOutput {
+    // closed before the drop policy is applied
+    stdout: Option<ChildStdout>,
+    process: Arc<ChildProcess>,
+    eof: bool,
+}
+
+impl ChildOutput {
+    /// Returns standard output of the child process.
+    pub fn get_ref(&self) -> &ChildStdout {
+        self.stdout.as_ref().expect("standard output taken on drop")
+    }
+
+    /// Changes the drop policy.
+    pub fn set_drop_policy(&mut self, policy: DropPolicy) {
+        self.process.set_policy(policy);
+    }
+}
+
+impl Read for ChildOutput {
+    fn read(&mut self, buf: &mut [u8]) -> Result<usize, IoError> {
+        let bytes = self.stdout.as_mut().expect("standard output taken on drop").read(buf)?;
+        if bytes == 0 && !buf.is_empty() {
+            self.process.reap();
+            self.eof = true;
+        }
+        Ok(bytes)
+    }
+}
+
+impl Drop for ChildOutput {
+    fn drop(&mut self) {
+        if self.eof {
+            return
+        }
+        // so that the child does not block writing to a pipe nobody reads
+        drop(self.stdout.take());
+        self.process.release();
+    }
+}
+
+/// Outcome of a child process fused with `fuse_child`.
+#[derive(Debug)]
+pub struct ChildFuse {
+    id: u32,
+    process: Arc<ChildProcess>,
+}
+
+impl ChildFuse {
+    /// OS-assigned identifier of the child process.
+    pub fn id(&self) -> u32 {
+        self.id
+    }
+
+    /// Waits for the outcome of the child process, recorded once the reader reached EOF or was
+    /// dropped.
+    ///
+    /// Fails if waiting for the child process failed.
+    pub fn wait(&self) -> Result<ChildOutcome, IoError> {
+        match self.process.wait().as_ref().expect("child outcome recorded") {
+            Ok(outcome) => Ok(outcome.clone()),
+            Err(err) => Err(FuseError::duplicate(err)),
+        }
+    }
+}
+
+/// Fuses standard output of the child process so that the reader fails if the process fails.
+///
+/// A background thread collects the tail of standard error of the child process, if piped. Once
+/// the reader reaches EOF it waits for the child process to exit. If the process exited with
+/// non-zero status the reader fails with error decoded from the record of `producer_main`, or with
+/// `ErrorKind::Other` error describing the exit status and the last line of standard error.
+/// Standard error is waited for at most 100ms after the child exited as processes it spawned may
+/// keep it open. If the reader is dropped before reaching EOF or is cancelled `policy` is applied
+/// to the child process like with `ChildReader` with the fuse attached.
+///
+/// Fails with `InvalidInput` error if standard output of the child process was not piped.
+pub fn fuse_child(mut child: Child, policy: DropPolicy) -> Result<(FusedReader<ChildOutput>, ChildFuse), IoError> {
+    let stdout = child.stdout.take()
+        .ok_or_else(|| IoError::new(ErrorKind::InvalidInput, "child process standard output was not piped"))?;
+    let stderr_pipe = child.stderr.take();
+    let id = child.id();
+
+    let stderr = Arc::new(StderrTail::default());
+    match stderr_pipe {
+        Some(pipe) => {
+            let stderr = stderr.clone();
+            thread::Builder::new().name(format!("fused-child-{}", id)).spawn(move || stderr.collect(pipe))?;
+        }
+        None => stderr.closed(),
+    }
+
+    let process = ChildProcess::new(child, policy, stderr);
+    let (reader, fuse) = fuse(ChildOutput {
+        stdout: Some(stdout),
+        process: process.clone(),
+        eof: false,
+    });
+    process.attach_fuse(&fuse);
+    *process.guard.lock().unwrap_or_else(|err| err.into_inner()) = Some(fuse.arm_owned().expect("new fuse can be armed"));
+
+    Ok((reader, ChildFuse { id, process }))
+}
+
+fn child_error(status: ExitStatus, stderr: &[u8]) -> IoError {
+    let stderr = String::from_utf8_lossy(stderr);
+    let message = match stderr.lines().rev().find(|line| !line.trim().is_empty()) {
+        Some(line) => format!("child process failed with {}: {}", status, line.trim()),
+        None => format!("child process failed with {}", status),
+    };
+    FuseError::explicit(IoError::other(message))
+}
+
+#[cfg(all(test, unix))]
+mod tests {
+    use super::*;
+    use crate::fuse;
+    use std::process::{Command, Stdio};
+
+    fn spawn(script: &str) -> Child {
+        Command::new("sh").arg("-c").arg(script).stdout(Stdio::piped()).spawn().unwrap()
+    }
+
+    fn running(pid: u32) -> bool {
+        Command::new("kill").arg("-0").arg(pid.to_string()).stderr(Stdio::null()).status().unwrap().success()
+    }
+
+    #[test]
+    fn test_child_reader() {
+        let (mut reader, _fuse) = fuse(ChildReader::new(spawn("echo hello"), DropPolicy::WaitOnDrop).unwrap());
+
+        let mut data = String::new();
+        reader.read_to_string(&mut data).unwrap();
+        assert_eq!(data, "hello\n");
+    }
+
+    #[test]
+    fn test_child_reader_kill_on_drop() {
+        let child = spawn("echo started; sleep 60");
+        let pid = child.id();
+        let (mut reader, _fuse) = fuse(ChildReader::new(child, DropPolicy::KillOnDrop).unwrap());
+
+        reader.read_exact(&mut [0; 8]).unwrap();
+        drop(reader);
+        assert!(!running(pid));
+    }
+
+    #[test]
+    fn test_child_reader_kill_on_cancel() {
+        let child = spawn("echo started; sleep 60");
+        let pid = child.id();
+        let reader = ChildReader::new(child, DropPolicy::KillOnDrop).unwrap();
+        let (mut reader, fuse) = fuse(reader);
+        reader.get_ref().attach_fuse(&fuse);
+
+        reader.read_exact(&mut [0; 8]).unwrap();
+        reader.cancel("not needed");
+        // killed child closes its output
+        assert_eq!(reader.read_to_end(&mut Vec::new()).unwrap(), 0);
+        assert!(!reader.get_mut().child().wait().unwrap().success());
+        assert!(!running(pid));
+    }
+
+    #[test]
+    fn test_child_reader_wait_on_drop() {
+        let child = spawn("yes");
+        let pid = child.id();
+        let reader = ChildReader::new(child, DropPolicy::WaitOnDrop).unwrap();
+
+        // child gets SIGPIPE once its output is closed
+        drop(reader);
+        assert!(!running(pid));
+    }
+
+    #[test]
+    fn test_child_reader_not_piped() {
+        let child = Command::new("true").spawn().unwrap();
+        assert_eq!(ChildReader::new(child, DropPolicy::WaitOnDrop).unwrap_err().kind(), ErrorKind::InvalidInput);
+    }
+
+    #[test]
+    fn test_fuse_child() {
+        let child = Command::new("sh").arg("-c").arg("echo hello").stdout(Stdio::piped()).spawn().unwrap();
+        let (mut reader, child) = fuse_child(child, DropPolicy::KillOnDrop).unwrap();
+
+        let mut data = String::new();
+        reader.read_to_string(&mut data).unwrap();
+        assert_eq!(data, "hello\n");
+        assert!(child.wait().unwrap().status.success());
+    }
+
+    #[test]
+    fn test_fuse_child_kill_on_drop() {
+        let (mut reader, child) = fuse_child(spawn("echo started; sleep 60"), DropPolicy::KillOnDrop).unwrap();
+
+        reader.read_exact(&mut [0; 8]).unwrap();
+        drop(reader);
+        assert!(!running(child.id()));
+        assert!(!child.wait().unwrap().status.success());
+    }
+
+    #[test]
+    fn test_fuse_child_kill_on_cancel() {
+        let (mut reader, child) = fuse_child(spawn("echo started; sleep 60"), DropPolicy::KillOnDrop).unwrap();
+
+        reader.read_exact(&mut [0; 8]).unwrap();
+        reader.cancel("not needed");
+        assert!(reader.read_to_end(&mut Vec::new()).is_err());
+        assert!(!child.wait().unwrap().status.success());
+    }
+
+    #[test]
+    fn test_fuse_child_wait_on_drop() {
+        let (reader, child) = fuse_child(spawn("yes"), DropPolicy::WaitOnDrop).unwrap();
+
+        // child gets SIGPIPE once its output is closed
+        drop(reader);
+        assert!(!running(child.id()));
+        assert!(!child.wait().unwrap().status.success());
+    }
+
+    #[test]
+    fn test_fuse_child_failed() {
+        let child = Command::new("sh").arg("-c").arg("echo hello; echo bad input >&2; exit 3")
+            .stdout(Stdio::piped()).stderr(Stdio::piped()).spawn().unwrap();
+        let (mut reader, child) = fuse_child(child, DropPolicy::KillOnDrop).unwrap();
+
+        let mut data = String::new();
+        let err = reader.read_to_string(&mut data).unwrap_err();
+        assert_eq!(err.kind(), ErrorKind::Other);
+        assert!(err.to_string().ends_with(": bad input"));
+        assert_eq!(data, "hello\n");
+
+        let outcome = child.wait().unwrap();
+        assert_eq!(outcome.status.code(), Some(3));
+        assert_eq!(outcome.stderr, b"bad input\n");
+    }
+
+    #[test]
+    fn test_fuse_child_stderr_inherited() {
+        // background process keeps standard error open after the child exited
+        let child = Command::new("sh").arg("-c").arg("sleep 60 >/dev/null & echo $!; echo bad input >&2; exit 3")
+            .stdout(Stdio::piped()).stderr(Stdio::piped()).spawn().unwrap();
+        let (mut reader, child) = fuse_child(child, DropPolicy::KillOnDrop).unwrap();
+
+        let mut data = String::new();
+        let err = reader.read_to_string(&mut data).unwrap_err();
+        assert!(err.to_string().ends_with(": bad input"));
+        assert_eq!(child.wait().unwrap().status.code(), Some(3));
+        Command::new("kill").arg(data.trim()).status().unwrap();
+    }
+}
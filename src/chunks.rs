@@ -0,0 +1,190 @@
+use std::io::{BufRead, BufReader, Read, Error as IoError, ErrorKind};
+
+use crate::{FusedReader, FuseStatus};
+
+impl<R: Read> FusedReader<R> {
+    /// Converts into iterator of chunks of `size` bytes; the last chunk may be shorter.
+    ///
+    /// Iteration ends after the stream ended or after first error; if the fuse was blown its error
+    /// is the last item, after the chunk of data read before it.
+    ///
+    /// Panics if `size` is 0.
+    pub fn chunks(self, size: usize) -> Chunks<R> {
+        assert!(size > 0, "chunk size must be non-zero");
+        Chunks {
+            reader: self,
+            size,
+            error: None,
+            done: false,
+        }
+    }
+
+    /// Converts into iterator of records separated by `delimiter`, not included in the records.
+    ///
+    /// Last record does not need to be terminated by the delimiter. Errors are delivered like with
+    /// `chunks`.
+    pub fn delimited(self, delimiter: u8) -> Delimited<R> {
+        Delimited {
+            reader: BufReader::new(self),
+            delimiter,
+            error: None,
+            done: false,
+        }
+    }
+}
+
+/// Iterator of chunks read from fused reader; see `FusedReader::chunks`.
+#[derive(Debug)]
+pub struct Chunks<R: Read> {
+    reader: FusedReader<R>,
+    size: usize,
+    // delivered after the chunk read before it
+    error: Option<IoError>,
+    done: bool,
+}
+
+impl<R: Read> Chunks<R> {
+    /// Checks status of the fuse; see `FusedReader::check_fuse`.
+    pub fn check_fuse(&self) -> FuseStatus {
+        self.reader.check_fuse()
+    }
+
+    /// Returns inner fused reader.
+    pub fn into_inner(self) -> FusedReader<R> {
+        self.reader
+    }
+}
+
+impl<R: Read> Iterator for Chunks<R> {
+    type Item = Result<Vec<u8>, IoError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.done {
+            return None
+        }
+        if let Some(err) = self.error.take() {
+            self.done = true;
+            return Some(Err(err))
+        }
+
+        let mut chunk = vec![0; self.size];
+        let mut filled = 0;
+        while filled < chunk.len() {
+            match self.reader.read(&mut chunk[filled..]) {
+                Ok(0) => break,
+                Ok(bytes) => filled += bytes,
+                Err(err) if err.kind() == ErrorKind::Interrupted => (),
+                Err(err) => {
+                    self.error = Some(err);
+                    break
+                }
+            }
+        }
+        chunk.truncate(filled);
+        next_item(chunk, &mut self.error, &mut self.done)
+    }
+}
+
+/// Iterator of delimited records read from fused reader; see `FusedReader::delimited`.
+#[derive(Debug)]
+pub struct Delimited<R: Read> {
+    reader: BufReader<FusedReader<R>>,
+    delimiter: u8,
+    error: Option<IoError>,
+    done: bool,
+}
+
+impl<R: Read> Delimited<R> {
+    /// Checks status of the fuse; see `FusedReader::check_fuse`.
+    pub fn check_fuse(&self) -> FuseStatus {
+        self.reader.get_ref().check_fuse()
+    }
+
+    /// Returns inner fused reader.
+    ///
+    /// Data buffered but not read yet is lost.
+    pub fn into_inner(self) -> FusedReader<R> {
+        self.reader.into_inner()
+    }
+}
+
+impl<R: Read> Iterator for Delimited<R> {
+    type Item = Result<Vec<u8>, IoError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.done {
+            return None
+        }
+        if let Some(err) = self.error.take() {
+            self.done = true;
+            return Some(Err(err))
+        }
+
+        let mut record = Vec::new();
+        match self.reader.read_until(self.delimiter, &mut record) {
+            Ok(_) if record.last() == Some(&self.delimiter) => {
+                record.pop();
+                return Some(Ok(record))
+            }
+            Ok(_) => (),
+            // data read before the error is kept in the record
+            Err(err) => self.error = Some(err),
+        }
+        next_item(record, &mut self.error, &mut self.done)
+    }
+}
+
+/// Returns data read before the end of the stream or the error, or the error if there is none.
+fn next_item(data: Vec<u8>, error: &mut Option<IoError>, done: &mut bool) -> Option<Result<Vec<u8>, IoError>> {
+    if !data.is_empty() {
+        return Some(Ok(data))
+    }
+    *done = true;
+    error.take().map(Err)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{fuse, ring_pipe};
+    use std::io::{Write, Error as IoError, ErrorKind};
+    use std::thread;
+
+    #[test]
+    fn test_chunks() {
+        let (reader, mut writer) = ring_pipe::pipe();
+        let (reader, fuse) = fuse(reader);
+
+        thread::spawn(move || {
+            let fuse = fuse.arm().unwrap();
+            writer.write_all(&[1, 2, 3, 4, 5]).unwrap();
+            fuse.blow(IoError::new(ErrorKind::InvalidData, "uh! oh!"));
+        });
+
+        let mut chunks = reader.chunks(2);
+        assert_eq!(chunks.next().unwrap().unwrap(), vec![1, 2]);
+        assert_eq!(chunks.next().unwrap().unwrap(), vec![3, 4]);
+        assert_eq!(chunks.next().unwrap().unwrap(), vec![5]);
+        assert_eq!(chunks.next().unwrap().unwrap_err().kind(), ErrorKind::InvalidData);
+        assert!(chunks.next().is_none());
+    }
+
+    #[test]
+    fn test_delimited() {
+        let (reader, mut writer) = ring_pipe::pipe();
+        let (reader, fuse) = fuse(reader);
+
+        thread::spawn(move || {
+            let _fuse = fuse.arm().unwrap();
+            writer.write_all(b"foo\n\nbar\nba").unwrap();
+            panic!("boom");
+        });
+
+        let mut records = reader.delimited(b'\n');
+        assert_eq!(records.next().unwrap().unwrap(), b"foo");
+        assert_eq!(records.next().unwrap().unwrap(), b"");
+        assert_eq!(records.next().unwrap().unwrap(), b"bar");
+        assert_eq!(records.next().unwrap().unwrap(), b"ba");
+        assert_eq!(records.next().unwrap().unwrap_err().kind(), ErrorKind::BrokenPipe);
+        assert!(records.next().is_none());
+    }
+}
@@ -0,0 +1,74 @@
+use std::error::Error;
+use std::fmt;
+use std::io::{Error as IoError, ErrorKind};
+
+use crate::reason::FuseError;
+
+/// Machine readable failure category passed from writer to reader with `blow_code`.
+///
+/// The reader end gets it as inner error of `ErrorKind::Other` I/O error; use `error_code` to retrieve it.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CodedError {
+    /// Numeric failure code.
+    pub code: i32,
+    /// Human readable failure description.
+    pub message: String,
+}
+
+impl CodedError {
+    pub(crate) fn into_io_error(code: i32, message: String) -> IoError {
+        IoError::other(CodedError { code, message })
+    }
+}
+
+impl fmt::Display for CodedError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} (code {})", self.message, self.code)
+    }
+}
+
+impl Error for CodedError {}
+
+/// Returns `CodedError` carried by I/O error if the fuse was blown with `blow_code`.
+pub fn coded_error(err: &IoError) -> Option<&CodedError> {
+    let err = FuseError::from_io(err).and_then(|err| err.explicit_error()).unwrap_or(err);
+    err.get_ref().and_then(|err| err.downcast_ref::<CodedError>())
+}
+
+/// Returns failure code carried by I/O error if the fuse was blown with `blow_code`.
+pub fn error_code(err: &IoError) -> Option<i32> {
+    coded_error(err).map(|err| err.code)
+}
+
+// error kinds that survive crossing the process boundary, others become `ErrorKind::Other`
+const ERROR_KINDS: [ErrorKind; 19] = [
+    ErrorKind::Other,
+    ErrorKind::NotFound,
+    ErrorKind::PermissionDenied,
+    ErrorKind::ConnectionRefused,
+    ErrorKind::ConnectionReset,
+    ErrorKind::ConnectionAborted,
+    ErrorKind::NotConnected,
+    ErrorKind::AddrInUse,
+    ErrorKind::AddrNotAvailable,
+    ErrorKind::BrokenPipe,
+    ErrorKind::AlreadyExists,
+    ErrorKind::WouldBlock,
+    ErrorKind::InvalidInput,
+    ErrorKind::InvalidData,
+    ErrorKind::TimedOut,
+    ErrorKind::WriteZero,
+    ErrorKind::Unsupported,
+    ErrorKind::UnexpectedEof,
+    ErrorKind::OutOfMemory,
+];
+
+/// Encodes error kind as a number for passing it across process boundary.
+pub(crate) fn kind_to_index(kind: ErrorKind) -> u32 {
+    ERROR_KINDS.iter().position(|known| *known == kind).unwrap_or(0) as u32
+}
+
+/// Decodes error kind encoded with `kind_to_index`.
+pub(crate) fn kind_from_index(index: u32) -> ErrorKind {
+    ERROR_KINDS.get(index as usize).copied().unwrap_or(ErrorKind::Other)
+}
@@ -0,0 +1,141 @@
+use std::collections::VecDeque;
+use std::io::{BufRead, Read, Write, Error as IoError};
+
+use crate::FusedReader;
+use crate::ring_pipe::PipeReader;
+
+/// Readers that can hand over their internal buffers for copying without an intermediate buffer.
+pub trait CopyBuffers {
+    /// Copies all data until EOF writing whole internal buffers to the `writer`.
+    ///
+    /// Returns number of bytes copied.
+    fn copy_buffers<W: Write + ?Sized>(&mut self, writer: &mut W) -> Result<u64, IoError>;
+}
+
+impl<R: BufRead> CopyBuffers for R {
+    fn copy_buffers<W: Write + ?Sized>(&mut self, writer: &mut W) -> Result<u64, IoError> {
+        let mut copied = 0;
+        loop {
+            let buf = self.fill_buf()?;
+            if buf.is_empty() {
+                return Ok(copied)
+            }
+            writer.write_all(buf)?;
+            let len = buf.len();
+            self.consume(len);
+            copied += len as u64;
+        }
+    }
+}
+
+impl CopyBuffers for PipeReader {
+    fn copy_buffers<W: Write + ?Sized>(&mut self, writer: &mut W) -> Result<u64, IoError> {
+        let mut copied = 0;
+        let mut spare = VecDeque::new();
+        loop {
+            let buf = self.swap_buffer(spare)?;
+            if buf.is_empty() {
+                return Ok(copied)
+            }
+            let (front, back) = buf.as_slices();
+            writer.write_all(front)?;
+            writer.write_all(back)?;
+            copied += buf.len() as u64;
+            spare = buf;
+        }
+    }
+}
+
+/// Copies all data from fused reader to the writer handing over whole buffers of the inner reader.
+///
+/// Unlike `std::io::copy` no intermediate buffer is used. The fuse is checked before copying, like
+/// with `read`, and after reaching EOF; the fuse error is returned if it was blown.
+pub fn fused_copy_buf<R, W>(reader: &mut FusedReader<R>, writer: &mut W) -> Result<u64, IoError>
+    where R: Read + CopyBuffers, W: Write + ?Sized {
+    reader.check_read()?;
+    let mut writer = Counted { writer, bytes: 0 };
+    let result = reader.reader.copy_buffers(&mut writer);
+    // data written before an error counts too
+    reader.advance(writer.bytes);
+    let copied = result?;
+    reader.finish_read(0).map(|_| copied)
+}
+
+/// Counts bytes written to the inner writer.
+struct Counted<'a, W: ?Sized> {
+    writer: &'a mut W,
+    bytes: u64,
+}
+
+impl<W: Write + ?Sized> Write for Counted<'_, W> {
+    fn write(&mut self, buf: &[u8]) -> Result<usize, IoError> {
+        let bytes = self.writer.write(buf)?;
+        self.bytes += bytes as u64;
+        Ok(bytes)
+    }
+
+    fn flush(&mut self) -> Result<(), IoError> {
+        self.writer.flush()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{fuse, ring_pipe};
+    use std::io::{BufReader, ErrorKind};
+    use std::thread;
+
+    #[test]
+    fn test_fused_copy_buf_bufread() {
+        let (reader, mut writer) = pipe::pipe();
+        let (mut reader, fuse) = fuse(BufReader::new(reader));
+
+        thread::spawn(move || {
+            let _fuse = fuse.arm().unwrap();
+            writer.write_all(&[1; 100]).unwrap();
+            panic!("boom");
+        });
+
+        let mut data = Vec::new();
+        assert_eq!(fused_copy_buf(&mut reader, &mut data).unwrap_err().kind(), ErrorKind::BrokenPipe);
+        assert_eq!(data, vec![1; 100]);
+    }
+
+    #[test]
+    fn test_fused_copy_buf_ring_pipe() {
+        let (reader, mut writer) = ring_pipe::pipe_with_capacity(7);
+        let (mut reader, fuse) = fuse(reader);
+
+        thread::spawn(move || {
+            let _fuse = fuse.arm().unwrap();
+            writer.write_all(&[1; 100]).unwrap();
+        });
+
+        let mut data = Vec::new();
+        assert_eq!(fused_copy_buf(&mut reader, &mut data).unwrap(), 100);
+        assert_eq!(data, vec![1; 100]);
+        assert_eq!(reader.position(), 100);
+    }
+
+    #[test]
+    fn test_fused_copy_buf_writer_error() {
+        let (mut reader, _fuse) = fuse(&[1; 100][..]);
+
+        let mut buf = [0; 10];
+        assert_eq!(fused_copy_buf(&mut reader, &mut &mut buf[..]).unwrap_err().kind(), ErrorKind::WriteZero);
+        assert_eq!(reader.position(), 10);
+    }
+
+    #[test]
+    fn test_fused_copy_buf_blow_reason() {
+        let (mut reader, fuse) = fuse(&[1; 10][..]);
+        reader.set_require_complete(true);
+        drop(fuse);
+
+        let mut data = Vec::new();
+        assert_eq!(fused_copy_buf(&mut reader, &mut data).unwrap_err().kind(), ErrorKind::UnexpectedEof);
+        assert_eq!(reader.blow_reason(), Some(&crate::BlowReason::Incomplete));
+        assert_eq!(data, vec![1; 10]);
+    }
+}
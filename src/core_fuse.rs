@@ -0,0 +1,314 @@
+use alloc::sync::Arc;
+use core::cell::UnsafeCell;
+use core::fmt;
+use core::sync::atomic::{AtomicU8, Ordering};
+
+const UNARMED: u8 = 0;
+const ARMED: u8 = 1;
+const COMPLETED: u8 = 2;
+const BLOWN: u8 = 3;
+const DROPPED: u8 = 4;
+// the reader end is taking the error
+const TAKING: u8 = 5;
+
+struct CoreShared<E> {
+    state: AtomicU8,
+    // written by the armed guard and taken by the reader end once `state` is `BLOWN`
+    error: UnsafeCell<Option<E>>,
+}
+
+// `error` is accessed by one end at a time as governed by `state`
+unsafe impl<E: Send> Sync for CoreShared<E> {}
+
+/// Status of `CoreFuse`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CoreStatus {
+    /// Fuse was not armed or guard was disarmed.
+    Unarmed,
+    /// Fuse armed.
+    Armed,
+    /// Writer signalled that the stream was written in full with `CoreFuseGuard::complete`.
+    Completed,
+    /// Fuse blown with custom error.
+    Blown,
+    /// Guard was dropped without being disarmed.
+    Dropped,
+}
+
+/// Error of the reader end of `CoreFuse`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum CoreFuseError<E> {
+    /// Fuse blown with custom error.
+    Blown(E),
+    /// Guard was dropped without being disarmed, e.g. writer task was aborted.
+    Dropped,
+}
+
+impl<E: fmt::Display> fmt::Display for CoreFuseError<E> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            CoreFuseError::Blown(err) => write!(f, "writer end failed: {}", err),
+            CoreFuseError::Dropped => f.write_str("writer end dropped while fuse was armed"),
+        }
+    }
+}
+
+/// Fuses `reader` with fuse not depending on `std`, for use with `core` and `alloc` only.
+///
+/// This is a separate minimal API rather than the state machine `Fuse` is built on, which stays
+/// `std` only: there is a single guard at a time and no TTL, cancellation or generations. The fuse
+/// needs atomics but no locks or thread support. Since writer panics can not be detected without
+/// `std` the guard must be released with `disarm`, `complete` or `blow`; dropping it otherwise
+/// fails the reader end like with `AsyncFuseGuard`. The error type is up to the caller.
+pub fn core_fuse<R, E>(reader: R) -> (CoreFusedReader<R, E>, CoreFuse<E>) {
+    let shared = Arc::new(CoreShared {
+        state: AtomicU8::new(UNARMED),
+        error: UnsafeCell::new(None),
+    });
+    (CoreFusedReader {
+            reader,
+            fuse: shared.clone(),
+        },
+        CoreFuse(shared),
+    )
+}
+
+fn status(state: &AtomicU8) -> CoreStatus {
+    match state.load(Ordering::Acquire) {
+        ARMED => CoreStatus::Armed,
+        COMPLETED => CoreStatus::Completed,
+        BLOWN | TAKING => CoreStatus::Blown,
+        DROPPED => CoreStatus::Dropped,
+        _ => CoreStatus::Unarmed,
+    }
+}
+
+/// Reader end of `CoreFuse`.
+///
+/// With the `embedded-io` feature it implements `embedded_io::Read` failing at the end of the
+/// stream if the fuse failed. Other integrations with I/O traits of the platform call `finish`
+/// once the inner reader reached the end of the stream.
+pub struct CoreFusedReader<R, E> {
+    reader: R,
+    fuse: Arc<CoreShared<E>>,
+}
+
+impl<R: fmt::Debug, E> fmt::Debug for CoreFusedReader<R, E> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("CoreFusedReader").field("reader", &self.reader).field("status", &self.status()).finish()
+    }
+}
+
+impl<R, E> CoreFusedReader<R, E> {
+    /// Checks status of the fuse.
+    pub fn status(&self) -> CoreStatus {
+        status(&self.fuse.state)
+    }
+
+    /// Returns error that the reader should fail with at the end of the stream, if any.
+    ///
+    /// The error the fuse was blown with is taken; the fuse is reported as `CoreStatus::Unarmed`
+    /// afterwards.
+    pub fn finish(&mut self) -> Result<(), CoreFuseError<E>> {
+        match self.fuse.state.compare_exchange(BLOWN, TAKING, Ordering::AcqRel, Ordering::Acquire) {
+            Ok(_) => {
+                // Safety: the guard stored the error before storing `BLOWN` and nobody else can
+                // access it until `state` is set again
+                let err = unsafe { (*self.fuse.error.get()).take() };
+                self.fuse.state.store(UNARMED, Ordering::Release);
+                err.map_or(Ok(()), |err| Err(CoreFuseError::Blown(err)))
+            }
+            Err(DROPPED) => Err(CoreFuseError::Dropped),
+            Err(_) => Ok(()),
+        }
+    }
+
+    /// Returns reference to the inner reader.
+    pub fn get_ref(&self) -> &R {
+        &self.reader
+    }
+
+    /// Returns mutable reference to the inner reader.
+    pub fn get_mut(&mut self) -> &mut R {
+        &mut self.reader
+    }
+
+    /// Returns inner reader.
+    pub fn into_inner(self) -> R {
+        self.reader
+    }
+}
+
+/// Error of `CoreFusedReader` read with `embedded_io::Read`.
+#[cfg(feature = "embedded-io")]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum CoreReadError<R, E> {
+    /// Inner reader failed.
+    Read(R),
+    /// Fuse failed at the end of the stream.
+    Fuse(CoreFuseError<E>),
+}
+
+#[cfg(feature = "embedded-io")]
+impl<R: embedded_io::Error, E: fmt::Debug> embedded_io::Error for CoreReadError<R, E> {
+    fn kind(&self) -> embedded_io::ErrorKind {
+        match self {
+            CoreReadError::Read(err) => err.kind(),
+            CoreReadError::Fuse(CoreFuseError::Blown(_)) => embedded_io::ErrorKind::Other,
+            CoreReadError::Fuse(CoreFuseError::Dropped) => embedded_io::ErrorKind::BrokenPipe,
+        }
+    }
+}
+
+#[cfg(feature = "embedded-io")]
+impl<R: embedded_io::ErrorType, E: fmt::Debug> embedded_io::ErrorType for CoreFusedReader<R, E> {
+    type Error = CoreReadError<R::Error, E>;
+}
+
+#[cfg(feature = "embedded-io")]
+impl<R: embedded_io::Read, E: fmt::Debug> embedded_io::Read for CoreFusedReader<R, E> {
+    fn read(&mut self, buf: &mut [u8]) -> Result<usize, Self::Error> {
+        let bytes = self.reader.read(buf).map_err(CoreReadError::Read)?;
+        if bytes == 0 && !buf.is_empty() {
+            self.finish().map_err(CoreReadError::Fuse)?;
+        }
+        Ok(bytes)
+    }
+}
+
+/// Fuse of `CoreFusedReader` that can be armed.
+pub struct CoreFuse<E>(Arc<CoreShared<E>>);
+
+impl<E> fmt::Debug for CoreFuse<E> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_tuple("CoreFuse").field(&self.status()).finish()
+    }
+}
+
+impl<E> CoreFuse<E> {
+    /// Arms the fuse.
+    ///
+    /// Never blocks; returns `None` if the fuse is already armed or the reader end did not get
+    /// the error of the previous guard yet.
+    pub fn arm(&self) -> Option<CoreFuseGuard<'_, E>> {
+        let state = &self.0.state;
+        [UNARMED, COMPLETED].iter()
+            .any(|&from| state.compare_exchange(from, ARMED, Ordering::AcqRel, Ordering::Acquire).is_ok())
+            .then(|| CoreFuseGuard { fuse: self, released: false })
+    }
+
+    /// Checks status of the fuse.
+    pub fn status(&self) -> CoreStatus {
+        status(&self.0.state)
+    }
+}
+
+/// Armed `CoreFuse`.
+///
+/// If dropped without calling `disarm`, `complete` or `blow` the reader end fails with
+/// `CoreFuseError::Dropped`.
+pub struct CoreFuseGuard<'a, E> {
+    fuse: &'a CoreFuse<E>,
+    released: bool,
+}
+
+impl<E> fmt::Debug for CoreFuseGuard<'_, E> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("CoreFuseGuard").field("fuse", self.fuse).finish()
+    }
+}
+
+impl<E> CoreFuseGuard<'_, E> {
+    /// Disarms the fuse signalling that the writer finished successfully.
+    pub fn disarm(self) {
+        self.release(UNARMED);
+    }
+
+    /// Disarms the fuse signalling that the stream was written in full.
+    pub fn complete(self) {
+        self.release(COMPLETED);
+    }
+
+    /// Blows the fuse with given error.
+    ///
+    /// The reader end will fail with this error at the end of the stream.
+    pub fn blow(self, err: E) {
+        // Safety: the reader end only accesses the error once `state` is `BLOWN`
+        unsafe { *self.fuse.0.error.get() = Some(err) };
+        self.release(BLOWN);
+    }
+
+    fn release(mut self, state: u8) {
+        self.released = true;
+        self.fuse.0.state.store(state, Ordering::Release);
+    }
+}
+
+impl<E> Drop for CoreFuseGuard<'_, E> {
+    fn drop(&mut self) {
+        if !self.released {
+            self.fuse.0.state.store(DROPPED, Ordering::Release);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::thread;
+
+    #[test]
+    fn test_core_fuse_blow() {
+        let (mut reader, fuse) = core_fuse::<_, &str>([1, 2, 3]);
+        thread::scope(|scope| {
+            scope.spawn(|| fuse.arm().unwrap().blow("uh! oh!"));
+        });
+
+        assert_eq!(reader.status(), CoreStatus::Blown);
+        assert!(fuse.arm().is_none());
+        assert_eq!(reader.finish(), Err(CoreFuseError::Blown("uh! oh!")));
+        assert_eq!(reader.finish(), Ok(()));
+        assert_eq!(reader.into_inner(), [1, 2, 3]);
+    }
+
+    #[test]
+    fn test_core_fuse_dropped() {
+        let (mut reader, fuse) = core_fuse::<_, ()>(());
+        let guard = fuse.arm().unwrap();
+        assert!(fuse.arm().is_none());
+        drop(guard);
+
+        assert_eq!(fuse.status(), CoreStatus::Dropped);
+        assert_eq!(reader.finish(), Err(CoreFuseError::Dropped));
+    }
+
+    #[cfg(feature = "embedded-io")]
+    #[test]
+    fn test_core_fuse_embedded_io() {
+        use embedded_io::{Error as _, ErrorKind, Read};
+
+        let (mut reader, fuse) = core_fuse::<_, &str>(&[1, 2][..]);
+        fuse.arm().unwrap().blow("uh! oh!");
+
+        let mut buf = [0; 4];
+        assert_eq!(reader.read(&mut buf).unwrap(), 2);
+        let err = reader.read(&mut buf).unwrap_err();
+        assert_eq!(err, CoreReadError::Fuse(CoreFuseError::Blown("uh! oh!")));
+        assert_eq!(err.kind(), ErrorKind::Other);
+        assert_eq!(reader.read(&mut buf).unwrap(), 0);
+
+        let (mut reader, fuse) = core_fuse::<_, ()>(&[][..]);
+        drop(fuse.arm().unwrap());
+        assert_eq!(reader.read(&mut buf).unwrap_err().kind(), ErrorKind::BrokenPipe);
+    }
+
+    #[test]
+    fn test_core_fuse_complete() {
+        let (mut reader, fuse) = core_fuse::<_, ()>(());
+        fuse.arm().unwrap().complete();
+        assert_eq!(reader.status(), CoreStatus::Completed);
+        assert_eq!(reader.finish(), Ok(()));
+        fuse.arm().unwrap().disarm();
+        assert_eq!(fuse.status(), CoreStatus::Unarmed);
+    }
+}
@@ -0,0 +1,355 @@
+use std::io::{Read, Error as IoError, ErrorKind};
+use std::net::TcpStream;
+#[cfg(unix)]
+use std::os::unix::net::UnixStream;
+use std::time::{Duration, Instant};
+
+use crate::FusedReader;
+use crate::ring_pipe::PipeReader;
+
+/// How often `FusedReader::wait_available` checks if the fuse got blown while waiting.
+const FUSE_CHECK_INTERVAL: Duration = Duration::from_millis(10);
+
+/// Reader backends which blocking reads can be interrupted with a timeout.
+pub trait ReadTimeout {
+    /// Returns currently set read timeout.
+    fn read_timeout(&self) -> Result<Option<Duration>, IoError>;
+
+    /// Sets read timeout; `None` blocks indefinitely.
+    fn set_read_timeout(&self, timeout: Option<Duration>) -> Result<(), IoError>;
+}
+
+impl ReadTimeout for TcpStream {
+    fn read_timeout(&self) -> Result<Option<Duration>, IoError> {
+        TcpStream::read_timeout(self)
+    }
+
+    fn set_read_timeout(&self, timeout: Option<Duration>) -> Result<(), IoError> {
+        TcpStream::set_read_timeout(self, timeout)
+    }
+}
+
+#[cfg(unix)]
+impl ReadTimeout for UnixStream {
+    fn read_timeout(&self) -> Result<Option<Duration>, IoError> {
+        UnixStream::read_timeout(self)
+    }
+
+    fn set_read_timeout(&self, timeout: Option<Duration>) -> Result<(), IoError> {
+        UnixStream::set_read_timeout(self, timeout)
+    }
+}
+
+impl ReadTimeout for PipeReader {
+    fn read_timeout(&self) -> Result<Option<Duration>, IoError> {
+        Ok(PipeReader::read_timeout(self))
+    }
+
+    fn set_read_timeout(&self, timeout: Option<Duration>) -> Result<(), IoError> {
+        PipeReader::set_read_timeout(self, timeout);
+        Ok(())
+    }
+}
+
+/// Reader backends that can wait until given number of bytes is buffered.
+pub trait WaitAvailable {
+    /// Blocks until at least `bytes` are buffered or EOF is reached, returning number of buffered bytes.
+    ///
+    /// Fails with `TimedOut` error if `timeout` elapses first.
+    fn wait_available(&self, bytes: usize, timeout: Duration) -> Result<usize, IoError>;
+}
+
+impl WaitAvailable for PipeReader {
+    fn wait_available(&self, bytes: usize, timeout: Duration) -> Result<usize, IoError> {
+        PipeReader::wait_available(self, bytes, timeout)
+    }
+}
+
+impl<R: Read + WaitAvailable> FusedReader<R> {
+    /// Blocks until at least `bytes` are available for reading without blocking, returning number
+    /// of available bytes.
+    ///
+    /// If EOF is reached before that the fuse is checked and its error returned if it was blown;
+    /// otherwise fewer bytes than requested are reported as available. The fuse is also checked
+    /// while waiting so the fuse error is returned soon after the fuse got blown, even if the
+    /// writer keeps the stream open. Fails with `TimedOut` error if `timeout` elapses first.
+    pub fn wait_available(&mut self, bytes: usize, timeout: Duration) -> Result<usize, IoError> {
+        self.check_read()?;
+        let deadline = Instant::now() + timeout;
+        loop {
+            let wait = deadline.saturating_duration_since(Instant::now()).min(FUSE_CHECK_INTERVAL);
+            match self.reader.wait_available(bytes, wait) {
+                Ok(available) => {
+                    if available < bytes {
+                        if let Some(err) = self.eof_error() {
+                            return Err(self.fuse_failed(err))
+                        }
+                    }
+                    return Ok(available)
+                }
+                Err(err) if err.kind() == ErrorKind::TimedOut => {
+                    if let Some(err) = self.eof_error() {
+                        return Err(self.fuse_failed(err))
+                    }
+                    if Instant::now() >= deadline {
+                        return Err(err)
+                    }
+                }
+                Err(err) => return Err(err),
+            }
+        }
+    }
+}
+
+impl<R: Read + ReadTimeout> FusedReader<R> {
+    /// Fails reads with `TimedOut` error if neither data nor `FuseGuard::heartbeat` of the writer
+    /// arrived within `timeout`.
+    ///
+    /// This detects writers that hung without blowing the fuse. The read timeout of the inner
+    /// reader is set to `timeout` so blocked read can notice the writer stalled about a `timeout`
+    /// late. If the fuse was blown the fuse error is returned instead.
+    pub fn with_timeout(mut self, timeout: Duration) -> Result<FusedReader<R>, IoError> {
+        self.reader.set_read_timeout(Some(timeout))?;
+        self.timeout = Some(timeout);
+        self.progress = Instant::now();
+        Ok(self)
+    }
+
+    /// Reads exact number of bytes required to fill `buf` failing with `TimedOut` error if they
+    /// were not delivered before the `deadline`.
+    ///
+    /// If the fuse was blown by the time deadline is reached the fuse error is returned instead.
+    /// Read timeout of the inner reader is restored before returning.
+    pub fn read_exact_deadline(&mut self, buf: &mut [u8], deadline: Instant) -> Result<(), IoError> {
+        let timeout = self.reader.read_timeout()?;
+        let res = self.read_exact_until(buf, deadline);
+        self.reader.set_read_timeout(timeout)?;
+        res
+    }
+
+    fn read_exact_until(&mut self, mut buf: &mut [u8], deadline: Instant) -> Result<(), IoError> {
+        while !buf.is_empty() {
+            if Instant::now() >= deadline {
+                return Err(self.timed_out())
+            }
+
+            // retries of reads timed out by `with_timeout` must not run past the deadline
+            let res = self.read_with_deadline(Some(deadline), |reader| {
+                let remaining = deadline.saturating_duration_since(Instant::now());
+                if remaining == Duration::from_secs(0) {
+                    return Err(IoError::new(ErrorKind::TimedOut, "deadline reached"))
+                }
+                reader.set_read_timeout(Some(remaining))?;
+                reader.read(buf)
+            });
+            match res {
+                Ok(0) => return Err(IoError::new(ErrorKind::UnexpectedEof, "failed to fill whole buffer")),
+                Ok(bytes) => buf = &mut buf[bytes..],
+                Err(err) if err.kind() == ErrorKind::Interrupted => (),
+                Err(err) if err.kind() == ErrorKind::WouldBlock || err.kind() == ErrorKind::TimedOut => (),
+                Err(err) => return Err(err),
+            }
+        }
+        Ok(())
+    }
+
+    fn timed_out(&mut self) -> IoError {
+        self.eof_error()
+            .unwrap_or_else(|| IoError::new(ErrorKind::TimedOut, "deadline reached before buffer was filled"))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{fuse, fuse_with, ring_pipe, FusePolicy};
+    use std::io::Write;
+    use std::sync::mpsc::channel;
+    use std::thread;
+
+    #[test]
+    fn test_wait_available() {
+        let (reader, mut writer) = ring_pipe::pipe();
+        let (mut reader, fuse) = fuse(reader);
+        let (done_tx, done_rx) = channel::<()>();
+
+        thread::spawn(move || {
+            let fuse = fuse.arm().unwrap();
+            writer.write_all(&[1, 2]).unwrap();
+            thread::sleep(Duration::from_millis(10));
+            writer.write_all(&[3, 4]).unwrap();
+            done_rx.recv().ok();
+            fuse.blow(IoError::new(ErrorKind::InvalidData, "uh! oh!"));
+        });
+
+        assert_eq!(reader.wait_available(4, Duration::from_secs(10)).unwrap(), 4);
+        assert_eq!(reader.wait_available(5, Duration::from_millis(10)).unwrap_err().kind(), ErrorKind::TimedOut);
+        drop(done_tx);
+        assert_eq!(reader.wait_available(5, Duration::from_secs(10)).unwrap_err().kind(), ErrorKind::InvalidData);
+    }
+
+    #[test]
+    fn test_wait_available_blown_while_open() {
+        let (reader, mut writer) = ring_pipe::pipe();
+        let (mut reader, fuse) = fuse(reader);
+
+        let writer = thread::spawn(move || {
+            let guard = fuse.arm().unwrap();
+            writer.write_all(&[1]).unwrap();
+            thread::sleep(Duration::from_millis(10));
+            guard.blow(IoError::new(ErrorKind::InvalidData, "uh! oh!"));
+            // pipe is kept open
+            thread::sleep(Duration::from_secs(1));
+            drop(writer);
+        });
+
+        let start = Instant::now();
+        assert_eq!(reader.wait_available(2, Duration::from_secs(10)).unwrap_err().kind(), ErrorKind::InvalidData);
+        assert!(start.elapsed() < Duration::from_millis(500));
+        writer.join().unwrap();
+    }
+
+    #[test]
+    fn test_wait_available_fail_fast() {
+        let (reader, _writer) = ring_pipe::pipe();
+        let (mut reader, fuse) = fuse_with(reader, FusePolicy::FailFast);
+
+        let other = fuse.clone();
+        let _guard = other.arm().unwrap();
+        fuse.arm().unwrap().blow(IoError::new(ErrorKind::InvalidData, "uh! oh!"));
+
+        let start = Instant::now();
+        assert_eq!(reader.wait_available(1, Duration::from_secs(10)).unwrap_err().kind(), ErrorKind::InvalidData);
+        assert!(start.elapsed() < Duration::from_millis(500));
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_read_exact_deadline() {
+        let (reader, mut writer) = UnixStream::pair().unwrap();
+        let (mut reader, fuse) = fuse(reader);
+
+        thread::spawn(move || {
+            let _fuse = fuse.arm().unwrap();
+            writer.write_all(&[1, 2]).unwrap();
+        });
+
+        let mut buf = [0; 2];
+        reader.read_exact_deadline(&mut buf, Instant::now() + Duration::from_secs(10)).unwrap();
+        assert_eq!(&buf, &[1, 2]);
+        assert_eq!(reader.into_inner().read_timeout().unwrap(), None);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_read_exact_deadline_timed_out() {
+        let (reader, mut writer) = UnixStream::pair().unwrap();
+        let (mut reader, fuse) = fuse(reader);
+        let (done_tx, done_rx) = channel::<()>();
+
+        thread::spawn(move || {
+            let _fuse = fuse.arm().unwrap();
+            writer.write_all(&[1]).unwrap();
+            done_rx.recv().ok();
+        });
+
+        let mut buf = [0; 2];
+        let err = reader.read_exact_deadline(&mut buf, Instant::now() + Duration::from_millis(50)).unwrap_err();
+        assert_eq!(err.kind(), ErrorKind::TimedOut);
+        drop(done_tx);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_with_timeout() {
+        let (reader, mut writer) = UnixStream::pair().unwrap();
+        let (reader, fuse) = fuse(reader);
+        let mut reader = reader.with_timeout(Duration::from_millis(50)).unwrap();
+        let (done_tx, done_rx) = channel::<()>();
+
+        thread::spawn(move || {
+            let fuse = fuse.arm().unwrap();
+            for _ in 0..5 {
+                thread::sleep(Duration::from_millis(20));
+                fuse.heartbeat();
+            }
+            writer.write_all(&[1]).unwrap();
+            // writer hangs
+            done_rx.recv().ok();
+        });
+
+        let mut buf = [0; 1];
+        assert_eq!(reader.read(&mut buf).unwrap(), 1);
+        let err = reader.read(&mut buf).unwrap_err();
+        assert_eq!(err.kind(), ErrorKind::TimedOut);
+        assert_eq!(reader.blow_reason(), Some(&crate::BlowReason::Timeout));
+        drop(done_tx);
+    }
+
+    #[test]
+    fn test_read_exact_deadline_with_timeout() {
+        let (reader, mut writer) = ring_pipe::pipe();
+        let (reader, fuse) = fuse(reader);
+        let mut reader = reader.with_timeout(Duration::from_secs(10)).unwrap();
+        let (done_tx, done_rx) = channel::<()>();
+
+        thread::spawn(move || {
+            let _fuse = fuse.arm().unwrap();
+            writer.write_all(&[1]).unwrap();
+            done_rx.recv().ok();
+        });
+
+        let start = Instant::now();
+        let mut buf = [0; 2];
+        let err = reader.read_exact_deadline(&mut buf, Instant::now() + Duration::from_millis(50)).unwrap_err();
+        assert_eq!(err.kind(), ErrorKind::TimedOut);
+        assert!(start.elapsed() < Duration::from_secs(5));
+        assert_eq!(ReadTimeout::read_timeout(&reader.into_inner()).unwrap(), Some(Duration::from_secs(10)));
+        drop(done_tx);
+    }
+
+    #[test]
+    fn test_with_timeout_ring_pipe() {
+        let (reader, mut writer) = ring_pipe::pipe();
+        let (reader, fuse) = fuse(reader);
+        let mut reader = reader.with_timeout(Duration::from_millis(50)).unwrap();
+        let (done_tx, done_rx) = channel::<()>();
+
+        thread::spawn(move || {
+            let _fuse = fuse.arm().unwrap();
+            writer.write_all(&[1]).unwrap();
+            // writer hangs
+            done_rx.recv().ok();
+        });
+
+        let mut buf = [0; 1];
+        assert_eq!(reader.read(&mut buf).unwrap(), 1);
+        assert_eq!(reader.read(&mut buf).unwrap_err().kind(), ErrorKind::TimedOut);
+        assert_eq!(reader.blow_reason(), Some(&crate::BlowReason::Timeout));
+        drop(done_tx);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_read_exact_deadline_blown() {
+        let (reader, mut writer) = UnixStream::pair().unwrap();
+        let (mut reader, fuse) = fuse(reader);
+        let (done_tx, done_rx) = channel::<()>();
+        let (blown_tx, blown_rx) = channel();
+
+        thread::spawn(move || {
+            let fuse = fuse.arm().unwrap();
+            writer.write_all(&[1]).unwrap();
+            fuse.blow(IoError::new(ErrorKind::InvalidData, "uh! oh!"));
+            blown_tx.send(()).unwrap();
+            // keep the stream open so that reader won't see EOF
+            done_rx.recv().ok();
+        });
+        blown_rx.recv().unwrap();
+
+        let mut buf = [0; 2];
+        let err = reader.read_exact_deadline(&mut buf, Instant::now() + Duration::from_millis(50)).unwrap_err();
+        assert_eq!(err.kind(), ErrorKind::InvalidData);
+        drop(done_tx);
+    }
+}
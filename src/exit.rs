@@ -0,0 +1,187 @@
+use std::io::{Write, Error as IoError, ErrorKind};
+use std::panic::{self, UnwindSafe};
+use std::process::{self, ExitStatus};
+
+use crate::{coded_error, install_panic_hook, BlowReason, CodedError, FuseError, PanicLocation};
+use crate::panic::{panic_message, take_panic};
+use crate::code::{kind_from_index, kind_to_index};
+
+const RECORD_PREFIX: &str = "fused-reader-error\t";
+
+/// Exit codes used by `producer_main` to report failures of producer process.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ExitCodes {
+    /// Exit code used when the producer returned an error.
+    pub error: i32,
+    /// Exit code used when the producer panicked.
+    pub panic: i32,
+}
+
+impl Default for ExitCodes {
+    fn default() -> ExitCodes {
+        ExitCodes {
+            error: 1,
+            panic: 101,
+        }
+    }
+}
+
+/// Runs `main` of a producer process and exits the process with its outcome.
+///
+/// Exits with code `0` if `main` succeeded. Otherwise writes a single line error record to `record`
+/// (usually standard error) and exits with the corresponding code from `codes`. The parent process
+/// can turn the exit status and the record into an error with `decode_exit`.
+///
+/// Installs the panic hook (see `install_panic_hook`) so that panic records include location of
+/// the panic.
+pub fn producer_main<W, F>(codes: ExitCodes, mut record: W, main: F) -> !
+    where W: Write, F: FnOnce() -> Result<(), IoError> + UnwindSafe {
+    install_panic_hook();
+    let code = match panic::catch_unwind(main) {
+        Ok(Ok(())) => process::exit(0),
+        Ok(Err(err)) => {
+            write_record(&mut record, "error", &err, None);
+            codes.error
+        }
+        Err(payload) => {
+            let err = IoError::new(ErrorKind::BrokenPipe, panic_message(&*payload));
+            write_record(&mut record, "panic", &err, take_panic().location);
+            codes.panic
+        }
+    };
+    let _ = record.flush();
+    process::exit(code)
+}
+
+fn write_record<W: Write>(record: &mut W, outcome: &str, err: &IoError, location: Option<PanicLocation>) {
+    let (code, message) = match coded_error(err) {
+        Some(coded) => (coded.code.to_string(), coded.message.clone()),
+        None => (String::new(), err.to_string()),
+    };
+    let location = location.map(|location| location.to_string()).unwrap_or_default();
+    let field = |value: &str| value.replace(['\t', '\n'], " ");
+    let _ = writeln!(record, "{}{}\t{}\t{}\t{}\t{}", RECORD_PREFIX, outcome, kind_to_index(err.kind()), code, field(&location), field(&message));
+}
+
+/// Turns exit status of a producer process run with `producer_main` and its captured record
+/// stream into an error for the reader end.
+///
+/// Returns `None` if the process succeeded. Returned error carries kind, message and failure code
+/// (see `error_code`) of the producer error; panics are reported as `BrokenPipe` error with
+/// `BlowReason::Panic`. If no record was found the error describes the exit status.
+pub fn decode_exit(status: ExitStatus, output: &[u8]) -> Option<IoError> {
+    if status.success() {
+        return None
+    }
+
+    Some(decode_record(output).unwrap_or_else(|| FuseError::explicit(IoError::other(format!("producer process failed with {}", status)))))
+}
+
+/// Decodes the last error record found in the output of a producer process.
+pub(crate) fn decode_record(output: &[u8]) -> Option<IoError> {
+    let output = String::from_utf8_lossy(output);
+    let record = output.lines().rev().find_map(|line| line.strip_prefix(RECORD_PREFIX));
+    record.and_then(parse_record)
+}
+
+fn parse_record(record: &str) -> Option<IoError> {
+    let mut fields = record.splitn(5, '\t');
+    let outcome = fields.next()?;
+    let kind = kind_from_index(fields.next()?.parse().ok()?);
+    let code = fields.next()?;
+    let location = parse_location(fields.next()?);
+    let message = fields.next()?.to_owned();
+
+    Some(match (outcome, code.parse()) {
+        ("panic", _) => {
+            let description = match &location {
+                Some(location) => format!("producer process panicked at {}: {}", location, message),
+                None => format!("producer process panicked: {}", message),
+            };
+            FuseError::new_io(ErrorKind::BrokenPipe, BlowReason::Panic { msg: Some(message), location }, description)
+        }
+        (_, Ok(code)) => FuseError::explicit(CodedError::into_io_error(code, message)),
+        (_, Err(_)) => FuseError::explicit(IoError::new(kind, message)),
+    })
+}
+
+fn parse_location(location: &str) -> Option<PanicLocation> {
+    let mut parts = location.rsplitn(3, ':');
+    let column = parts.next()?.parse().ok()?;
+    let line = parts.next()?.parse().ok()?;
+    let file = parts.next()?.to_owned();
+    Some(PanicLocation { file, line, column })
+}
+
+#[cfg(all(test, unix))]
+mod tests {
+    use super::*;
+    use crate::{child_process_command, child_process_test, error_code, extract_fuse_error};
+    use std::os::unix::process::ExitStatusExt;
+    use std::process::Stdio;
+
+    const CHILD_OUTCOME: &str = "FUSED_READER_EXIT_CHILD_OUTCOME";
+
+    fn run_producer(outcome: &str) -> (ExitStatus, Vec<u8>) {
+        let output = child_process_command("exit::tests::exit_producer", CHILD_OUTCOME, outcome)
+            .arg("--nocapture")
+            .stdout(Stdio::null())
+            .stderr(Stdio::piped())
+            .output()
+            .unwrap();
+        (output.status, output.stderr)
+    }
+
+    #[test]
+    fn test_decode_exit() {
+        let (status, output) = run_producer("ok");
+        assert!(status.success());
+        assert!(decode_exit(status, &output).is_none());
+
+        let (status, output) = run_producer("error");
+        assert_eq!(status.code(), Some(3));
+        let err = decode_exit(status, &output).unwrap();
+        assert_eq!(err.kind(), ErrorKind::InvalidData);
+        assert_eq!(err.to_string(), "uh! oh!");
+
+        let (status, output) = run_producer("code");
+        let err = decode_exit(status, &output).unwrap();
+        assert_eq!(error_code(&err), Some(42));
+
+        let (status, output) = run_producer("panic");
+        assert_eq!(status.code(), Some(4));
+        let err = decode_exit(status, &output).unwrap();
+        assert_eq!(err.kind(), ErrorKind::BrokenPipe);
+        match extract_fuse_error(&err).unwrap().reason() {
+            BlowReason::Panic { msg, location } => {
+                assert_eq!(msg.as_deref(), Some("boom"));
+                assert_eq!(location.as_ref().unwrap().file, file!());
+            }
+            reason => panic!("unexpected reason: {:?}", reason),
+        }
+    }
+
+    #[test]
+    fn test_decode_exit_without_record() {
+        let err = decode_exit(ExitStatus::from_raw(2 << 8), b"some output\n").unwrap();
+        assert_eq!(err.kind(), ErrorKind::Other);
+    }
+
+    // run in child process by `test_decode_exit`
+    #[test]
+    #[ignore]
+    fn exit_producer() {
+        let outcome = match child_process_test(CHILD_OUTCOME) {
+            Some(outcome) => outcome.into_string().unwrap(),
+            None => return,
+        };
+        producer_main(ExitCodes { error: 3, panic: 4 }, std::io::stderr(), move || {
+            match outcome.as_str() {
+                "error" => Err(IoError::new(ErrorKind::InvalidData, "uh! oh!")),
+                "code" => Err(CodedError::into_io_error(42, "bad input".to_owned())),
+                "panic" => panic!("boom"),
+                _ => Ok(()),
+            }
+        })
+    }
+}
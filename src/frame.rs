@@ -0,0 +1,217 @@
+use std::io::{Read, Write, IoSlice, Error as IoError, ErrorKind};
+use std::convert::TryFrom;
+use std::ops::Deref;
+
+use crate::{FusedReader, FuseStatus};
+
+/// Default maximum size of a frame accepted by `Frames` in bytes.
+pub const DEFAULT_MAX_FRAME_SIZE: usize = 16 * 1024 * 1024;
+
+/// Writer of length prefixed frames.
+///
+/// Each frame is prefixed with its length as big-endian `u32`.
+#[derive(Debug)]
+pub struct FrameWriter<W: Write> {
+    writer: W,
+}
+
+impl<W: Write> FrameWriter<W> {
+    /// Wraps writer so that it writes discrete frames.
+    pub fn new(writer: W) -> FrameWriter<W> {
+        FrameWriter { writer }
+    }
+
+    /// Writes single frame made of all given slices with vectored writes.
+    ///
+    /// Fails with `InvalidInput` error if the frame is longer than `u32::MAX` bytes.
+    pub fn write_frame(&mut self, parts: &[IoSlice<'_>]) -> Result<(), IoError> {
+        let len: usize = parts.iter().map(|part| part.len()).sum();
+        let header = u32::try_from(len)
+            .map_err(|_| IoError::new(ErrorKind::InvalidInput, "frame is too long"))?
+            .to_be_bytes();
+
+        let mut bufs: Vec<IoSlice<'_>> = Some(IoSlice::new(&header)).into_iter().chain(parts.iter().copied()).collect();
+        let mut bufs = &mut bufs[..];
+        while !bufs.is_empty() {
+            match self.writer.write_vectored(bufs) {
+                Ok(0) => return Err(IoError::new(ErrorKind::WriteZero, "failed to write whole frame")),
+                Ok(bytes) => IoSlice::advance_slices(&mut bufs, bytes),
+                Err(err) if err.kind() == ErrorKind::Interrupted => (),
+                Err(err) => return Err(err),
+            }
+        }
+        Ok(())
+    }
+
+    /// Returns mutable reference to the inner writer.
+    pub fn get_mut(&mut self) -> &mut W {
+        &mut self.writer
+    }
+
+    /// Returns inner writer.
+    pub fn into_inner(self) -> W {
+        self.writer
+    }
+}
+
+/// Frame read by `Frames`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Frame(Vec<u8>);
+
+impl Frame {
+    /// Returns data of the frame.
+    pub fn into_vec(self) -> Vec<u8> {
+        self.0
+    }
+}
+
+impl Deref for Frame {
+    type Target = [u8];
+
+    fn deref(&self) -> &[u8] {
+        &self.0
+    }
+}
+
+impl<R: Read> FusedReader<R> {
+    /// Converts into iterator of frames written with `FrameWriter`.
+    ///
+    /// Iteration ends after the stream ended at frame boundary or after first error; if the fuse
+    /// was blown its error is the last item.
+    pub fn frames(self) -> Frames<R> {
+        Frames {
+            reader: self,
+            buf: Vec::new(),
+            max_frame_size: DEFAULT_MAX_FRAME_SIZE,
+            done: false,
+        }
+    }
+}
+
+/// Iterator of frames read from fused reader.
+#[derive(Debug)]
+pub struct Frames<R: Read> {
+    reader: FusedReader<R>,
+    buf: Vec<u8>,
+    max_frame_size: usize,
+    done: bool,
+}
+
+impl<R: Read> Frames<R> {
+    /// Sets maximum size of accepted frame in bytes; longer frames fail with `InvalidData` error.
+    pub fn set_max_frame_size(&mut self, max_frame_size: usize) {
+        self.max_frame_size = max_frame_size;
+    }
+
+    /// Reads next frame into internal buffer returning reference to it.
+    ///
+    /// Unlike `next` this does not allocate for every frame.
+    pub fn next_frame(&mut self) -> Option<Result<&[u8], IoError>> {
+        if self.done {
+            return None
+        }
+        match self.read_frame() {
+            Ok(true) => Some(Ok(&self.buf)),
+            Ok(false) => {
+                self.done = true;
+                None
+            }
+            Err(err) => {
+                self.done = true;
+                Some(Err(err))
+            }
+        }
+    }
+
+    /// Checks status of the fuse; see `FusedReader::check_fuse`.
+    pub fn check_fuse(&self) -> FuseStatus {
+        self.reader.check_fuse()
+    }
+
+    /// Returns inner fused reader.
+    pub fn into_inner(self) -> FusedReader<R> {
+        self.reader
+    }
+
+    /// Reads next frame into the buffer returning `false` if the stream ended at frame boundary.
+    fn read_frame(&mut self) -> Result<bool, IoError> {
+        let mut header = [0; 4];
+        let mut filled = 0;
+        while filled < header.len() {
+            match self.reader.read(&mut header[filled..]) {
+                Ok(0) if filled == 0 => return Ok(false),
+                Ok(0) => return Err(IoError::new(ErrorKind::UnexpectedEof, "stream ended in the middle of a frame")),
+                Ok(bytes) => filled += bytes,
+                Err(err) if err.kind() == ErrorKind::Interrupted => (),
+                Err(err) => return Err(err),
+            }
+        }
+
+        let len = u32::from_be_bytes(header) as usize;
+        if len > self.max_frame_size {
+            return Err(IoError::new(ErrorKind::InvalidData, format!("frame of {} bytes exceeds maximum frame size", len)))
+        }
+        self.buf.resize(len, 0);
+        self.reader.read_exact(&mut self.buf)
+            .map_err(|err| match err.kind() {
+                ErrorKind::UnexpectedEof => IoError::new(ErrorKind::UnexpectedEof, "stream ended in the middle of a frame"),
+                _ => err,
+            })?;
+        Ok(true)
+    }
+}
+
+impl<R: Read> Iterator for Frames<R> {
+    type Item = Result<Frame, IoError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.next_frame().map(|frame| frame.map(|frame| Frame(frame.to_vec())))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{fuse, ring_pipe};
+    use std::thread;
+
+    #[test]
+    fn test_frames() {
+        let (reader, writer) = ring_pipe::pipe_with_capacity(5);
+        let (reader, fuse) = fuse(reader);
+
+        thread::spawn(move || {
+            let _fuse = fuse.arm().unwrap();
+            let mut writer = FrameWriter::new(writer);
+            writer.write_frame(&[IoSlice::new(b"hello"), IoSlice::new(b" "), IoSlice::new(b"world")]).unwrap();
+            writer.write_frame(&[]).unwrap();
+            writer.write_frame(&[IoSlice::new(b"bye")]).unwrap();
+        });
+
+        let frames: Vec<Frame> = reader.frames().collect::<Result<_, _>>().unwrap();
+        assert_eq!(frames.len(), 3);
+        assert_eq!(&*frames[0], b"hello world");
+        assert_eq!(&*frames[1], b"");
+        assert_eq!(frames[2].clone().into_vec(), b"bye".to_vec());
+    }
+
+    #[test]
+    fn test_frames_panic() {
+        let (reader, writer) = ring_pipe::pipe();
+        let (reader, fuse) = fuse(reader);
+
+        thread::spawn(move || {
+            let mut writer = FrameWriter::new(writer);
+            // fuse guard is dropped before the writer closes the pipe
+            let _fuse = fuse.arm().unwrap();
+            writer.write_frame(&[IoSlice::new(b"hello")]).unwrap();
+            writer.get_mut().write_all(&[0, 0, 0, 10, 1]).unwrap();
+            panic!("boom");
+        });
+
+        let mut frames = reader.frames();
+        assert_eq!(frames.next_frame().unwrap().unwrap(), b"hello");
+        assert_eq!(frames.next_frame().unwrap().unwrap_err().kind(), ErrorKind::BrokenPipe);
+        assert!(frames.next_frame().is_none());
+    }
+}
@@ -0,0 +1,230 @@
+use std::io::{Read, Error as IoError, ErrorKind};
+use std::sync::{Arc, Mutex};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::thread::{self, JoinHandle};
+
+use crate::{fuse, BlowReason, FuseError, FuseGuard, FuseState, FusedReader, BLOWN};
+
+#[derive(Debug, Default)]
+struct GroupShared {
+    fuses: Mutex<Vec<FuseState>>,
+    failed: AtomicBool,
+}
+
+impl GroupShared {
+    fn fuses(&self) -> Vec<FuseState> {
+        self.fuses.lock().unwrap_or_else(|err| err.into_inner()).clone()
+    }
+
+    /// Marks the group failed, blows fuses of all the producers but the `failed` one and cancels
+    /// them.
+    fn fail(&self, failed: &FuseState) {
+        self.failed.store(true, Ordering::Release);
+        for fuse in self.fuses().iter().filter(|fuse| !Arc::ptr_eq(fuse, failed)) {
+            fuse.set_error(fuse.generation(), sibling_failed_error(), BLOWN);
+        }
+        self.cancel();
+    }
+
+    fn cancel(&self) {
+        for fuse in self.fuses() {
+            fuse.cancel(None);
+        }
+    }
+}
+
+fn sibling_failed_error() -> IoError {
+    FuseError::new_io(ErrorKind::BrokenPipe, BlowReason::SiblingFailed, "other producer of the task group failed")
+}
+
+/// Fails the group if the producer thread unwinds.
+struct FailOnPanic<'a>(&'a GroupShared, &'a FuseState);
+
+impl Drop for FailOnPanic<'_> {
+    fn drop(&mut self) {
+        if thread::panicking() {
+            self.0.fail(self.1);
+        }
+    }
+}
+
+/// Group of producer threads feeding fused readers of one pipeline that fail together.
+///
+/// Each producer runs with its fuse armed. The first producer that fails, with an error or by
+/// panicking, blows fuses of all the others with `BrokenPipe` error with
+/// `BlowReason::SiblingFailed` and cancels them; see `FuseGuard::is_cancelled`. Readers with
+/// `FusePolicy::FailFast` fail right away, even if their producer is blocked, others once their
+/// producer releases the fuse, so either the whole pipeline completes or every stream still in
+/// flight fails.
+///
+/// Dropping the group cancels the producers still running without waiting for them, as a
+/// producer blocked writing to a reader that is still alive would never finish; use `join` to wait.
+#[derive(Debug, Default)]
+pub struct FusedTaskGroup {
+    shared: Arc<GroupShared>,
+    threads: Vec<JoinHandle<()>>,
+}
+
+impl FusedTaskGroup {
+    /// Creates empty group.
+    pub fn new() -> FusedTaskGroup {
+        FusedTaskGroup::default()
+    }
+
+    /// Spawns producer thread writing to `writer` the stream read by returned fused reader.
+    ///
+    /// The producer is called with the fuse armed. Returning an error blows the fuse with it. The
+    /// writer is dropped after the fuse was disarmed or blown so the reader sees the outcome at EOF.
+    pub fn spawn<R, W, F>(&mut self, reader: R, writer: W, producer: F) -> FusedReader<R>
+        where R: Read, W: Send + 'static, F: FnOnce(&mut W, &FuseGuard<'_>) -> Result<(), IoError> + Send + 'static {
+        let (reader, fuse) = fuse(reader);
+        self.shared.fuses.lock().unwrap_or_else(|err| err.into_inner()).push(fuse.0.clone());
+        if self.is_failed() {
+            fuse.0.set_error(fuse.0.generation(), sibling_failed_error(), BLOWN);
+            fuse.0.cancel(None);
+        }
+
+        let shared = self.shared.clone();
+        self.threads.push(thread::spawn(move || {
+            let mut writer = writer;
+            let _fail_on_panic = FailOnPanic(&shared, &fuse.0);
+            let guard = fuse.arm().expect("new fuse can be armed");
+            match producer(&mut writer, &guard) {
+                Err(err) => {
+                    shared.fail(&fuse.0);
+                    guard.blow(err);
+                }
+                Ok(()) => drop(guard),
+            }
+            // EOF only after the outcome was recorded
+            drop(writer);
+        }));
+        reader
+    }
+
+    /// Returns `true` if any of the producers failed.
+    pub fn is_failed(&self) -> bool {
+        self.shared.failed.load(Ordering::Acquire)
+    }
+
+    /// Waits for all the producers to finish without cancelling them.
+    ///
+    /// Returns `true` if any of them failed.
+    pub fn join(mut self) -> bool {
+        self.join_all();
+        self.is_failed()
+    }
+
+    fn join_all(&mut self) {
+        for thread in self.threads.drain(..) {
+            // panics are reported to the readers
+            let _ = thread.join();
+        }
+    }
+}
+
+impl Drop for FusedTaskGroup {
+    fn drop(&mut self) {
+        if !self.threads.is_empty() {
+            // the producers are detached
+            self.shared.cancel();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{extract_fuse_error, FusePolicy};
+    use std::io::Write;
+
+    #[test]
+    fn test_fused_task_group_fails_siblings() {
+        let mut group = FusedTaskGroup::new();
+
+        let (reader, writer) = pipe::pipe();
+        let mut failing = group.spawn(reader, writer, |writer, _fuse| {
+            writer.write_all(&[1])?;
+            Err(IoError::new(ErrorKind::InvalidData, "uh! oh!"))
+        });
+        let (reader, writer) = pipe::pipe();
+        let mut endless = group.spawn(reader, writer, |writer, fuse| {
+            while !fuse.is_cancelled() {
+                writer.write_all(&[2])?;
+            }
+            Ok(())
+        });
+
+        let mut data = Vec::new();
+        assert_eq!(failing.read_to_end(&mut data).unwrap_err().kind(), ErrorKind::InvalidData);
+        assert_eq!(data, vec![1]);
+
+        let err = endless.read_to_end(&mut Vec::new()).unwrap_err();
+        assert_eq!(err.kind(), ErrorKind::BrokenPipe);
+        assert_eq!(extract_fuse_error(&err).unwrap().reason(), &BlowReason::SiblingFailed);
+        assert!(group.join());
+    }
+
+    #[test]
+    fn test_fused_task_group_fails_blocked_sibling() {
+        let mut group = FusedTaskGroup::new();
+
+        let (reader, writer) = crate::ring_pipe::pipe_with_capacity(4);
+        let mut blocked = group.spawn(reader, writer, |writer, _fuse| writer.write_all(&[0; 64]));
+        blocked.policy = FusePolicy::FailFast;
+        let (reader, writer) = pipe::pipe();
+        let mut failing = group.spawn(reader, writer, |_writer: &mut pipe::PipeWriter, _fuse| {
+            Err(IoError::new(ErrorKind::InvalidData, "uh! oh!"))
+        });
+
+        assert_eq!(failing.read_to_end(&mut Vec::new()).unwrap_err().kind(), ErrorKind::InvalidData);
+        let err = blocked.read(&mut [0; 4]).unwrap_err();
+        assert_eq!(extract_fuse_error(&err).unwrap().reason(), &BlowReason::SiblingFailed);
+        drop(blocked);
+        assert!(group.join());
+    }
+
+    #[test]
+    fn test_fused_task_group_panic() {
+        let mut group = FusedTaskGroup::new();
+
+        let (reader, writer) = pipe::pipe();
+        let mut panicking = group.spawn(reader, writer, |_writer: &mut pipe::PipeWriter, _fuse| panic!("boom"));
+
+        assert_eq!(panicking.read_to_end(&mut Vec::new()).unwrap_err().kind(), ErrorKind::BrokenPipe);
+        assert!(group.join());
+    }
+
+    #[test]
+    fn test_fused_task_group_drop_with_blocked_producer() {
+        let mut group = FusedTaskGroup::new();
+        let (reader, writer) = crate::ring_pipe::pipe_with_capacity(4);
+        let mut reader = group.spawn(reader, writer, |writer, _fuse| writer.write_all(&[0; 64]));
+
+        // give the producer time to block on the full pipe
+        thread::sleep(std::time::Duration::from_millis(50));
+        drop(group);
+
+        // the detached producer finishes once the reader drains the pipe
+        let mut data = Vec::new();
+        reader.read_to_end(&mut data).unwrap();
+        assert_eq!(data, vec![0; 64]);
+    }
+
+    #[test]
+    fn test_fused_task_group_complete() {
+        let mut group = FusedTaskGroup::new();
+
+        let mut readers: Vec<_> = (0..3u8).map(|byte| {
+            let (reader, writer) = pipe::pipe();
+            group.spawn(reader, writer, move |writer, _fuse| writer.write_all(&[byte]))
+        }).collect();
+
+        for (byte, reader) in readers.iter_mut().enumerate() {
+            let mut data = Vec::new();
+            reader.read_to_end(&mut data).unwrap();
+            assert_eq!(data, vec![byte as u8]);
+        }
+        assert!(!group.join());
+    }
+}
@@ -0,0 +1,187 @@
+use std::io::{Read, Error as IoError};
+use std::time::{Duration, Instant};
+
+use crate::{FusedReader, FuseStatus};
+
+const BUCKETS: usize = 40;
+
+/// Histogram of durations with power of two microsecond buckets.
+///
+/// Bucket `0` counts durations below 1µs and bucket `n` counts durations in `[2^(n-1), 2^n)` µs.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct LatencyHistogram {
+    buckets: Vec<u64>,
+}
+
+impl LatencyHistogram {
+    fn record(&mut self, duration: Duration) {
+        if self.buckets.is_empty() {
+            self.buckets = vec![0; BUCKETS];
+        }
+        let micros = duration.as_micros().min(u128::from(u64::MAX)) as u64;
+        let bucket = (64 - micros.leading_zeros() as usize).min(BUCKETS - 1);
+        self.buckets[bucket] += 1;
+    }
+
+    /// Counts of recorded durations per bucket.
+    pub fn buckets(&self) -> &[u64] {
+        &self.buckets
+    }
+
+    /// Exclusive upper bound of durations counted in given bucket.
+    pub fn bucket_upper_bound(bucket: usize) -> Duration {
+        Duration::from_micros(1 << bucket.min(BUCKETS - 1))
+    }
+
+    /// Number of recorded durations.
+    pub fn count(&self) -> u64 {
+        self.buckets.iter().sum()
+    }
+
+    /// Upper bound of the bucket containing given percentile (`0.0` to `1.0`) of recorded durations.
+    ///
+    /// Returns `None` if nothing was recorded.
+    pub fn percentile(&self, percentile: f64) -> Option<Duration> {
+        let count = self.count();
+        if count == 0 {
+            return None
+        }
+        let rank = ((count as f64 * percentile.clamp(0.0, 1.0)).ceil() as u64).max(1);
+        let mut seen = 0;
+        self.buckets.iter().position(|&bucket| {
+            seen += bucket;
+            seen >= rank
+        }).map(LatencyHistogram::bucket_upper_bound)
+    }
+}
+
+/// Statistics collected by `InstrumentedReader`.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ReadStats {
+    /// Number of `read` calls.
+    pub reads: u64,
+    /// Number of bytes read.
+    pub bytes: u64,
+    /// Total time spent blocked in `read` calls waiting for the producer.
+    pub blocked: Duration,
+    /// Total time between `read` calls spent by the consumer.
+    pub consuming: Duration,
+    /// Histogram of per `read` call latencies.
+    pub latency: LatencyHistogram,
+}
+
+impl<R: Read> FusedReader<R> {
+    /// Wraps reader so that it records latency statistics of each read.
+    ///
+    /// Large `blocked` time in the statistics indicates that the producer is the pipeline
+    /// bottleneck while large `consuming` time points to the consumer.
+    pub fn instrument(self) -> InstrumentedReader<R> {
+        InstrumentedReader {
+            reader: self,
+            stats: ReadStats::default(),
+            last_read: None,
+        }
+    }
+}
+
+/// Fused reader recording read latency statistics.
+#[derive(Debug)]
+pub struct InstrumentedReader<R: Read> {
+    reader: FusedReader<R>,
+    stats: ReadStats,
+    last_read: Option<Instant>,
+}
+
+impl<R: Read> InstrumentedReader<R> {
+    /// Statistics collected so far.
+    pub fn stats(&self) -> &ReadStats {
+        &self.stats
+    }
+
+    /// Clears collected statistics.
+    pub fn reset_stats(&mut self) {
+        self.stats = ReadStats::default();
+        self.last_read = None;
+    }
+
+    /// Checks status of the fuse; see `FusedReader::check_fuse`.
+    pub fn check_fuse(&self) -> FuseStatus {
+        self.reader.check_fuse()
+    }
+
+    /// Returns inner fused reader and collected statistics.
+    pub fn into_inner(self) -> (FusedReader<R>, ReadStats) {
+        (self.reader, self.stats)
+    }
+}
+
+impl<R: Read> Read for InstrumentedReader<R> {
+    fn read(&mut self, buf: &mut [u8]) -> Result<usize, IoError> {
+        let start = Instant::now();
+        if let Some(last_read) = self.last_read {
+            self.stats.consuming += start.duration_since(last_read);
+        }
+
+        let res = self.reader.read(buf);
+
+        let end = Instant::now();
+        let latency = end.duration_since(start);
+        self.last_read = Some(end);
+        self.stats.reads += 1;
+        self.stats.blocked += latency;
+        self.stats.latency.record(latency);
+        if let Ok(bytes) = res {
+            self.stats.bytes += bytes as u64;
+        }
+        res
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::fuse;
+    use pipe::pipe;
+    use std::io::{ErrorKind, Write};
+    use std::thread;
+
+    #[test]
+    fn test_histogram() {
+        let mut histogram = LatencyHistogram::default();
+        assert_eq!(histogram.percentile(0.5), None);
+
+        histogram.record(Duration::from_nanos(10));
+        histogram.record(Duration::from_micros(3));
+        histogram.record(Duration::from_micros(3));
+        histogram.record(Duration::from_millis(1));
+
+        assert_eq!(histogram.count(), 4);
+        assert_eq!(histogram.buckets()[0], 1);
+        assert_eq!(histogram.buckets()[2], 2);
+        assert_eq!(histogram.percentile(0.5), Some(Duration::from_micros(4)));
+        assert_eq!(histogram.percentile(1.0), Some(Duration::from_micros(1024)));
+    }
+
+    #[test]
+    fn test_instrumented_reader() {
+        let (reader, mut writer) = pipe();
+        let (reader, fuse) = fuse(reader);
+        let mut reader = reader.instrument();
+
+        thread::spawn(move || {
+            let _fuse = fuse.arm().unwrap();
+            writer.write_all(&[1; 10]).unwrap();
+            thread::sleep(Duration::from_millis(20));
+            writer.write_all(&[1; 10]).unwrap();
+            panic!("boom");
+        });
+
+        let mut data = Vec::new();
+        assert_eq!(reader.read_to_end(&mut data).unwrap_err().kind(), ErrorKind::BrokenPipe);
+
+        let stats = reader.stats();
+        assert_eq!(stats.bytes, 20);
+        assert_eq!(stats.latency.count(), stats.reads);
+        assert!(stats.blocked >= Duration::from_millis(20));
+    }
+}
@@ -0,0 +1,312 @@
+use std::cell::UnsafeCell;
+use std::io::Error as IoError;
+use std::os::windows::io::{AsRawHandle, OwnedHandle};
+use std::pin::Pin;
+use std::ptr;
+use std::sync::{Arc, Mutex, MutexGuard};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::task::{Context, Poll, Waker};
+use std::thread;
+
+use futures_io::AsyncRead;
+use windows_sys::Win32::Foundation::{ERROR_BROKEN_PIPE, ERROR_HANDLE_EOF, ERROR_IO_PENDING, ERROR_OPERATION_ABORTED, HANDLE, INVALID_HANDLE_VALUE};
+use windows_sys::Win32::Storage::FileSystem::ReadFile;
+use windows_sys::Win32::System::IO::{CancelIoEx, CreateIoCompletionPort, GetQueuedCompletionStatus, OVERLAPPED};
+
+use crate::async_fuse::AsyncFuseState;
+use crate::async_read::fuse_async_read_state;
+use crate::{AsyncFuse, FusedAsyncReader};
+
+const CHUNK_SIZE: usize = 64 * 1024;
+const INFINITE: u32 = u32::MAX;
+
+/// Overlapped read owned jointly by the reader and the kernel until it completes.
+#[repr(C)]
+struct Operation {
+    // first field so that `OVERLAPPED` pointer of the completion points to the operation
+    overlapped: UnsafeCell<OVERLAPPED>,
+    buf: UnsafeCell<Vec<u8>>,
+    completion: Mutex<Completion>,
+}
+
+// `overlapped` and `buf` are only accessed by the kernel while the read is pending and by the
+// reader after it completed
+unsafe impl Send for Operation {}
+unsafe impl Sync for Operation {}
+
+#[derive(Default)]
+struct Completion {
+    result: Option<Result<usize, IoError>>,
+    waker: Option<Waker>,
+}
+
+impl std::fmt::Debug for Operation {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str("Operation")
+    }
+}
+
+impl Operation {
+    fn completion(&self) -> MutexGuard<'_, Completion> {
+        self.completion.lock().unwrap_or_else(|err| err.into_inner())
+    }
+}
+
+/// Returns completion port shared by all overlapped readers, starting the thread dispatching
+/// its completions on first use.
+fn port() -> Result<HANDLE, IoError> {
+    static PORT: Mutex<Option<usize>> = Mutex::new(None);
+
+    let mut port = PORT.lock().unwrap_or_else(|err| err.into_inner());
+    if let Some(port) = *port {
+        return Ok(port as HANDLE)
+    }
+    let handle = unsafe { CreateIoCompletionPort(INVALID_HANDLE_VALUE, ptr::null_mut(), 0, 1) };
+    if handle.is_null() {
+        return Err(IoError::last_os_error())
+    }
+    let raw = handle as usize;
+    thread::Builder::new().name("fused-iocp".into()).spawn(move || dispatch(raw as HANDLE))?;
+    *port = Some(raw);
+    Ok(handle)
+}
+
+fn dispatch(port: HANDLE) {
+    loop {
+        let mut bytes = 0;
+        let mut key = 0;
+        let mut overlapped = ptr::null_mut();
+        let ok = unsafe { GetQueuedCompletionStatus(port, &mut bytes, &mut key, &mut overlapped, INFINITE) };
+        if overlapped.is_null() {
+            continue
+        }
+        let result = if ok != 0 { Ok(bytes as usize) } else { Err(IoError::last_os_error()) };
+
+        // Safety: each submitted read leaks one reference to its operation that is reclaimed here
+        let operation = unsafe { Arc::from_raw(overlapped as *const Operation) };
+        let waker = {
+            let mut completion = operation.completion();
+            completion.result = Some(result);
+            completion.waker.take()
+        };
+        if let Some(waker) = waker {
+            waker.wake();
+        }
+    }
+}
+
+#[derive(Debug)]
+struct PipeHandle {
+    handle: OwnedHandle,
+    // set once the fuse got blown; no more reads are submitted
+    cancelled: AtomicBool,
+}
+
+impl PipeHandle {
+    fn raw(&self) -> HANDLE {
+        self.handle.as_raw_handle() as HANDLE
+    }
+
+    fn cancel(&self, overlapped: *const OVERLAPPED) {
+        unsafe { CancelIoEx(self.raw(), overlapped) };
+    }
+}
+
+/// Async reader of a Windows named pipe or file opened for overlapped I/O.
+///
+/// Reads are submitted as overlapped operations completed on a completion port shared by all
+/// readers so no thread is blocked per stream. Writer end closing the pipe or a cancelled read is
+/// reported as EOF.
+#[derive(Debug)]
+pub struct OverlappedPipeReader {
+    handle: Arc<PipeHandle>,
+    pending: Option<Arc<Operation>>,
+    ready: Vec<u8>,
+    consumed: usize,
+}
+
+impl OverlappedPipeReader {
+    /// Creates reader of handle opened with `FILE_FLAG_OVERLAPPED`.
+    pub fn new(handle: OwnedHandle) -> Result<OverlappedPipeReader, IoError> {
+        let port = port()?;
+        if unsafe { CreateIoCompletionPort(handle.as_raw_handle() as HANDLE, port, 0, 0) }.is_null() {
+            return Err(IoError::last_os_error())
+        }
+        Ok(OverlappedPipeReader {
+            handle: Arc::new(PipeHandle {
+                handle,
+                cancelled: AtomicBool::new(false),
+            }),
+            pending: None,
+            ready: Vec::new(),
+            consumed: 0,
+        })
+    }
+
+    /// Submits overlapped read of up to `len` bytes.
+    fn submit(&mut self, len: usize) -> Result<(), IoError> {
+        if self.handle.cancelled.load(Ordering::Acquire) {
+            return Err(IoError::from_raw_os_error(ERROR_OPERATION_ABORTED as i32))
+        }
+        let operation = Arc::new(Operation {
+            overlapped: UnsafeCell::new(unsafe { std::mem::zeroed() }),
+            buf: UnsafeCell::new(vec![0; len.min(CHUNK_SIZE)]),
+            completion: Mutex::new(Completion::default()),
+        });
+        let raw = Arc::into_raw(operation.clone());
+        let ok = unsafe {
+            let buf = &mut *operation.buf.get();
+            ReadFile(self.handle.raw(), buf.as_mut_ptr(), buf.len() as u32, ptr::null_mut(), operation.overlapped.get())
+        };
+        if ok == 0 {
+            let err = IoError::last_os_error();
+            if err.raw_os_error() != Some(ERROR_IO_PENDING as i32) {
+                // no completion will be queued for failed submission
+                drop(unsafe { Arc::from_raw(raw) });
+                return Err(err)
+            }
+        }
+        // fuse may have been blown while submitting
+        if self.handle.cancelled.load(Ordering::Acquire) {
+            self.handle.cancel(operation.overlapped.get());
+        }
+        self.pending = Some(operation);
+        Ok(())
+    }
+}
+
+/// Maps errors meaning end of the stream to EOF.
+fn finish(result: Result<usize, IoError>) -> Result<usize, IoError> {
+    match result {
+        Err(err) if [ERROR_BROKEN_PIPE, ERROR_HANDLE_EOF, ERROR_OPERATION_ABORTED].iter().any(|code| err.raw_os_error() == Some(*code as i32)) => Ok(0),
+        other => other,
+    }
+}
+
+impl AsyncRead for OverlappedPipeReader {
+    fn poll_read(self: Pin<&mut Self>, cx: &mut Context<'_>, buf: &mut [u8]) -> Poll<Result<usize, IoError>> {
+        let this = self.get_mut();
+        if buf.is_empty() {
+            return Poll::Ready(Ok(0))
+        }
+
+        if this.consumed == this.ready.len() {
+            if this.pending.is_none() {
+                if let Err(err) = this.submit(buf.len()) {
+                    return Poll::Ready(finish(Err(err)))
+                }
+            }
+            let operation = this.pending.clone().expect("read submitted");
+            let mut completion = operation.completion();
+            let result = match completion.result.take() {
+                Some(result) => result,
+                None => {
+                    completion.waker = Some(cx.waker().clone());
+                    return Poll::Pending
+                }
+            };
+            drop(completion);
+            this.pending = None;
+
+            let bytes = match finish(result) {
+                Ok(bytes) => bytes,
+                Err(err) => return Poll::Ready(Err(err)),
+            };
+            // Safety: the read completed so the kernel no longer uses the buffer
+            let data = unsafe { &mut *operation.buf.get() };
+            data.truncate(bytes);
+            this.ready = std::mem::take(data);
+            this.consumed = 0;
+        }
+
+        let bytes = buf.len().min(this.ready.len() - this.consumed);
+        buf[..bytes].copy_from_slice(&this.ready[this.consumed..this.consumed + bytes]);
+        this.consumed += bytes;
+        Poll::Ready(Ok(bytes))
+    }
+}
+
+impl Drop for OverlappedPipeReader {
+    fn drop(&mut self) {
+        // the operation stays alive until its completion is dispatched
+        if let Some(operation) = self.pending.take() {
+            self.handle.cancel(operation.overlapped.get());
+        }
+    }
+}
+
+/// Fuses named pipe handle opened with `FILE_FLAG_OVERLAPPED` for reading with overlapped I/O.
+///
+/// Blowing the fuse, or the writer task panicking or being dropped while the fuse is armed,
+/// cancels outstanding read so the reader gets the fuse error without waiting for the pipe.
+pub fn fuse_named_pipe(handle: OwnedHandle) -> Result<(FusedAsyncReader<OverlappedPipeReader>, AsyncFuse), IoError> {
+    let reader = OverlappedPipeReader::new(handle)?;
+    let handle = Arc::downgrade(&reader.handle);
+    let state = AsyncFuseState::with_fail_hook(move || {
+        if let Some(handle) = handle.upgrade() {
+            handle.cancelled.store(true, Ordering::Release);
+            handle.cancel(ptr::null());
+        }
+    });
+    Ok(fuse_async_read_state(reader, state))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use futures::executor::block_on;
+    use futures::io::AsyncReadExt;
+    use std::fs::OpenOptions;
+    use std::io::{ErrorKind, Write};
+    use std::os::windows::ffi::OsStrExt;
+    use std::os::windows::io::FromRawHandle;
+    use windows_sys::Win32::Storage::FileSystem::{FILE_FLAG_OVERLAPPED, PIPE_ACCESS_INBOUND};
+    use windows_sys::Win32::System::Pipes::{CreateNamedPipeW, PIPE_TYPE_BYTE, PIPE_WAIT};
+
+    fn named_pipe(name: &str) -> (OwnedHandle, std::fs::File) {
+        let path = format!(r"\\.\pipe\fused-reader-{}-{}", name, std::process::id());
+        let wide: Vec<u16> = std::ffi::OsStr::new(&path).encode_wide().chain(Some(0)).collect();
+        let server = unsafe {
+            CreateNamedPipeW(wide.as_ptr(), PIPE_ACCESS_INBOUND | FILE_FLAG_OVERLAPPED, PIPE_TYPE_BYTE | PIPE_WAIT, 1, 0, 64 * 1024, 0, ptr::null())
+        };
+        assert_ne!(server, INVALID_HANDLE_VALUE);
+        let client = OpenOptions::new().write(true).open(&path).unwrap();
+        (unsafe { OwnedHandle::from_raw_handle(server as _) }, client)
+    }
+
+    #[test]
+    fn test_fuse_named_pipe() {
+        let (server, mut client) = named_pipe("data");
+        let (mut reader, fuse) = fuse_named_pipe(server).unwrap();
+
+        thread::spawn(move || {
+            let fuse = fuse.arm();
+            client.write_all(&[1, 2, 3]).unwrap();
+            fuse.disarm();
+        });
+
+        let mut data = Vec::new();
+        block_on(reader.read_to_end(&mut data)).unwrap();
+        assert_eq!(data, vec![1, 2, 3]);
+    }
+
+    #[test]
+    fn test_fuse_named_pipe_blow_cancels() {
+        let (server, mut client) = named_pipe("blow");
+        let (mut reader, fuse) = fuse_named_pipe(server).unwrap();
+
+        thread::spawn(move || {
+            let fuse = fuse.arm();
+            client.write_all(&[1]).unwrap();
+            thread::sleep(std::time::Duration::from_millis(50));
+            fuse.blow(IoError::new(ErrorKind::InvalidData, "uh! oh!"));
+            // writer end of the pipe stays open
+            thread::sleep(std::time::Duration::from_secs(60));
+            drop(client);
+        });
+
+        let mut data = Vec::new();
+        assert_eq!(block_on(reader.read_to_end(&mut data)).unwrap_err().kind(), ErrorKind::InvalidData);
+        assert_eq!(data, vec![1]);
+    }
+}
@@ -3,6 +3,16 @@ When working with data pipes it is often necessary to distinguish between EOF on
 
 Fuses can also be blown with custom error that is passed to the reader end.
 
+Use `fuse_writer` for the reverse direction where the writer end fails if the reader thread dies while holding armed fuse.
+
+Use `fuse_child` to fuse standard output of a child process so that the reader fails if the process exits with non-zero status.
+
+Use `fuse_bundle` to fuse several readers fed by one writer so that its failure fails all of them.
+
+Use `fuse_local` within a single thread, like an event loop or on `wasm32-unknown-unknown`, where the fuse does not need to be shared between threads.
+
+Use `core_fuse` without `std`. It is a separate minimal fuse generic over the error type: it can be armed, completed and blown, but it does not detect writer panics and has none of the other features of `Fuse`. `Fuse`, `FuseGuard` and `FusedReader` are not built on it and are available with `std` only. `CoreFusedReader` implements `embedded_io::Read` with the `embedded-io` feature; other integrations with I/O traits of the platform call `CoreFusedReader::finish` at the end of the stream.
+
 Example usage
 =============
 
@@ -51,183 +61,2591 @@ let mut data = Vec::new();
 assert_eq!(reader.read_to_end(&mut data).unwrap_err().kind(), ErrorKind::UnexpectedEof);
 assert_eq!(&data, &[1]); // data that was written before error
 ```
+
+Optional features
+=================
+
+* `std` - enabled by default; everything but `core_fuse`, which only needs `core` and `alloc` for targets without `std` such as embedded RTOS, depends on it.
+* `stream` - `fuse_stream` function fusing `futures` `Stream` of `Result` items with `AsyncFuse` that can be armed inside of async task; `fuse_stream_local` for thread-per-core runtimes not requiring `Send`.
+* `crossbeam` - `fuse_receiver` function fusing `crossbeam_channel::Receiver`.
+* `linux` - `fused_copy_fd` function copying between file descriptors in-kernel with `splice` or `sendfile` on Linux.
+* `uring` - `fused_copy_uring` function copying between file descriptors with overlapping reads and writes submitted to io_uring on Linux; the in-memory `ring_pipe` is not supported.
+* `shm` - `shm_pipe` module with memory mapped pipe for streaming data between processes on Unix.
+* `futures-io` - `fuse_async_read` function fusing `futures::io::AsyncRead` readers, including async-std readers.
+* `tokio` - `fuse_async_read` function fusing `tokio::io::AsyncRead` readers.
+* `async-std` - `spawn_fused` function spawning async-std task with armed `AsyncFuse`.
+* `iocp` - `fuse_named_pipe` function reading Windows named pipes with overlapped I/O without a thread per stream.
+* `transcode` - `TranscodingReader` converting UTF-16LE or Latin-1 streams to UTF-8.
+* `registry` - `live_fuses` function listing fuses of readers registered with `FusedReader::register` for diagnostics.
+* `embedded-io` - `embedded_io::Read` implementation of `CoreFusedReader`; does not need `std`.
+* `log` - fuses log being armed, completed, blown and poisoned with `log` crate under `fused_reader` target; name fuses with `fuse_named` to tell the events of different pipelines apart.
+
 !*/
-use std::io::{Read, Error as IoError, ErrorKind};
-use std::sync::{Arc, Mutex, MutexGuard, TryLockError};
+#![cfg_attr(not(any(feature = "std", test)), no_std)]
 
-/// Fuses reader so that if writer thread dies while holding armed fuse the reader will get `BrokenPipe` error.
-pub fn fuse<R: Read>(reader: R) -> (FusedReader<R>, Fuse) {
-    let reader_fuse = Arc::new(Mutex::new(Ok(())));
-    let writer_fuse = reader_fuse.clone();
-( FusedReader {
-            reader,
-            fuse: reader_fuse,
-        },
-        Fuse(writer_fuse),
-    )
+extern crate alloc;
+
+/// Logs lifecycle event of the fuse with the `log` feature.
+#[cfg(feature = "std")]
+macro_rules! fuse_event {
+    ($level:ident, $shared:expr, $($arg:tt)+) => {
+        #[cfg(feature = "log")]
+        log::$level!(target: "fused_reader", "fuse {}: {}", $shared.label(), format_args!($($arg)+));
+    };
 }
 
-/// Reader that will fail with I/O error if fuse was blown.
+mod core_fuse;
+pub use core_fuse::{core_fuse, CoreFuse, CoreFuseError, CoreFuseGuard, CoreFusedReader, CoreStatus};
+#[cfg(feature = "embedded-io")]
+pub use core_fuse::CoreReadError;
+
+// `FusedReader` and everything built on it needs `std::io`; items of optional features imply it
+#[cfg(feature = "std")]
+use std::any::Any;
+#[cfg(feature = "std")]
+use std::io::{self, BufRead, IoSliceMut, Read, Seek, SeekFrom, Write, Error as IoError, ErrorKind};
+#[cfg(feature = "std")]
+use std::marker::PhantomData;
+#[cfg(feature = "std")]
+use std::mem::ManuallyDrop;
+#[cfg(feature = "std")]
+use std::ops::{Deref, DerefMut};
+#[cfg(feature = "std")]
+use std::sync::{Arc, Condvar, Mutex, MutexGuard, OnceLock};
+#[cfg(feature = "std")]
+use std::sync::atomic::{AtomicBool, AtomicU64, AtomicU8, AtomicUsize, Ordering};
+#[cfg(feature = "std")]
+use std::thread;
+#[cfg(feature = "std")]
+use std::time::{Duration, Instant};
+#[cfg(all(feature = "std", unix))]
+use std::os::unix::io::{AsFd, AsRawFd, BorrowedFd, RawFd};
+#[cfg(all(feature = "std", windows))]
+use std::os::windows::io::{AsHandle, AsRawHandle, AsRawSocket, AsSocket, BorrowedHandle, BorrowedSocket, RawHandle, RawSocket};
+
+#[cfg(feature = "std")]
+pub mod prelude;
+#[cfg(feature = "std")]
+pub mod ring_pipe;
+#[cfg(feature = "std")]
+pub use ring_pipe::{fused_pipe, fused_pipe_with_capacity};
+
+#[cfg(feature = "std")]
+mod code;
+#[cfg(feature = "std")]
+pub use code::{coded_error, error_code, CodedError};
+
+#[cfg(feature = "std")]
+mod panic;
+#[cfg(feature = "std")]
+pub use panic::{install_panic_hook, PanicInfo, PanicLocation};
+
+#[cfg(feature = "std")]
+mod reason;
+#[cfg(feature = "std")]
+pub use reason::{extract_fuse_error, is_fuse_error, BlowReason, FuseError};
+
+#[cfg(feature = "std")]
+mod deadline;
+#[cfg(feature = "std")]
+pub use deadline::{ReadTimeout, WaitAvailable};
+
+#[cfg(feature = "std")]
+mod throttle;
+#[cfg(feature = "std")]
+pub use throttle::{ThrottledReader, ThrottledWriter};
+
+#[cfg(feature = "std")]
+mod instrument;
+#[cfg(feature = "std")]
+pub use instrument::{InstrumentedReader, LatencyHistogram, ReadStats};
+
+#[cfg(feature = "std")]
+mod copy;
+#[cfg(feature = "std")]
+pub use copy::{fused_copy_buf, CopyBuffers};
+
+#[cfg(feature = "std")]
+mod limit;
+#[cfg(feature = "std")]
+pub use limit::LimitedReader;
+
+#[cfg(feature = "std")]
+mod skip;
+
+#[cfg(feature = "std")]
+mod checkpoint;
+#[cfg(feature = "std")]
+pub use checkpoint::{Checkpoint, Resume};
+
+#[cfg(feature = "std")]
+mod shared;
+#[cfg(feature = "std")]
+pub use shared::SharedFusedReader;
+
+#[cfg(feature = "std")]
+mod stdin;
+#[cfg(feature = "std")]
+pub use stdin::{fused_stdin, StdinReader};
+
+#[cfg(feature = "std")]
+mod prefetch;
+
+#[cfg(feature = "std")]
+mod child;
+#[cfg(feature = "std")]
+pub use child::{fuse_child, ChildFuse, ChildOutcome, ChildOutput, ChildReader, DropPolicy};
+
+#[cfg(feature = "std")]
+mod exit;
+#[cfg(feature = "std")]
+pub use exit::{decode_exit, producer_main, ExitCodes};
+
+#[cfg(feature = "std")]
+mod owned;
+#[cfg(feature = "std")]
+pub use owned::OwnedFuseGuard;
+
+#[cfg(feature = "std")]
+mod armed;
+#[cfg(feature = "std")]
+pub use armed::ArmedWriter;
+
+#[cfg(feature = "std")]
+mod queue;
+#[cfg(feature = "std")]
+use queue::ArmQueue;
+#[cfg(feature = "std")]
+pub use queue::ArmTicket;
+
+#[cfg(feature = "std")]
+mod group;
+#[cfg(feature = "std")]
+pub use group::FusedTaskGroup;
+
+#[cfg(feature = "std")]
+mod scope;
+#[cfg(feature = "std")]
+pub use scope::{fused_scope, FusedScope};
+
+#[cfg(feature = "std")]
+mod bundle;
+#[cfg(feature = "std")]
+pub use bundle::{fuse_bundle, BundleGuard, FuseBundle};
+
+#[cfg(feature = "std")]
+mod local;
+#[cfg(feature = "std")]
+pub use local::{fuse_local, LocalFuse, LocalFuseGuard, LocalFusedReader};
+
+#[cfg(feature = "std")]
+mod writer;
+#[cfg(feature = "std")]
+pub use writer::{fuse_writer, FusedWriter};
+
+#[cfg(feature = "std")]
+mod frame;
+#[cfg(feature = "std")]
+pub use frame::{Frame, FrameWriter, Frames, DEFAULT_MAX_FRAME_SIZE};
+
+#[cfg(feature = "std")]
+mod chunks;
+#[cfg(feature = "std")]
+pub use chunks::{Chunks, Delimited};
+
+#[cfg(feature = "std")]
+mod text;
+#[cfg(feature = "std")]
+pub use text::FusedTextReader;
+
+#[cfg(feature = "stream")]
+mod async_fuse;
+#[cfg(feature = "stream")]
+pub use async_fuse::{AsyncFuse, AsyncFuseGuard, FuseCell, LocalAsyncFuse, LocalAsyncFuseGuard, LocalState, SyncState};
+
+#[cfg(any(feature = "futures-io", feature = "tokio"))]
+mod async_read;
+#[cfg(any(feature = "futures-io", feature = "tokio"))]
+pub use async_read::{fuse_async_read, fuse_async_read_local, FusedAsyncReader};
+#[cfg(feature = "futures-io")]
+pub use async_read::AsyncChunks;
+
+#[cfg(feature = "async-std")]
+mod spawn;
+#[cfg(feature = "async-std")]
+pub use spawn::spawn_fused;
+
+#[cfg(feature = "stream")]
+mod stream;
+#[cfg(feature = "stream")]
+pub use stream::{fuse_stream, fuse_stream_local, FusedStream};
+
+#[cfg(feature = "crossbeam")]
+pub mod channel;
+#[cfg(feature = "crossbeam")]
+pub use channel::{fuse_receiver, FusedReceiver};
+
+#[cfg(all(feature = "linux", target_os = "linux"))]
+mod splice;
+#[cfg(all(feature = "linux", target_os = "linux"))]
+pub use splice::fused_copy_fd;
+
+#[cfg(all(feature = "uring", target_os = "linux"))]
+mod uring;
+#[cfg(all(feature = "uring", target_os = "linux"))]
+pub use uring::fused_copy_uring;
+
+#[cfg(all(feature = "shm", unix))]
+pub mod shm_pipe;
+
+#[cfg(all(feature = "iocp", windows))]
+mod iocp;
+#[cfg(all(feature = "iocp", windows))]
+pub use iocp::{fuse_named_pipe, OverlappedPipeReader};
+
+#[cfg(feature = "transcode")]
+mod transcode;
+#[cfg(feature = "transcode")]
+pub use transcode::{Encoding, TranscodingReader};
+
+#[cfg(feature = "registry")]
+mod registry;
+#[cfg(feature = "registry")]
+pub use registry::{live_fuses, LiveFuse, LiveFuseState};
+
+// values of `FuseShared::status`
+#[cfg(feature = "std")]
+const CLEAR: u8 = 0;
+// error is waiting for the reader in `FuseShared::error`
+#[cfg(feature = "std")]
+const BLOWN: u8 = 1;
+// writer panicked; the error stays in `FuseShared::error` until the fuse is reset
+#[cfg(feature = "std")]
+const POISONED: u8 = 2;
+
+#[cfg(feature = "std")]
+const GENERATION_SHIFT: u32 = 32;
+#[cfg(feature = "std")]
+const GUARDS_MASK: u64 = (1 << GENERATION_SHIFT) - 1;
+
+/// State of the fuse shared by its ends.
+///
+/// Checking, arming and disarming are lock-free; `error` is locked only once the fuse was blown.
+#[cfg(feature = "std")]
 #[derive(Debug)]
-pub struct FusedReader<R: Read> {
-    reader: R,
-    fuse: Arc<Mutex<Result<(), IoError>>>,
+struct FuseShared {
+    status: AtomicU8,
+    error: Mutex<Option<IoError>>,
+    // generation of the fuse in high and number of its live `FuseGuard`s of all clones of the fuse
+    // in low `GENERATION_SHIFT` bits
+    armed: AtomicU64,
+    // nanoseconds since `epoch` plus one; zero if not armed with TTL
+    deadline: AtomicU64,
+    // last `FuseGuard::heartbeat` like `deadline`
+    heartbeat: AtomicU64,
+    cancelled: AtomicBool,
+    // given to `FusedReader::cancel`
+    cancel_reason: Mutex<Option<String>>,
+    // event that closed one side of the stream first, recorded by its transport
+    first_close: Mutex<Option<ring_pipe::CloseEvent>>,
+    // writer signalled the stream is complete
+    completed: AtomicBool,
+    // guards released without `complete` blow the fuse; set by `fuse_writer`
+    require_complete: AtomicBool,
+    // declared length of the stream plus one; zero if not declared
+    expected: AtomicU64,
+    // position of the reader in the stream
+    consumed: AtomicU64,
+    // number of live `OwnedFuseGuard`s of the current generation; updated under `error` lock
+    owned: AtomicUsize,
+    queue: Mutex<ArmQueue>,
+    queue_turn: Condvar,
+    // typed error of `Fuse<E>` blown with error other than `IoError`
+    payload: Mutex<Option<Box<dyn Any + Send>>>,
+    on_blow: Callbacks<dyn Fn(&IoError) + Send + Sync>,
+    // called once the reader cancelled the stream, e.g. to stop the producer process
+    on_cancel: Callbacks<dyn Fn() + Send + Sync>,
+    // given to `fuse_named`
+    name: OnceLock<String>,
+    #[cfg(feature = "registry")]
+    registration: std::sync::OnceLock<registry::Registration>,
 }
 
-/// Status of the fuse.
-#[derive(Debug)]
-pub enum FuseStatus {
-    /// Fuse was not armed or guard got dropped.
-    Unarmed,
-    /// Fuse armed.
-    Armed,
-    /// Fuse blown with custom error.
-    Blown(IoError),
-    /// Fuse blown by panic unwind.
-    Poisoned,
+#[cfg(feature = "std")]
+type BlowCallback = Arc<dyn Fn(&IoError) + Send + Sync>;
+
+#[cfg(feature = "std")]
+type CancelCallback = Arc<dyn Fn() + Send + Sync>;
+
+/// Callbacks registered with `on_blow` and `on_cancel`.
+#[cfg(feature = "std")]
+struct Callbacks<F: ?Sized>(Mutex<Vec<Arc<F>>>);
+
+#[cfg(feature = "std")]
+impl<F: ?Sized> Default for Callbacks<F> {
+    fn default() -> Self {
+        Callbacks(Mutex::new(Vec::new()))
+    }
 }
 
-impl<R: Read> FusedReader<R> {
-    /// Checks status of the fuse.
+#[cfg(feature = "std")]
+impl<F: ?Sized> Callbacks<F> {
+    fn lock(&self) -> MutexGuard<'_, Vec<Arc<F>>> {
+        self.0.lock().unwrap_or_else(|err| err.into_inner())
+    }
+
+    fn get(&self) -> Vec<Arc<F>> {
+        self.lock().clone()
+    }
+}
+
+#[cfg(feature = "std")]
+impl<F: ?Sized> std::fmt::Debug for Callbacks<F> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "Callbacks({})", self.get().len())
+    }
+}
+
+/// Instant TTL deadlines are stored relative to.
+#[cfg(feature = "std")]
+fn epoch() -> Instant {
+    static EPOCH: OnceLock<Instant> = OnceLock::new();
+    *EPOCH.get_or_init(Instant::now)
+}
+
+#[cfg(feature = "std")]
+fn load_instant(nanos: &AtomicU64) -> Option<Instant> {
+    match nanos.load(Ordering::Acquire) {
+        0 => None,
+        nanos => Some(epoch() + Duration::from_nanos(nanos - 1)),
+    }
+}
+
+#[cfg(feature = "std")]
+fn instant_nanos(instant: Option<Instant>) -> u64 {
+    instant.map_or(0, |instant| instant.saturating_duration_since(epoch()).as_nanos() as u64 + 1)
+}
+
+#[cfg(feature = "std")]
+fn store_instant(nanos: &AtomicU64, instant: Option<Instant>) {
+    nanos.store(instant_nanos(instant), Ordering::Release);
+}
+
+#[cfg(feature = "std")]
+impl FuseShared {
+    fn new(result: Result<(), IoError>) -> FuseShared {
+        FuseShared {
+            status: AtomicU8::new(if result.is_err() { BLOWN } else { CLEAR }),
+            error: Mutex::new(result.err()),
+            armed: AtomicU64::new(0),
+            deadline: AtomicU64::new(0),
+            heartbeat: AtomicU64::new(0),
+            cancelled: AtomicBool::new(false),
+            cancel_reason: Mutex::new(None),
+            first_close: Mutex::new(None),
+            completed: AtomicBool::new(false),
+            require_complete: AtomicBool::new(false),
+            expected: AtomicU64::new(0),
+            consumed: AtomicU64::new(0),
+            owned: AtomicUsize::new(0),
+            queue: Mutex::new(ArmQueue::default()),
+            queue_turn: Condvar::new(),
+            payload: Mutex::new(None),
+            on_blow: Callbacks::default(),
+            on_cancel: Callbacks::default(),
+            name: OnceLock::new(),
+            #[cfg(feature = "registry")]
+            registration: std::sync::OnceLock::new(),
+        }
+    }
+
+    /// Name of the fuse for log events.
+    #[cfg(feature = "log")]
+    fn label(&self) -> &str {
+        self.name.get().map_or("<unnamed>", String::as_str)
+    }
+
+    fn status(&self) -> u8 {
+        self.status.load(Ordering::Acquire)
+    }
+
+    fn error(&self) -> MutexGuard<'_, Option<IoError>> {
+        self.error.lock().unwrap_or_else(|err| err.into_inner())
+    }
+
+    /// Returns `true` if any `FuseGuard` of the current generation is alive.
+    fn is_guarded(&self) -> bool {
+        self.armed.load(Ordering::Acquire) & GUARDS_MASK > 0
+    }
+
+    fn generation(&self) -> u64 {
+        self.armed.load(Ordering::Acquire) >> GENERATION_SHIFT
+    }
+
+    /// Counts new `FuseGuard` returning its generation.
+    fn add_guard(&self) -> u64 {
+        self.armed.fetch_add(1, Ordering::AcqRel) >> GENERATION_SHIFT
+    }
+
+    /// Uncounts `FuseGuard` unless its generation was reset.
+    fn remove_guard(&self, generation: u64) {
+        let _ = self.armed.fetch_update(Ordering::AcqRel, Ordering::Acquire, |armed| {
+            (armed >> GENERATION_SHIFT == generation).then(|| armed - 1)
+        });
+    }
+
+    /// Counts new `OwnedFuseGuard` unless its generation was reset.
+    fn add_owned(&self, generation: u64) {
+        let _error = self.error();
+        if generation == self.generation() {
+            self.owned.fetch_add(1, Ordering::AcqRel);
+        }
+    }
+
+    /// Uncounts `OwnedFuseGuard` unless its generation was reset.
+    fn remove_owned(&self, generation: u64) {
+        let _error = self.error();
+        if generation == self.generation() {
+            self.owned.fetch_sub(1, Ordering::AcqRel);
+        }
+    }
+
+    /// Starts new generation of the fuse discarding its state left by the writers of previous one.
+    fn reset(&self) -> u64 {
+        let mut error = self.error();
+        let armed = self.armed.fetch_update(Ordering::AcqRel, Ordering::Acquire, |armed| {
+            Some(((armed >> GENERATION_SHIFT) + 1) << GENERATION_SHIFT)
+        }).expect("generation updated");
+        self.owned.store(0, Ordering::Release);
+        *error = None;
+        self.status.store(CLEAR, Ordering::Release);
+        drop(error);
+        *self.payload() = None;
+        self.set_deadline(None);
+        self.completed.store(false, Ordering::Release);
+        *self.first_close.lock().unwrap_or_else(|err| err.into_inner()) = None;
+        self.expected.store(0, Ordering::Release);
+        (armed >> GENERATION_SHIFT) + 1
+    }
+
+    /// Returns `true` if any `FuseGuard` or `OwnedFuseGuard` is alive.
+    fn is_armed(&self) -> bool {
+        self.is_guarded() || self.owned.load(Ordering::Acquire) > 0
+    }
+
+    /// Records error for the reader end.
     ///
-    /// Note that the variant `FuseStatus::Blown` is provided only once and following calls will
-    /// return `FuseStatus::Unarmed` instead.
-    pub fn check_fuse(&mut self) -> FuseStatus {
-        match self.fuse.try_lock() {
-            Err(TryLockError::Poisoned(_)) => FuseStatus::Poisoned,
-            Ok(mut guard) => {
-                if guard.is_err() {
-                    let mut res = Ok(());
-                    std::mem::swap(&mut *guard, &mut res);
-                    FuseStatus::Blown(res.unwrap_err())
+    /// Error waiting for the reader is kept over later errors and the first panic error is kept
+    /// for good.
+    fn set_error(&self, generation: u64, err: IoError, status: u8) {
+        let mut error = self.error();
+        if generation != self.generation() {
+            // writer of the previous generation
+            return
+        }
+        match self.status() {
+            POISONED => (),
+            BLOWN if status == BLOWN => (),
+            _ => {
+                if status == POISONED {
+                    fuse_event!(error, self, "poisoned: {}", err);
                 } else {
-                    FuseStatus::Unarmed
+                    fuse_event!(warn, self, "blown: {}", err);
+                }
+                let callbacks = self.on_blow.get();
+                let blown = (!callbacks.is_empty()).then(|| FuseError::duplicate(&err));
+                *error = Some(err);
+                self.status.store(status, Ordering::Release);
+                drop(error);
+                if let Some(err) = blown {
+                    for callback in callbacks {
+                        callback(&err);
+                    }
                 }
             }
-            Err(TryLockError::WouldBlock) => FuseStatus::Armed,
         }
     }
 
-    /// Returns inner reader.
-    pub fn into_inner(self) -> R {
-        self.reader
+    /// Registers callback called when the fuse gets blown, right away if it already was.
+    fn on_blow(&self, callback: BlowCallback) {
+        let error = self.error();
+        let blown = match self.status() {
+            BLOWN | POISONED => error.as_ref().map(FuseError::duplicate),
+            _ => None,
+        };
+        match blown {
+            Some(err) => {
+                drop(error);
+                callback(&err);
+            }
+            None => self.on_blow.lock().push(callback),
+        }
     }
-}
 
-impl<R: Read> Read for FusedReader<R> {
-    fn read(&mut self, buf: &mut [u8]) -> Result<usize, IoError> {
-        // let it read to end before checking fuse
-        self.reader.read(buf).and_then(|bytes| if bytes == 0 {
-            match self.check_fuse() {
-                FuseStatus::Blown(err) => Err(err),
-                FuseStatus::Poisoned => Err(IoError::new(ErrorKind::BrokenPipe, "writer end dropped due to panic")),
-                FuseStatus::Unarmed |
-                FuseStatus::Armed => Ok(bytes),
-            }
-        } else {
-            Ok(bytes)
-        })
+    /// Returns copy of error waiting for the reader end.
+    fn peek_error(&self) -> Option<IoError> {
+        let error = self.error();
+        if self.status() != BLOWN {
+            return None
+        }
+        error.as_ref().map(FuseError::duplicate)
     }
-}
 
-/// Fuse that can be armed.
-#[derive(Debug)]
-pub struct Fuse(Arc<Mutex<Result<(), IoError>>>);
+    /// Takes error waiting for the reader end.
+    fn take_error(&self) -> Option<IoError> {
+        let mut error = self.error();
+        if self.status() != BLOWN {
+            return None
+        }
+        self.status.store(CLEAR, Ordering::Release);
+        error.take()
+    }
 
-impl Fuse {
-    /// Arms the fuse.
-    ///
-    /// Returns `BrokenPipe` error if reader was dropped due to panic.
-    pub fn arm(&self) -> Result<FuseGuard, IoError> {
-        self.0.lock().map(FuseGuard).map_err(|_| IoError::new(ErrorKind::BrokenPipe, "reader end dropped due to panic"))
+    /// Location and message of the panic that poisoned the fuse.
+    fn panic_info(&self) -> PanicInfo {
+        match self.error().as_ref().and_then(FuseError::from_io).map(FuseError::reason) {
+            Some(BlowReason::Panic { msg, location }) => PanicInfo { msg: msg.clone(), location: location.clone() },
+            _ => PanicInfo::default(),
+        }
     }
-}
 
-/// Armed fuse that if dropped due to panic will signal reader to fail with `BrokenPipe` error.
-#[derive(Debug)]
-pub struct FuseGuard<'a>(MutexGuard<'a, Result<(), IoError>>);
+    fn payload(&self) -> MutexGuard<'_, Option<Box<dyn Any + Send>>> {
+        self.payload.lock().unwrap_or_else(|err| err.into_inner())
+    }
 
-impl<'a> FuseGuard<'a> {
-    /// Blows the fuse with given error.
-    ///
-    /// The reader end will fail with this error after reaching EOF.
-    pub fn blow(mut self, err: IoError) {
-        *self.0 = Err(err);
+    /// Takes typed error the fuse was blown with if it is of type `E`.
+    fn take_payload<E: 'static>(&self) -> Option<E> {
+        let mut payload = self.payload();
+        if !payload.as_ref().is_some_and(|payload| payload.is::<E>()) {
+            return None
+        }
+        payload.take().and_then(|payload| payload.downcast().ok()).map(|err| *err)
     }
-}
 
-#[cfg(test)]
-mod tests {
-    use super::*;
-    use std::thread;
-    use std::io::Write;
-    use pipe::pipe;
+    fn deadline(&self) -> Option<Instant> {
+        load_instant(&self.deadline)
+    }
 
-    #[test]
-    fn test_unfused_panic() {
-        let (mut reader, mut writer) = pipe();
+    fn set_deadline(&self, deadline: Option<Instant>) {
+        store_instant(&self.deadline, deadline);
+    }
 
-        thread::spawn(move || {
-            writer.write(&[1]).unwrap();
-            panic!("boom");
-        });
+    /// Clears TTL deadline unless another guard has set its own since.
+    fn clear_deadline(&self, deadline: Instant) {
+        let _ = self.deadline.compare_exchange(instant_nanos(Some(deadline)), 0, Ordering::AcqRel, Ordering::Acquire);
+    }
 
-        let mut data = Vec::new();
+    fn heartbeat(&self) -> Option<Instant> {
+        load_instant(&self.heartbeat)
+    }
 
-        assert!(reader.read_to_end(&mut data).is_ok());
-        assert_eq!(&data, &[1]);
+    /// Signals the writer that the reader does not want the rest of the stream.
+    fn cancel(&self, reason: Option<String>) {
+        if reason.is_some() {
+            *self.cancel_reason.lock().unwrap_or_else(|err| err.into_inner()) = reason;
+        }
+        if !self.cancelled.swap(true, Ordering::AcqRel) {
+            for callback in self.on_cancel.get() {
+                callback();
+            }
+        }
     }
 
-    #[test]
-    fn test_fused_nopanic() {
-        let (reader, mut writer) = pipe();
+    /// Registers callback called when the reader cancels the stream, right away if it already did.
+    fn on_cancel(&self, callback: CancelCallback) {
+        let mut callbacks = self.on_cancel.lock();
+        if self.cancelled.load(Ordering::Acquire) {
+            drop(callbacks);
+            callback();
+        } else {
+            callbacks.push(callback);
+        }
+    }
 
-        let (mut reader, fuse) = fuse(reader);
+    /// Returns error for the writer if the reader cancelled the stream.
+    fn check_cancel(&self) -> Result<(), IoError> {
+        if !self.cancelled.load(Ordering::Acquire) {
+            return Ok(())
+        }
+        let message = match self.cancel_reason.lock().unwrap_or_else(|err| err.into_inner()).as_ref() {
+            Some(reason) => format!("reader cancelled the stream: {}", reason),
+            None => "reader cancelled the stream".to_owned(),
+        };
+        Err(FuseError::new_io(ErrorKind::BrokenPipe, BlowReason::ReaderCancelled, message))
+    }
 
-        thread::spawn(move || {
-            let _fuse = fuse.arm().unwrap();
-            writer.write(&[1]).unwrap();
+    /// Records the event that closed one side of the stream unless one was recorded already or
+    /// its generation was reset.
+    fn set_first_close(&self, generation: u64, event: ring_pipe::CloseEvent) {
+        let _error = self.error();
+        if generation == self.generation() {
+            self.first_close.lock().unwrap_or_else(|err| err.into_inner()).get_or_insert(event);
+        }
+    }
+
+    fn first_close(&self) -> Option<ring_pipe::CloseEvent> {
+        *self.first_close.lock().unwrap_or_else(|err| err.into_inner())
+    }
+
+    /// Returns `true` if guard released now should blow the fuse as it was not completed.
+    fn released_incomplete(&self) -> bool {
+        self.require_complete.load(Ordering::Acquire) && !self.completed.load(Ordering::Acquire)
+    }
+
+    /// Records error of a guard being dropped: the panic of its writer, expiry of its TTL
+    /// `deadline`, the pending `result` or the stream left incomplete, in that order.
+    fn drop_guard(&self, generation: u64, deadline: Option<Instant>, result: Result<(), IoError>, panic_status: u8) {
+        if thread::panicking() {
+            self.set_error(generation, panic_error(panic::take_panic()), panic_status);
+            return
+        }
+        let result = result.and_then(|()| match deadline {
+            Some(deadline) if Instant::now() >= deadline => Err(ttl_error()),
+            _ => Ok(()),
         });
+        let result = result.and_then(|()| if self.released_incomplete() { Err(released_error()) } else { Ok(()) });
+        if let Err(err) = result {
+            self.set_error(generation, err, BLOWN);
+        }
+    }
 
-        let mut data = Vec::new();
+    /// Declared length of the stream.
+    fn expected(&self) -> Option<u64> {
+        self.expected.load(Ordering::Acquire).checked_sub(1)
+    }
 
-        assert!(reader.read_to_end(&mut data).is_ok());
-        assert_eq!(&data, &[1]);
+    /// Returns `TimedOut` error if fuse is still armed past its TTL.
+    fn ttl_expired(&self) -> Option<IoError> {
+        let expired = self.deadline().is_some_and(|deadline| Instant::now() >= deadline);
+        if expired && self.is_guarded() {
+            return Some(ttl_error())
+        }
+        None
     }
+}
 
-    #[test]
-    fn test_fused_panic() {
-        let (reader, mut writer) = pipe();
+/// Exclusive arming of a `Fuse`; each clone of the fuse has its own.
+#[cfg(feature = "std")]
+#[derive(Debug, Default)]
+struct ArmSlot {
+    armed: AtomicBool,
+    // callers of `arm` waiting for `FuseGuard` to be released
+    waiting: AtomicUsize,
+    lock: Mutex<()>,
+    released: Condvar,
+}
 
-        let (mut reader, fuse) = fuse(reader);
+#[cfg(feature = "std")]
+impl ArmSlot {
+    /// Waits for `FuseGuard` to be released and takes its place; returns `false` if it was not
+    /// released by `until`.
+    fn acquire(&self, until: Option<Instant>) -> bool {
+        let try_acquire = || self.armed.compare_exchange(false, true, Ordering::SeqCst, Ordering::SeqCst).is_ok();
+        if try_acquire() {
+            return true
+        }
+        if until.is_some_and(|until| Instant::now() >= until) {
+            return false
+        }
+        self.waiting.fetch_add(1, Ordering::SeqCst);
+        let mut lock = self.lock.lock().unwrap_or_else(|err| err.into_inner());
+        let acquired = loop {
+            if try_acquire() {
+                break true
+            }
+            lock = match until.map(|until| until.saturating_duration_since(Instant::now())) {
+                None => self.released.wait(lock).unwrap_or_else(|err| err.into_inner()),
+                Some(timeout) if timeout.is_zero() => break false,
+                Some(timeout) => self.released.wait_timeout(lock, timeout).unwrap_or_else(|err| err.into_inner()).0,
+            };
+        };
+        drop(lock);
+        self.waiting.fetch_sub(1, Ordering::SeqCst);
+        acquired
+    }
 
-        thread::spawn(move || {
-            let _fuse = fuse.arm().unwrap();
-            writer.write(&[1]).unwrap();
-            panic!("boom");
-        });
+    fn release(&self) {
+        self.armed.store(false, Ordering::SeqCst);
+        if self.waiting.load(Ordering::SeqCst) > 0 {
+            let _lock = self.lock.lock().unwrap_or_else(|err| err.into_inner());
+            self.released.notify_all();
+        }
+    }
+}
 
-        let mut data = Vec::new();
+#[cfg(feature = "std")]
+fn panic_error(panic: panic::PanicInfo) -> IoError {
+    let mut message = "writer end dropped due to panic".to_owned();
+    if let Some(location) = &panic.location {
+        message.push_str(&format!(" at {}", location));
+    }
+    if let Some(msg) = &panic.msg {
+        message.push_str(&format!(": {}", msg));
+    }
+    FuseError::new_io(ErrorKind::BrokenPipe, BlowReason::Panic { msg: panic.msg, location: panic.location }, message)
+}
 
-        assert_eq!(reader.read_to_end(&mut data).unwrap_err().kind(), ErrorKind::BrokenPipe);
-        assert_eq!(&data, &[1]);
+#[cfg(feature = "std")]
+fn ttl_error() -> IoError {
+    FuseError::new_io(ErrorKind::TimedOut, BlowReason::Timeout, "stream did not complete within its TTL")
+}
+
+#[cfg(feature = "std")]
+fn incomplete_error() -> IoError {
+    FuseError::new_io(ErrorKind::UnexpectedEof, BlowReason::Incomplete, "stream ended before writer signalled completion")
+}
+
+#[cfg(feature = "std")]
+fn released_error() -> IoError {
+    FuseError::new_io(ErrorKind::BrokenPipe, BlowReason::ReaderGone, "reader end released the fuse without completing")
+}
+
+#[cfg(feature = "std")]
+fn truncated_error(position: u64, expected: u64) -> IoError {
+    let message = format!("stream ended after {} of {} expected bytes", position, expected);
+    FuseError::new_io(ErrorKind::UnexpectedEof, BlowReason::Incomplete, message)
+}
+
+#[cfg(feature = "std")]
+type FuseState = Arc<FuseShared>;
+
+#[cfg(feature = "std")]
+fn new_fuse_state() -> FuseState {
+    Arc::new(FuseShared::new(Ok(())))
+}
+
+/// Checks the fuse; the error it was blown with stays until taken with `take_fuse_error`.
+#[cfg(feature = "std")]
+fn check_fuse(fuse: &FuseState) -> FuseStatus {
+    check_fuse_with(fuse, false)
+}
+
+/// Like `check_fuse` but with `fail_fast` reports blown fuse while other guards are still alive.
+#[cfg(feature = "std")]
+fn check_fuse_with(fuse: &FuseState, fail_fast: bool) -> FuseStatus {
+    // guards record the outcome before they are released so it is seen if they are not
+    let guarded = fuse.is_guarded();
+    let armed = guarded || fuse.is_armed();
+    match fuse.status() {
+        POISONED => return FuseStatus::Poisoned(fuse.panic_info()),
+        // error of armed fuse is delivered after the guard is released
+        BLOWN if fail_fast || !guarded => if let Some(err) = fuse.peek_error() {
+            return FuseStatus::Blown(err)
+        },
+        _ => (),
     }
+    if armed {
+        FuseStatus::Armed
+    } else if fuse.completed.load(Ordering::Acquire) {
+        FuseStatus::Completed
+    } else {
+        FuseStatus::Unarmed
+    }
+}
 
-    #[test]
-    fn test_fused_blow() {
-        let (reader, mut writer) = pipe();
+/// Takes the error the fuse was blown with once its guard was released.
+#[cfg(feature = "std")]
+fn take_fuse_error(fuse: &FuseState) -> Option<IoError> {
+    if fuse.is_guarded() {
+        return None
+    }
+    fuse.take_error()
+}
 
-        let (mut reader, fuse) = fuse(reader);
+/// Checks the fuse returning error that the reader end should fail with after reaching EOF.
+#[cfg(feature = "std")]
+fn eof_error(fuse: &FuseState) -> Option<IoError> {
+    eof_error_with(fuse, false)
+}
 
-        thread::spawn(move || {
-            let fuse = fuse.arm().unwrap();
-            writer.write(&[1]).unwrap();
-            fuse.blow(IoError::new(ErrorKind::UnexpectedEof, "uh! oh!"))
-        });
+/// Like `eof_error` but with `fail_fast` checks the fuse like `check_fuse_with`.
+#[cfg(feature = "std")]
+fn eof_error_with(fuse: &FuseState, fail_fast: bool) -> Option<IoError> {
+    if fuse.status() == POISONED {
+        return Some(fuse.error().as_ref().map_or_else(|| panic_error(Default::default()), FuseError::duplicate))
+    }
+    check_fuse_with(fuse, fail_fast).into_eof_error()
+}
 
-        let mut data = Vec::new();
+/// Like `fuse` but names the fuse; the name is included in log events of the `log` feature.
+#[cfg(feature = "std")]
+pub fn fuse_named<R: Read>(name: impl Into<String>, reader: R) -> (FusedReader<R>, Fuse) {
+    let (reader, fuse) = fuse(reader);
+    let _ = fuse.0.name.set(name.into());
+    (reader, fuse)
+}
 
-        assert_eq!(reader.read_to_end(&mut data).unwrap_err().kind(), ErrorKind::UnexpectedEof);
+/// Fuses reader so that if writer thread dies while holding armed fuse the reader will get `BrokenPipe` error.
+#[cfg(feature = "std")]
+pub fn fuse<R: Read>(reader: R) -> (FusedReader<R>, Fuse) {
+    let reader_fuse = new_fuse_state();
+    let writer_fuse = reader_fuse.clone();
+( FusedReader {
+            reader: DropGuard::new(reader, DropBehavior::Detach, vec![reader_fuse.clone()]),
+            fuse: reader_fuse,
+            also_fused: Vec::new(),
+            position: 0,
+            blow_reason: None,
+            failed_fuse: None,
+            policy: FusePolicy::DrainToEof,
+            require_complete: false,
+            timeout: None,
+            progress: Instant::now(),
+        },
+        Fuse::from_state(writer_fuse),
+    )
+}
+
+/// Like `fuse` but the reader checks the fuse according to given policy.
+#[cfg(feature = "std")]
+pub fn fuse_with<R: Read>(reader: R, policy: FusePolicy) -> (FusedReader<R>, Fuse) {
+    let (mut reader, fuse) = fuse(reader);
+    reader.policy = policy;
+    (reader, fuse)
+}
+
+/// Like `fuse` but the fuse is blown with errors of type `E`.
+///
+/// The reader fails with `ErrorKind::Other` error naming the error type after reaching EOF; the
+/// original error can be taken with `FusedReader::take_typed_error`.
+#[cfg(feature = "std")]
+pub fn fuse_typed<R: Read, E>(reader: R) -> (FusedReader<R>, Fuse<E>) {
+    let (reader, fuse) = fuse(reader);
+    (reader, Fuse::from_state(fuse.0))
+}
+
+/// Reader that will fail with I/O error if fuse was blown.
+#[cfg(feature = "std")]
+#[derive(Debug)]
+pub struct FusedReader<R: Read> {
+    reader: DropGuard<R>,
+    fuse: FuseState,
+    also_fused: Vec<FuseState>,
+    position: u64,
+    blow_reason: Option<BlowReason>,
+    failed_fuse: Option<usize>,
+    policy: FusePolicy,
+    require_complete: bool,
+    timeout: Option<Duration>,
+    progress: Instant,
+}
+
+/// When `FusedReader` reports blown fuse.
+#[cfg(feature = "std")]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum FusePolicy {
+    /// Fail after reading the stream to EOF so that all data written before the fuse was blown is
+    /// delivered.
+    #[default]
+    DrainToEof,
+    /// Check the fuse before every read and fail as soon as it was blown, even while other guards
+    /// are still armed, discarding data still buffered in the stream.
+    FailFast,
+}
+
+/// What happens when `FusedReader` is dropped.
+#[cfg(feature = "std")]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum DropBehavior {
+    /// Drop the inner reader.
+    #[default]
+    Detach,
+    /// Read and discard the rest of the stream so that the writer is not left blocked.
+    ///
+    /// Note that dropping blocks until the writer finishes.
+    Drain,
+    /// Signal the writer that the rest of the stream is not wanted; see `FuseGuard::is_cancelled`.
+    Cancel,
+}
+
+/// Status of the fuse.
+///
+/// This is a snapshot of the state shared by the fuse and the reader; it tells apart writer that
+/// has not started yet from one that finished cleanly or died.
+#[cfg(feature = "std")]
+#[derive(Debug)]
+pub enum FuseStatus {
+    /// Fuse was not armed or guard got dropped.
+    Unarmed,
+    /// Fuse armed.
+    Armed,
+    /// Fuse not armed any more after the writer signalled completion with `complete`.
+    Completed,
+    /// Fuse blown with custom error.
+    Blown(IoError),
+    /// Fuse blown by panic unwind.
+    Poisoned(PanicInfo),
+}
+
+#[cfg(feature = "std")]
+impl FuseStatus {
+    /// Error that the reader end should fail with after reaching EOF.
+    fn into_eof_error(self) -> Option<IoError> {
+        match self {
+            FuseStatus::Blown(err) => Some(err),
+            FuseStatus::Poisoned(panic) => Some(panic_error(panic)),
+            FuseStatus::Unarmed |
+            FuseStatus::Armed |
+            FuseStatus::Completed => None,
+        }
+    }
+}
+
+#[cfg(feature = "std")]
+impl<R: Read> FusedReader<R> {
+    /// Checks status of the fuse.
+    ///
+    /// Once blown the fuse is reported as `FuseStatus::Blown` with a copy of its error, and the
+    /// reader fails with it at EOF, until the error is taken with `take_error`.
+    ///
+    /// With fuses attached by `also_fused_by` the first blown or poisoned fuse is reported, otherwise
+    /// `FuseStatus::Armed` if any of them is armed and `FuseStatus::Completed` if all of them
+    /// completed.
+    pub fn check_fuse(&self) -> FuseStatus {
+        let mut armed = false;
+        let mut completed = true;
+        let fail_fast = self.policy == FusePolicy::FailFast;
+        for fuse in self.fuses() {
+            match check_fuse_with(fuse, fail_fast) {
+                FuseStatus::Unarmed => completed = false,
+                FuseStatus::Armed => armed = true,
+                FuseStatus::Completed => (),
+                failed => return failed,
+            }
+        }
+        match (armed, completed) {
+            (true, _) => FuseStatus::Armed,
+            (false, true) => FuseStatus::Completed,
+            (false, false) => FuseStatus::Unarmed,
+        }
+    }
+
+    /// Attaches another fuse so that this reader fails if any of its fuses was blown.
+    ///
+    /// This is useful when the stream passes through several stages, each holding its own armed
+    /// fuse. All fuses are checked in order of attaching after reaching EOF; see `failed_fuse` to
+    /// find out which stage failed.
+    pub fn also_fused_by<E>(&mut self, fuse: &Fuse<E>) {
+        self.also_fused.push(fuse.0.clone());
+        self.reader.fuses.push(fuse.0.clone());
+    }
+
+    /// Calls `callback` with the error as soon as own fuse of this reader gets blown.
+    ///
+    /// This lets other components stop their work right away instead of discovering the failure
+    /// at EOF. The callback runs on the thread that blew the fuse, or right away if it already was
+    /// blown, and is called again if the fuse is blown again after `take_error`.
+    pub fn on_blow(&self, callback: impl Fn(&IoError) + Send + Sync + 'static) {
+        self.fuse.on_blow(Arc::new(callback));
+    }
+
+    /// Signals the writers of this reader that the rest of the stream is not wanted.
+    ///
+    /// Writers observe it with `FuseGuard::is_cancelled` or `FuseGuard::check_cancel` which fails
+    /// with `BrokenPipe` error with `BlowReason::ReaderCancelled` carrying `reason`.
+    pub fn cancel(&self, reason: impl Into<String>) {
+        let reason = reason.into();
+        for fuse in self.fuses() {
+            fuse.cancel(Some(reason.clone()));
+        }
+    }
+
+    /// Number of bytes consumed from the stream, the same as `position`.
+    pub fn bytes_read(&self) -> u64 {
+        self.position
+    }
+
+    /// Returns handle observing how far this reader consumed the stream from other threads.
+    pub fn progress(&self) -> ProgressHandle {
+        ProgressHandle(self.fuse.clone())
+    }
+
+    /// Replaces own fuse of this reader with a new one returned for the producer taking over the
+    /// stream.
+    ///
+    /// The old fuse is no longer checked and any error it was blown with is discarded; use
+    /// `check_fuse` before the swap to handle it.
+    pub fn replace_fuse(&mut self) -> Fuse {
+        self.fuse = new_fuse_state();
+        self.fuse.consumed.store(self.position, Ordering::Relaxed);
+        Fuse::from_state(self.fuse.clone())
+    }
+
+    /// Takes the error the first blown fuse of this reader was blown with, if any.
+    ///
+    /// The fuse is reported as `FuseStatus::Unarmed` afterwards and the reader no longer fails with
+    /// the error at EOF. Errors of poisoned fuses stay for good.
+    pub fn take_error(&mut self) -> Option<IoError> {
+        self.fuses().find_map(take_fuse_error)
+    }
+
+    /// Takes the typed error a `Fuse<E>` of this reader was blown with, if any.
+    ///
+    /// The error the reader failed with at EOF only names the error type.
+    pub fn take_typed_error<E: 'static>(&mut self) -> Option<E> {
+        self.fuses().find_map(|fuse| fuse.take_payload())
+    }
+
+    /// Reason of the fuse error this reader failed with, if any.
+    pub fn blow_reason(&self) -> Option<&BlowReason> {
+        self.blow_reason.as_ref()
+    }
+
+    /// Returns the event that closed one side of the stream first as recorded on the fuse by the
+    /// transport, e.g. `ring_pipe` pipe with the fuse attached.
+    pub fn first_close(&self) -> Option<ring_pipe::CloseEvent> {
+        self.fuse.first_close()
+    }
+
+    /// Index of the fuse this reader failed with, if any.
+    ///
+    /// Own fuse of the reader has index `0` and fuses attached with `also_fused_by` follow in
+    /// order of attaching.
+    pub fn failed_fuse(&self) -> Option<usize> {
+        self.failed_fuse
+    }
+
+    /// Returns when this reader reports blown fuse.
+    pub fn policy(&self) -> FusePolicy {
+        self.policy
+    }
+
+    /// Returns what happens when this reader is dropped.
+    pub fn drop_behavior(&self) -> DropBehavior {
+        self.reader.behavior
+    }
+
+    /// Sets what happens when this reader is dropped.
+    pub fn set_drop_behavior(&mut self, drop_behavior: DropBehavior) {
+        self.reader.behavior = drop_behavior;
+    }
+
+    /// Returns `true` if this reader fails at EOF unless the writer signalled completion.
+    pub fn requires_complete(&self) -> bool {
+        self.require_complete
+    }
+
+    /// Sets whether this reader fails with `UnexpectedEof` error with `BlowReason::Incomplete`
+    /// after reaching EOF if the writer did not call `Fuse::complete` or `FuseGuard::complete`.
+    ///
+    /// This catches writers that returned early, before arming the fuse or between guards, that
+    /// would otherwise look like the stream was written in full. Only own fuse of the reader is
+    /// checked for completion.
+    pub fn set_require_complete(&mut self, require: bool) {
+        self.require_complete = require;
+    }
+
+    /// Returns reference to the inner reader.
+    pub fn get_ref(&self) -> &R {
+        &self.reader
+    }
+
+    /// Returns mutable reference to the inner reader.
+    ///
+    /// Data read directly from the inner reader is not counted by `position`.
+    pub fn get_mut(&mut self) -> &mut R {
+        &mut self.reader
+    }
+
+    /// Returns inner reader.
+    ///
+    /// The drop behavior is not applied.
+    pub fn into_inner(self) -> R {
+        self.into_parts().0
+    }
+
+    /// Detaches the fuses returning inner reader and the error this reader would fail with after
+    /// reaching EOF, if any.
+    ///
+    /// Fuses still armed are not waited for. The drop behavior is not applied.
+    pub fn unfuse(mut self) -> (R, Option<IoError>) {
+        let err = self.end_error();
+        (self.into_inner(), err)
+    }
+
+    /// Splits into inner reader and a handle keeping the fuse and any pending fuse error.
+    ///
+    /// Use `FuseHandle::attach` to fuse the inner reader, or another reader continuing the stream,
+    /// again. The drop behavior is not applied.
+    pub fn into_parts(self) -> (R, FuseHandle) {
+        let drop_behavior = self.reader.behavior;
+        (self.reader.into_inner(), FuseHandle {
+            fuse: self.fuse,
+            also_fused: self.also_fused,
+            position: self.position,
+            blow_reason: self.blow_reason,
+            failed_fuse: self.failed_fuse,
+            drop_behavior,
+            policy: self.policy,
+            require_complete: self.require_complete,
+            timeout: self.timeout,
+            progress: self.progress,
+        })
+    }
+
+    /// Advances position of this reader by bytes consumed from the stream.
+    fn advance(&mut self, bytes: u64) {
+        self.position += bytes;
+        self.fuse.consumed.store(self.position, Ordering::Relaxed);
+    }
+
+    fn fuses(&self) -> impl Iterator<Item = &FuseState> {
+        std::iter::once(&self.fuse).chain(&self.also_fused)
+    }
+
+    /// Checks the fuses returning error this reader should fail with after reaching EOF.
+    fn eof_error(&mut self) -> Option<IoError> {
+        let fail_fast = self.policy == FusePolicy::FailFast;
+        let (index, err) = self.fuses().enumerate().find_map(|(index, fuse)| eof_error_with(fuse, fail_fast).map(|err| (index, err)))?;
+        self.failed_fuse = Some(index);
+        Some(err)
+    }
+
+    /// Like `eof_error` but also fails incomplete stream if completion is required or the stream
+    /// is shorter than declared by the writer.
+    fn end_error(&mut self) -> Option<IoError> {
+        self.eof_error().or_else(|| {
+            let err = match self.fuse.expected() {
+                Some(expected) if self.position < expected => truncated_error(self.position, expected),
+                _ if self.require_complete && !self.fuse.completed.load(Ordering::Acquire) => incomplete_error(),
+                _ => return None,
+            };
+            self.failed_fuse = Some(0);
+            Some(err)
+        })
+    }
+
+    /// Returns `TimedOut` error if any of the fuses is still armed past its TTL.
+    fn ttl_expired(&mut self) -> Option<IoError> {
+        let (index, err) = self.fuses().enumerate().find_map(|(index, fuse)| fuse.ttl_expired().map(|err| (index, err)))?;
+        self.failed_fuse = Some(index);
+        Some(err)
+    }
+
+    /// Checks the fuses before reading from the inner reader.
+    fn check_read(&mut self) -> Result<(), IoError> {
+        if let Some(err) = self.ttl_expired() {
+            return Err(self.fuse_failed(err))
+        }
+        if self.policy == FusePolicy::FailFast {
+            if let Some(err) = self.eof_error() {
+                return Err(self.fuse_failed(err))
+            }
+        }
+        Ok(())
+    }
+
+    /// Reads from the inner reader with `read` retrying reads timed out while the writer makes
+    /// progress.
+    fn read_with(&mut self, read: impl FnMut(&mut R) -> Result<usize, IoError>) -> Result<usize, IoError> {
+        self.read_with_deadline(None, read)
+    }
+
+    /// Like `read_with` but returns error of the inner reader without retrying once `deadline`
+    /// has passed.
+    fn read_with_deadline(&mut self, deadline: Option<Instant>, mut read: impl FnMut(&mut R) -> Result<usize, IoError>) -> Result<usize, IoError> {
+        self.check_read()?;
+        let bytes = loop {
+            match read(&mut self.reader) {
+                Ok(bytes) => break bytes,
+                Err(err) if deadline.is_some_and(|deadline| Instant::now() >= deadline) => return Err(err),
+                Err(err) => self.check_stalled(err)?,
+            }
+        };
+        if bytes > 0 && self.timeout.is_some() {
+            self.progress = Instant::now();
+        }
+        self.finish_read(bytes)
+    }
+
+    /// Passes error of the inner reader through unless it is read timeout while the writer still
+    /// makes progress.
+    ///
+    /// Fails with `TimedOut` error if neither data nor heartbeat arrived within the timeout set by
+    /// `with_timeout`, or with the fuse error if the fuse was blown.
+    fn check_stalled(&mut self, err: IoError) -> Result<(), IoError> {
+        let timeout = match self.timeout {
+            Some(timeout) if err.kind() == ErrorKind::WouldBlock || err.kind() == ErrorKind::TimedOut => timeout,
+            _ => return Err(err),
+        };
+        if let Some(heartbeat) = self.fuse.heartbeat() {
+            self.progress = self.progress.max(heartbeat);
+        }
+        if self.progress.elapsed() < timeout {
+            return Ok(())
+        }
+        let err = self.eof_error().unwrap_or_else(|| {
+            FuseError::new_io(ErrorKind::TimedOut, BlowReason::Timeout, "writer made no progress within the timeout")
+        });
+        Err(self.fuse_failed(err))
+    }
+
+    /// Accounts bytes read from the inner reader checking the fuses at EOF.
+    fn finish_read(&mut self, bytes: usize) -> Result<usize, IoError> {
+        if bytes == 0 {
+            if let Some(err) = self.end_error() {
+                return Err(self.fuse_failed(err))
+            }
+        }
+        self.advance(bytes as u64);
+        Ok(bytes)
+    }
+
+    fn fuse_failed(&mut self, err: IoError) -> IoError {
+        self.blow_reason = FuseError::from_io(&err).map(|err| err.reason().clone());
+        err
+    }
+}
+
+/// Fuse of a `FusedReader` split with `into_parts`.
+#[cfg(feature = "std")]
+#[derive(Debug)]
+pub struct FuseHandle {
+    fuse: FuseState,
+    also_fused: Vec<FuseState>,
+    position: u64,
+    blow_reason: Option<BlowReason>,
+    failed_fuse: Option<usize>,
+    drop_behavior: DropBehavior,
+    policy: FusePolicy,
+    require_complete: bool,
+    timeout: Option<Duration>,
+    progress: Instant,
+}
+
+#[cfg(feature = "std")]
+impl FuseHandle {
+    /// Fuses given reader with this fuse restoring the state of the split `FusedReader`.
+    pub fn attach<R: Read>(self, reader: R) -> FusedReader<R> {
+        let fuses = std::iter::once(&self.fuse).chain(&self.also_fused).cloned().collect();
+        FusedReader {
+            reader: DropGuard::new(reader, self.drop_behavior, fuses),
+            fuse: self.fuse,
+            also_fused: self.also_fused,
+            position: self.position,
+            blow_reason: self.blow_reason,
+            failed_fuse: self.failed_fuse,
+            policy: self.policy,
+            require_complete: self.require_complete,
+            timeout: self.timeout,
+            progress: self.progress,
+        }
+    }
+}
+
+/// Inner reader of `FusedReader` applying its drop behavior.
+///
+/// It is kept apart from `FusedReader` so that `FusedReader::into_parts` can move the reader out
+/// without applying the drop behavior.
+#[cfg(feature = "std")]
+#[derive(Debug)]
+struct DropGuard<R: Read> {
+    // taken by `into_inner`
+    reader: Option<R>,
+    behavior: DropBehavior,
+    // all fuses of the reader for `DropBehavior::Cancel`
+    fuses: Vec<FuseState>,
+}
+
+#[cfg(feature = "std")]
+impl<R: Read> DropGuard<R> {
+    fn new(reader: R, behavior: DropBehavior, fuses: Vec<FuseState>) -> DropGuard<R> {
+        DropGuard {
+            reader: Some(reader),
+            behavior,
+            fuses,
+        }
+    }
+
+    fn into_inner(mut self) -> R {
+        self.reader.take().expect("reader not taken")
+    }
+}
+
+#[cfg(feature = "std")]
+impl<R: Read> Deref for DropGuard<R> {
+    type Target = R;
+
+    fn deref(&self) -> &R {
+        self.reader.as_ref().expect("reader not taken")
+    }
+}
+
+#[cfg(feature = "std")]
+impl<R: Read> DerefMut for DropGuard<R> {
+    fn deref_mut(&mut self) -> &mut R {
+        self.reader.as_mut().expect("reader not taken")
+    }
+}
+
+#[cfg(feature = "std")]
+impl<R: Read> Drop for DropGuard<R> {
+    fn drop(&mut self) {
+        let reader = match self.reader.as_mut() {
+            Some(reader) => reader,
+            None => return,
+        };
+        match self.behavior {
+            DropBehavior::Detach => (),
+            DropBehavior::Drain => {
+                let _ = io::copy(reader, &mut io::sink());
+            }
+            DropBehavior::Cancel => {
+                for fuse in &self.fuses {
+                    fuse.cancel(None);
+                }
+            }
+        }
+    }
+}
+
+#[cfg(feature = "std")]
+impl<R: Read> Read for FusedReader<R> {
+    fn read(&mut self, buf: &mut [u8]) -> Result<usize, IoError> {
+        // let it read to end before checking fuse
+        self.read_with(|reader| reader.read(buf))
+    }
+
+    fn read_vectored(&mut self, bufs: &mut [IoSliceMut<'_>]) -> Result<usize, IoError> {
+        self.read_with(|reader| reader.read_vectored(bufs))
+    }
+
+    fn read_to_end(&mut self, buf: &mut Vec<u8>) -> Result<usize, IoError> {
+        if self.policy == FusePolicy::FailFast || self.timeout.is_some() {
+            // the fuse needs to be checked before every read
+            let start = buf.len();
+            let mut chunk = [0; 8 * 1024];
+            loop {
+                match self.read(&mut chunk) {
+                    Ok(0) => return Ok(buf.len() - start),
+                    Ok(bytes) => buf.extend_from_slice(&chunk[..bytes]),
+                    Err(err) if err.kind() == ErrorKind::Interrupted => (),
+                    Err(err) => return Err(err),
+                }
+            }
+        }
+        self.check_read()?;
+
+        let start = buf.len();
+        let result = self.reader.read_to_end(buf);
+        self.advance((buf.len() - start) as u64);
+        let bytes = result?;
+        self.finish_read(0).map(|_| bytes)
+    }
+
+    fn read_to_string(&mut self, buf: &mut String) -> Result<usize, IoError> {
+        if self.policy == FusePolicy::FailFast || self.timeout.is_some() {
+            let mut data = Vec::new();
+            let bytes = self.read_to_end(&mut data)?;
+            buf.push_str(&String::from_utf8(data).map_err(|err| IoError::new(ErrorKind::InvalidData, err))?);
+            return Ok(bytes)
+        }
+        self.check_read()?;
+
+        let start = buf.len();
+        let result = self.reader.read_to_string(buf);
+        self.advance((buf.len() - start) as u64);
+        let bytes = result?;
+        self.finish_read(0).map(|_| bytes)
+    }
+}
+
+#[cfg(feature = "std")]
+impl<R: BufRead> BufRead for FusedReader<R> {
+    fn fill_buf(&mut self) -> Result<&[u8], IoError> {
+        self.check_read()?;
+
+        // let it read to end before checking fuse
+        while let Err(err) = self.reader.fill_buf() {
+            self.check_stalled(err)?;
+        }
+        if self.reader.fill_buf()?.is_empty() {
+            if let Some(err) = self.end_error() {
+                return Err(self.fuse_failed(err))
+            }
+        }
+        self.reader.fill_buf()
+    }
+
+    fn consume(&mut self, amt: usize) {
+        self.reader.consume(amt);
+        self.advance(amt as u64);
+        if amt > 0 && self.timeout.is_some() {
+            self.progress = Instant::now();
+        }
+    }
+}
+
+#[cfg(feature = "std")]
+impl<R: Read + Seek> Seek for FusedReader<R> {
+    fn seek(&mut self, pos: SeekFrom) -> Result<u64, IoError> {
+        self.reader.seek(pos)
+    }
+}
+
+#[cfg(feature = "std")]
+impl<R: Read + Write> Write for FusedReader<R> {
+    fn write(&mut self, buf: &[u8]) -> Result<usize, IoError> {
+        self.reader.write(buf)
+    }
+
+    fn flush(&mut self) -> Result<(), IoError> {
+        self.reader.flush()
+    }
+}
+
+#[cfg(all(feature = "std", unix))]
+impl<R: Read + AsRawFd> AsRawFd for FusedReader<R> {
+    fn as_raw_fd(&self) -> RawFd {
+        self.reader.as_raw_fd()
+    }
+}
+
+#[cfg(all(feature = "std", unix))]
+impl<R: Read + AsFd> AsFd for FusedReader<R> {
+    fn as_fd(&self) -> BorrowedFd<'_> {
+        self.reader.as_fd()
+    }
+}
+
+#[cfg(all(feature = "std", windows))]
+impl<R: Read + AsRawHandle> AsRawHandle for FusedReader<R> {
+    fn as_raw_handle(&self) -> RawHandle {
+        self.reader.as_raw_handle()
+    }
+}
+
+#[cfg(all(feature = "std", windows))]
+impl<R: Read + AsHandle> AsHandle for FusedReader<R> {
+    fn as_handle(&self) -> BorrowedHandle<'_> {
+        self.reader.as_handle()
+    }
+}
+
+#[cfg(all(feature = "std", windows))]
+impl<R: Read + AsRawSocket> AsRawSocket for FusedReader<R> {
+    fn as_raw_socket(&self) -> RawSocket {
+        self.reader.as_raw_socket()
+    }
+}
+
+#[cfg(all(feature = "std", windows))]
+impl<R: Read + AsSocket> AsSocket for FusedReader<R> {
+    fn as_socket(&self) -> BorrowedSocket<'_> {
+        self.reader.as_socket()
+    }
+}
+
+/// Cheap handle observing progress of the `FusedReader` from other threads.
+///
+/// Obtain it with `FusedReader::progress` or `Fuse::progress`; the writer can use it for stall
+/// detection or progress reporting.
+#[cfg(feature = "std")]
+#[derive(Debug, Clone)]
+pub struct ProgressHandle(FuseState);
+
+#[cfg(feature = "std")]
+impl ProgressHandle {
+    /// Number of bytes the reader consumed from the stream so far.
+    pub fn bytes_read(&self) -> u64 {
+        self.0.consumed.load(Ordering::Relaxed)
+    }
+}
+
+/// Fuse that can be armed.
+///
+/// The fuse is blown with errors of type `E`; see `fuse_typed`.
+///
+/// Clones of the fuse can be armed at the same time, for example by threads writing to the same
+/// stream. The reader fails if any of their guards was dropped due to panic or with the error of
+/// the first guard that blew the fuse; errors of the other guards are discarded.
+#[cfg(feature = "std")]
+#[derive(Debug)]
+pub struct Fuse<E = IoError>(FuseState, ArmSlot, PhantomData<fn(E)>);
+
+/// Fuse blown with `IoError`; see `Fuse`.
+#[cfg(feature = "std")]
+pub type IoFuse = Fuse<IoError>;
+
+#[cfg(feature = "std")]
+impl<E> Fuse<E> {
+    /// Creates fuse not attached to any reader yet.
+    ///
+    /// Attach it to a reader with `FusedReader::also_fused_by`.
+    pub fn new() -> Fuse<E> {
+        Fuse::from_state(new_fuse_state())
+    }
+
+    pub(crate) fn from_state(state: FuseState) -> Fuse<E> {
+        Fuse(state, ArmSlot::default(), PhantomData)
+    }
+
+    /// Arms the fuse.
+    ///
+    /// Blocks while `FuseGuard` of this fuse is alive; clones of the fuse can be armed at the same
+    /// time. Use `try_arm` or `arm_timeout` to not wait for it to be released.
+    ///
+    /// Returns `BrokenPipe` error if reader was dropped due to panic.
+    pub fn arm(&self) -> Result<FuseGuard<'_, E>, IoError> {
+        Ok(self.arm_until(None, None)?.expect("waited for the fuse"))
+    }
+
+    /// Arms the fuse unless `FuseGuard` of this fuse is alive, in which case `None` is returned.
+    ///
+    /// Returns `BrokenPipe` error if reader was dropped due to panic.
+    pub fn try_arm(&self) -> Result<Option<FuseGuard<'_, E>>, IoError> {
+        self.arm_until(Some(Instant::now()), None)
+    }
+
+    /// Like `arm` but waits at most `timeout` for `FuseGuard` of this fuse to be released,
+    /// returning `None` if it was not.
+    pub fn arm_timeout(&self, timeout: Duration) -> Result<Option<FuseGuard<'_, E>>, IoError> {
+        self.arm_until(Some(Instant::now() + timeout), None)
+    }
+
+    /// Arms the fuse with time to live.
+    ///
+    /// If the fuse is still armed after `ttl` elapsed the reader will fail with `TimedOut` error
+    /// regardless of stream activity. Expiry is checked on every read and when the guard is dropped.
+    ///
+    /// Returns `BrokenPipe` error if reader was dropped due to panic.
+    pub fn arm_with_ttl(&self, ttl: Duration) -> Result<FuseGuard<'_, E>, IoError> {
+        Ok(self.arm_until(None, Some(Instant::now() + ttl))?.expect("waited for the fuse"))
+    }
+
+    /// Calls `callback` with the error as soon as the fuse gets blown; see `FusedReader::on_blow`.
+    pub fn on_blow(&self, callback: impl Fn(&IoError) + Send + Sync + 'static) {
+        self.0.on_blow(Arc::new(callback));
+    }
+
+    /// Returns handle observing how far the reader consumed the stream.
+    pub fn progress(&self) -> ProgressHandle {
+        ProgressHandle(self.0.clone())
+    }
+
+    /// Returns name given to `fuse_named`.
+    pub fn name(&self) -> Option<&str> {
+        self.0.name.get().map(String::as_str)
+    }
+
+    /// Returns status of the fuse as seen by the reader end.
+    pub fn status(&self) -> FuseStatus {
+        check_fuse(&self.0)
+    }
+
+    /// Starts new generation of the fuse for the next writer session returning its number.
+    ///
+    /// The reader keeps reading the stream but no longer fails with error the fuse was blown or
+    /// poisoned with; TTL, completion and expected length are cleared too. Guards armed before the
+    /// reset no longer keep the fuse armed and errors they blow the fuse with are discarded, so a
+    /// writer session left hanging can be replaced. Arm clone of this fuse for the new session
+    /// if guard of the previous one may still be alive.
+    pub fn reset(&self) -> u64 {
+        self.0.reset()
+    }
+
+    /// Returns current generation of the fuse; see `reset`.
+    pub fn generation(&self) -> u64 {
+        self.0.generation()
+    }
+
+    /// Signals that the stream was written in full without arming the fuse.
+    ///
+    /// See `FusedReader::set_require_complete`.
+    pub fn complete(self) {
+        self.0.completed.store(true, Ordering::Release);
+        fuse_event!(debug, self.0, "completed");
+    }
+
+    fn arm_until(&self, wait: Option<Instant>, deadline: Option<Instant>) -> Result<Option<FuseGuard<'_, E>>, IoError> {
+        let poisoned = || FuseError::new_io(ErrorKind::BrokenPipe, BlowReason::ReaderGone, "reader end dropped due to panic");
+        if self.0.status() == POISONED {
+            return Err(poisoned())
+        }
+        if !self.1.acquire(wait) {
+            return Ok(None)
+        }
+        if self.0.status() == POISONED {
+            self.1.release();
+            return Err(poisoned())
+        }
+        let generation = self.0.add_guard();
+        if deadline.is_some() {
+            // guards armed without TTL leave TTL of other guards in place
+            self.0.set_deadline(deadline);
+        }
+        fuse_event!(debug, self.0, "armed");
+        Ok(Some(FuseGuard {
+            result: Ok(()),
+            shared: &self.0,
+            slot: &self.1,
+            generation,
+            deadline,
+            error: PhantomData,
+        }))
+    }
+}
+
+#[cfg(feature = "std")]
+impl Fuse {
+    /// Runs `f` with the fuse armed so that its failure always reaches the reader.
+    ///
+    /// Error returned by `f` blows the fuse and a copy of it is returned. If `f` panics the fuse is
+    /// blown with the panic message like with `FuseGuard::catching` and the panic continues to
+    /// unwind. The fuse is disarmed once `f` succeeds.
+    ///
+    /// Returns `BrokenPipe` error without calling `f` if reader was dropped due to panic.
+    pub fn armed<T>(&self, f: impl FnOnce(&mut FuseGuard<'_>) -> Result<T, IoError>) -> Result<T, IoError> {
+        let mut guard = self.arm()?;
+        match panic::catch_panic_message(|| f(&mut guard)) {
+            Ok(value) => Ok(value),
+            Err(err) => {
+                let copy = FuseError::duplicate(&err);
+                guard.blow(err);
+                Err(copy)
+            }
+        }
+    }
+}
+
+#[cfg(feature = "std")]
+impl<E> Clone for Fuse<E> {
+    /// Returns fuse of the same reader that can be armed independently of this one.
+    fn clone(&self) -> Fuse<E> {
+        Fuse::from_state(self.0.clone())
+    }
+}
+
+#[cfg(feature = "std")]
+impl<E> Default for Fuse<E> {
+    fn default() -> Fuse<E> {
+        Fuse::new()
+    }
+}
+
+/// Armed fuse that if dropped due to panic will signal reader to fail with `BrokenPipe` error.
+#[cfg(feature = "std")]
+#[derive(Debug)]
+pub struct FuseGuard<'a, E = IoError> {
+    // error the reader end will fail with once the guard is released
+    result: Result<(), IoError>,
+    shared: &'a FuseState,
+    slot: &'a ArmSlot,
+    generation: u64,
+    // TTL the guard was armed with
+    deadline: Option<Instant>,
+    error: PhantomData<fn(E)>,
+}
+
+/// Armed `IoFuse`.
+#[cfg(feature = "std")]
+pub type IoFuseGuard<'a> = FuseGuard<'a, IoError>;
+
+#[cfg(feature = "std")]
+impl<'a, E: Send + 'static> FuseGuard<'a, E> {
+    /// Blows the fuse with given error.
+    ///
+    /// The reader end will fail with this error after reaching EOF. Errors other than `IoError`
+    /// are reported as `ErrorKind::Other` error naming their type and can be taken with
+    /// `FusedReader::take_typed_error`.
+    pub fn blow(mut self, err: E) {
+        self.result = Err(match (Box::new(err) as Box<dyn Any + Send>).downcast::<IoError>() {
+            Ok(err) => FuseError::explicit(*err),
+            Err(err) => {
+                *self.shared.payload() = Some(err);
+                let message = format!("writer end failed with {}", std::any::type_name::<E>());
+                FuseError::new_io(ErrorKind::Other, BlowReason::ExplicitError, message)
+            }
+        });
+    }
+}
+
+#[cfg(feature = "std")]
+impl<'a, E> FuseGuard<'a, E> {
+    /// Disarms the fuse signalling that the stream was written in full.
+    ///
+    /// See `FusedReader::set_require_complete`.
+    pub fn complete(self) {
+        self.shared.completed.store(true, Ordering::Release);
+        fuse_event!(debug, self.shared, "completed");
+    }
+
+    /// Returns `TimedOut` error if the fuse was armed with TTL that has expired.
+    ///
+    /// Writer can use this to stop producing data that the reader will reject anyway.
+    pub fn check_ttl(&self) -> Result<(), IoError> {
+        match self.deadline {
+            Some(deadline) if Instant::now() >= deadline => Err(ttl_error()),
+            _ => Ok(()),
+        }
+    }
+
+    /// Returns `true` if the reader end cancelled the stream with `FusedReader::cancel` or was
+    /// dropped with `DropBehavior::Cancel`.
+    ///
+    /// Writer can use this to stop producing data nobody will read.
+    pub fn is_cancelled(&self) -> bool {
+        self.shared.cancelled.load(Ordering::Acquire)
+    }
+
+    /// Returns `BrokenPipe` error with `BlowReason::ReaderCancelled` if the reader end cancelled
+    /// the stream; see `is_cancelled`.
+    pub fn check_cancel(&self) -> Result<(), IoError> {
+        self.shared.check_cancel()
+    }
+
+    /// Blows the fuse with error of given kind and message caused by `source`.
+    ///
+    /// The reader end will fail with this error after reaching EOF; `source` is its
+    /// `Error::source` and can be downcast to its original type. See `FuseError`.
+    pub fn blow_with_source(mut self, kind: ErrorKind, msg: impl Into<String>, source: impl Into<Box<dyn std::error::Error + Send + Sync>>) {
+        self.result = Err(FuseError::with_source(kind, msg.into(), source.into()));
+    }
+
+    /// Declares total length of the stream.
+    ///
+    /// The reader fails with `UnexpectedEof` error with `BlowReason::Incomplete` if it reaches EOF
+    /// before reading `len` bytes, even if the writer finished cleanly.
+    pub fn expect_len(&self, len: u64) {
+        self.shared.expected.store(len + 1, Ordering::Release);
+    }
+
+    /// Adds `len` bytes to the declared length of the stream; see `expect_len`.
+    ///
+    /// Writer streaming data of known size in parts can declare each part before writing it.
+    pub fn add_expected(&self, len: u64) {
+        let _ = self.shared.expected.fetch_update(Ordering::AcqRel, Ordering::Acquire, |expected| Some(expected.max(1) + len));
+    }
+
+    /// Returns generation of the fuse this guard was armed in; see `Fuse::reset`.
+    pub fn generation(&self) -> u64 {
+        self.generation
+    }
+
+    /// Signals the reader that the writer is alive while it is not writing any data.
+    ///
+    /// See `FusedReader::with_timeout`.
+    pub fn heartbeat(&self) {
+        store_instant(&self.shared.heartbeat, Some(Instant::now()));
+    }
+
+    /// Runs `f` capturing message of its panic.
+    ///
+    /// If `f` panics the reader end fails with `BrokenPipe` error carrying the panic message, also
+    /// available in `BlowReason::Panic`, and the panic continues to unwind. Use `install_panic_hook`
+    /// to capture messages of all writer panics.
+    pub fn catching<T>(&self, f: impl FnOnce() -> T) -> T {
+        panic::catch_panic_message(f)
+    }
+}
+
+#[cfg(feature = "std")]
+impl<'a> FuseGuard<'a> {
+    /// Blows the fuse with numeric failure code and message.
+    ///
+    /// The reader end will fail with `ErrorKind::Other` error after reaching EOF; the code can be
+    /// retrieved with `error_code`.
+    pub fn blow_code(self, code: i32, msg: impl Into<String>) {
+        self.blow(CodedError::into_io_error(code, msg.into()))
+    }
+
+    /// Turns this guard into `OwnedFuseGuard` that can be sent to another thread.
+    ///
+    /// The owned guard is armed before this guard is released so the fuse stays armed throughout.
+    pub fn transfer(self) -> OwnedFuseGuard {
+        let owned = OwnedFuseGuard::new(self.shared.clone(), self.generation, self.deadline);
+        // the owned guard takes over, TTL included, so this one is released without blowing the fuse
+        let mut guard = ManuallyDrop::new(self);
+        if let Err(err) = std::mem::replace(&mut guard.result, Ok(())) {
+            guard.shared.set_error(guard.generation, err, BLOWN);
+        }
+        guard.deadline = None;
+        guard.release();
+        owned
+    }
+}
+
+#[cfg(feature = "std")]
+impl<'a, E> Drop for FuseGuard<'a, E> {
+    fn drop(&mut self) {
+        let result = std::mem::replace(&mut self.result, Ok(()));
+        self.shared.drop_guard(self.generation, self.deadline, result, POISONED);
+        self.release();
+    }
+}
+
+#[cfg(feature = "std")]
+impl<E> FuseGuard<'_, E> {
+    fn release(&self) {
+        self.shared.remove_guard(self.generation);
+        if let Some(deadline) = self.deadline {
+            self.shared.clear_deadline(deadline);
+        }
+        self.slot.release();
+    }
+}
+
+/// Returns command running ignored helper test `name` of the test binary in a child process with
+/// `var` set to `value`; the test gets it with `child_process_test`.
+#[cfg(all(test, feature = "std", unix))]
+fn child_process_command(name: &str, var: &str, value: impl AsRef<std::ffi::OsStr>) -> std::process::Command {
+    let mut command = std::process::Command::new(std::env::current_exe().unwrap());
+    command.args(["--ignored", "--exact", name]).env(var, value);
+    command
+}
+
+/// Returns value of `var` given to `child_process_command` in helper test run in child process,
+/// or `None` if the test was run directly with `cargo test -- --ignored` and should do nothing.
+#[cfg(all(test, feature = "std", unix))]
+fn child_process_test(var: &str) -> Option<std::ffi::OsString> {
+    std::env::var_os(var)
+}
+
+#[cfg(all(test, feature = "std"))]
+mod tests {
+    use super::*;
+    use std::thread;
+    use pipe::pipe;
+
+    #[test]
+    fn test_unfused_panic() {
+        let (mut reader, mut writer) = pipe();
+
+        thread::spawn(move || {
+            writer.write_all(&[1]).unwrap();
+            panic!("boom");
+        });
+
+        let mut data = Vec::new();
+
+        assert!(reader.read_to_end(&mut data).is_ok());
+        assert_eq!(&data, &[1]);
+    }
+
+    #[test]
+    fn test_fused_nopanic() {
+        let (reader, mut writer) = pipe();
+
+        let (mut reader, fuse) = fuse(reader);
+
+        thread::spawn(move || {
+            let _fuse = fuse.arm().unwrap();
+            writer.write_all(&[1]).unwrap();
+        });
+
+        let mut data = Vec::new();
+
+        assert!(reader.read_to_end(&mut data).is_ok());
+        assert_eq!(&data, &[1]);
+    }
+
+    #[test]
+    fn test_fused_panic() {
+        let (reader, mut writer) = pipe();
+
+        let (mut reader, fuse) = fuse(reader);
+
+        thread::spawn(move || {
+            let _fuse = fuse.arm().unwrap();
+            writer.write_all(&[1]).unwrap();
+            panic!("boom");
+        });
+
+        let mut data = Vec::new();
+
+        assert_eq!(reader.read_to_end(&mut data).unwrap_err().kind(), ErrorKind::BrokenPipe);
+        assert_eq!(&data, &[1]);
+    }
+
+    #[test]
+    fn test_fused_blow() {
+        let (reader, mut writer) = pipe();
+
+        let (mut reader, fuse) = fuse(reader);
+
+        thread::spawn(move || {
+            let fuse = fuse.arm().unwrap();
+            writer.write_all(&[1]).unwrap();
+            fuse.blow(IoError::new(ErrorKind::UnexpectedEof, "uh! oh!"))
+        });
+
+        let mut data = Vec::new();
+
+        assert_eq!(reader.read_to_end(&mut data).unwrap_err().kind(), ErrorKind::UnexpectedEof);
+        assert_eq!(&data, &[1]);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_fused_as_raw_fd() {
+        let (reader, _writer) = std::os::unix::net::UnixStream::pair().unwrap();
+        let fd = reader.as_raw_fd();
+        let (reader, _fuse) = fuse(reader);
+
+        assert_eq!(reader.as_raw_fd(), fd);
+        assert_eq!(reader.as_fd().as_raw_fd(), fd);
+    }
+
+    #[cfg(windows)]
+    #[test]
+    fn test_fused_as_raw_socket() {
+        let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+        let reader = std::net::TcpStream::connect(listener.local_addr().unwrap()).unwrap();
+        let socket = reader.as_raw_socket();
+        let (reader, _fuse) = fuse(reader);
+
+        assert_eq!(reader.as_raw_socket(), socket);
+        assert_eq!(reader.as_socket().as_raw_socket(), socket);
+    }
+
+    #[test]
+    fn test_fused_blow_code() {
+        let (reader, mut writer) = pipe();
+
+        let (mut reader, fuse) = fuse(reader);
+
+        thread::spawn(move || {
+            let fuse = fuse.arm().unwrap();
+            writer.write_all(&[1]).unwrap();
+            fuse.blow_code(42, "uh! oh!")
+        });
+
+        let mut data = Vec::new();
+
+        let err = reader.read_to_end(&mut data).unwrap_err();
+        assert_eq!(err.kind(), ErrorKind::Other);
+        assert_eq!(error_code(&err), Some(42));
+        assert_eq!(coded_error(&err).unwrap().message, "uh! oh!");
+        assert_eq!(&data, &[1]);
+    }
+
+    #[test]
+    fn test_fused_ttl() {
+        let (reader, mut writer) = pipe();
+
+        let (mut reader, fuse) = fuse(reader);
+
+        thread::spawn(move || {
+            let fuse = fuse.arm_with_ttl(Duration::from_millis(10)).unwrap();
+            writer.write_all(&[1]).unwrap();
+            thread::sleep(Duration::from_millis(20));
+            assert_eq!(fuse.check_ttl().unwrap_err().kind(), ErrorKind::TimedOut);
+            writer.write_all(&[1]).unwrap();
+        });
+
+        let mut data = Vec::new();
+
+        assert_eq!(reader.read_to_end(&mut data).unwrap_err().kind(), ErrorKind::TimedOut);
+    }
+
+    #[test]
+    fn test_fused_ttl_other_clone() {
+        let (mut reader, writer_fuse) = fuse(std::io::Cursor::new(vec![1]));
+        let other = writer_fuse.clone();
+
+        let ttl_guard = writer_fuse.arm_with_ttl(Duration::from_millis(10)).unwrap();
+        let guard = other.arm().unwrap();
+        thread::sleep(Duration::from_millis(20));
+
+        assert!(guard.check_ttl().is_ok());
+        assert_eq!(ttl_guard.check_ttl().unwrap_err().kind(), ErrorKind::TimedOut);
+        assert_eq!(reader.read(&mut [0; 1]).unwrap_err().kind(), ErrorKind::TimedOut);
+    }
+
+    #[test]
+    fn test_fused_ttl_completed() {
+        let (reader, mut writer) = pipe();
+
+        let (mut reader, fuse) = fuse(reader);
+
+        thread::spawn(move || {
+            let _fuse = fuse.arm_with_ttl(Duration::from_secs(10)).unwrap();
+            writer.write_all(&[1]).unwrap();
+        });
+
+        let mut data = Vec::new();
+
+        assert!(reader.read_to_end(&mut data).is_ok());
+        assert_eq!(&data, &[1]);
+    }
+
+    #[test]
+    fn test_fused_blow_reason() {
+        let (reader, mut writer) = pipe();
+
+        let (mut reader, fuse) = fuse(reader);
+
+        thread::spawn(move || {
+            let _fuse = fuse.arm().unwrap();
+            writer.write_all(&[1]).unwrap();
+            panic!("boom");
+        });
+
+        let mut data = Vec::new();
+
+        let err = reader.read_to_end(&mut data).unwrap_err();
+        assert!(format!("{:?}", err).contains("Panic"));
+        assert!(matches!(reader.blow_reason(), Some(BlowReason::Panic { .. })));
+    }
+
+    #[test]
+    fn test_fused_blow_reason_explicit() {
+        let (mut reader, fuse) = fuse(std::io::empty());
+        fuse.arm().unwrap().blow(IoError::new(ErrorKind::UnexpectedEof, "uh! oh!"));
+
+        let err = reader.read(&mut [0; 1]).unwrap_err();
+        assert_eq!(err.kind(), ErrorKind::UnexpectedEof);
+        assert_eq!(err.to_string(), "uh! oh!");
+        assert_eq!(reader.blow_reason(), Some(&BlowReason::ExplicitError));
+    }
+
+    #[test]
+    fn test_fused_drop_drain() {
+        let (reader, mut writer) = crate::ring_pipe::pipe_with_capacity(4);
+        let (mut reader, fuse) = fuse(reader);
+
+        let writer = thread::spawn(move || {
+            let _fuse = fuse.arm().unwrap();
+            writer.write_all(&[1; 100])
+        });
+
+        reader.read_exact(&mut [0; 2]).unwrap();
+        reader.set_drop_behavior(DropBehavior::Drain);
+        drop(reader);
+        writer.join().unwrap().unwrap();
+    }
+
+    #[test]
+    fn test_fused_drop_cancel() {
+        let (mut reader, fuse) = fuse(std::io::empty());
+        let guard = fuse.arm().unwrap();
+
+        assert!(!guard.is_cancelled());
+        reader.set_drop_behavior(DropBehavior::Cancel);
+        drop(reader);
+        assert!(guard.is_cancelled());
+    }
+
+    #[test]
+    fn test_fused_cancel() {
+        let (reader, mut writer) = pipe();
+        let (mut reader, fuse) = fuse(reader);
+
+        let writer = thread::spawn(move || -> Result<(), IoError> {
+            let fuse = fuse.arm().unwrap();
+            loop {
+                fuse.check_cancel()?;
+                writer.write_all(&[1])?;
+            }
+        });
+
+        reader.read_exact(&mut [0; 2]).unwrap();
+        reader.cancel("bad header");
+        // unblock the writer if it is writing already
+        assert!(reader.read(&mut [0; 1]).unwrap() <= 1);
+
+        let err = writer.join().unwrap().unwrap_err();
+        assert_eq!(err.kind(), ErrorKind::BrokenPipe);
+        assert_eq!(err.to_string(), "reader cancelled the stream: bad header");
+        assert_eq!(extract_fuse_error(&err).unwrap().reason(), &BlowReason::ReaderCancelled);
+    }
+
+    #[test]
+    fn test_fused_into_parts() {
+        let (mut reader, fuse) = fuse(std::io::Cursor::new(vec![1, 2, 3]));
+        reader.read_exact(&mut [0; 1]).unwrap();
+        fuse.arm().unwrap().blow(IoError::new(ErrorKind::InvalidData, "uh! oh!"));
+
+        let (inner, handle) = reader.into_parts();
+        let mut reader = handle.attach(std::io::BufReader::new(inner));
+        assert_eq!(reader.position(), 1);
+
+        let mut data = Vec::new();
+        assert_eq!(reader.read_to_end(&mut data).unwrap_err().kind(), ErrorKind::InvalidData);
+        assert_eq!(&data, &[2, 3]);
+    }
+
+    #[test]
+    fn test_fused_into_parts_keeps_drop_behavior() {
+        let (mut reader, fuse) = fuse(std::io::Cursor::new(vec![1, 2, 3]));
+        reader.set_drop_behavior(DropBehavior::Cancel);
+        let guard = fuse.arm().unwrap();
+
+        let (inner, handle) = reader.into_parts();
+        assert!(!guard.is_cancelled());
+        let reader = handle.attach(inner);
+        assert_eq!(reader.drop_behavior(), DropBehavior::Cancel);
+        drop(reader);
+        assert!(guard.is_cancelled());
+    }
+
+    #[test]
+    fn test_fused_panic_location() {
+        install_panic_hook();
+        let (mut reader, fuse) = fuse(std::io::empty());
+
+        let line = line!() + 3;
+        thread::spawn(move || {
+            let _fuse = fuse.arm().unwrap();
+            panic!("boom");
+        }).join().unwrap_err();
+
+        let err = reader.read(&mut [0; 1]).unwrap_err();
+        assert_eq!(err.kind(), ErrorKind::BrokenPipe);
+        match reader.blow_reason() {
+            Some(BlowReason::Panic { location: Some(location), msg }) => {
+                assert_eq!(location.file, file!());
+                assert_eq!(location.line, line);
+                assert_eq!(msg.as_deref(), Some("boom"));
+                assert!(err.to_string().ends_with(&format!("{}: boom", location)));
+            }
+            reason => panic!("unexpected reason: {:?}", reason),
+        }
+    }
+
+    #[test]
+    fn test_fuse_arm_waits_for_guard() {
+        let (mut reader, fuse) = fuse(std::io::empty());
+        let guard = fuse.arm().unwrap();
+
+        thread::scope(|scope| {
+            let second = scope.spawn(|| fuse.arm().unwrap().blow(IoError::new(ErrorKind::InvalidData, "uh! oh!")));
+            thread::sleep(Duration::from_millis(50));
+            assert!(!second.is_finished());
+            assert!(matches!(reader.check_fuse(), FuseStatus::Armed));
+            drop(guard);
+        });
+
+        assert_eq!(reader.read(&mut [0; 1]).unwrap_err().kind(), ErrorKind::InvalidData);
+    }
+
+    #[test]
+    fn test_fuse_clone() {
+        let (mut reader, fuse) = fuse(std::io::empty());
+        let first = fuse.clone();
+        let second = fuse.clone();
+
+        let first_guard = first.arm().unwrap();
+        let second_guard = second.arm().unwrap();
+        assert!(matches!(reader.check_fuse(), FuseStatus::Armed));
+        first_guard.blow(IoError::new(ErrorKind::InvalidData, "first"));
+        assert!(matches!(reader.check_fuse(), FuseStatus::Armed));
+        second_guard.blow(IoError::new(ErrorKind::TimedOut, "second"));
+
+        let err = reader.read(&mut [0; 1]).unwrap_err();
+        assert_eq!(err.kind(), ErrorKind::InvalidData);
+        assert_eq!(reader.take_error().unwrap().kind(), ErrorKind::InvalidData);
+        assert_eq!(reader.read(&mut [0; 1]).unwrap(), 0);
+
+        thread::scope(|scope| {
+            scope.spawn(|| fuse.arm().unwrap());
+            scope.spawn(|| {
+                let _guard = first.arm().unwrap();
+                panic!("boom");
+            }).join().unwrap_err();
+        });
+        assert_eq!(reader.read(&mut [0; 1]).unwrap_err().kind(), ErrorKind::BrokenPipe);
+    }
+
+    #[test]
+    fn test_fuse_with_fail_fast() {
+        let (mut reader, fuse) = fuse_with(std::io::Cursor::new(vec![1; 100]), FusePolicy::FailFast);
+        assert_eq!(reader.policy(), FusePolicy::FailFast);
+
+        let mut buf = [0; 10];
+        assert_eq!(reader.read(&mut buf).unwrap(), 10);
+        fuse.arm().unwrap().blow(IoError::new(ErrorKind::InvalidData, "uh! oh!"));
+        assert_eq!(reader.read(&mut buf).unwrap_err().kind(), ErrorKind::InvalidData);
+        assert_eq!(reader.position(), 10);
+    }
+
+    #[test]
+    fn test_fuse_with_fail_fast_other_guard_alive() {
+        let (mut reader, writer_fuse) = fuse_with(std::io::Cursor::new(vec![1; 100]), FusePolicy::FailFast);
+        let other = writer_fuse.clone();
+
+        let _guard = other.arm().unwrap();
+        writer_fuse.arm().unwrap().blow(IoError::new(ErrorKind::InvalidData, "uh! oh!"));
+        assert!(matches!(reader.check_fuse(), FuseStatus::Blown(_)));
+        assert_eq!(reader.read(&mut [0; 10]).unwrap_err().kind(), ErrorKind::InvalidData);
+    }
+
+    #[test]
+    fn test_fused_require_complete() {
+        let (reader, mut writer) = pipe();
+        let (mut reader, writer_fuse) = fuse(reader);
+        reader.set_require_complete(true);
+
+        thread::spawn(move || {
+            // writer gives up before arming the fuse
+            writer.write_all(&[1]).unwrap();
+            drop(writer_fuse);
+        });
+
+        let mut data = Vec::new();
+        let err = reader.read_to_end(&mut data).unwrap_err();
+        assert_eq!(err.kind(), ErrorKind::UnexpectedEof);
+        assert_eq!(reader.blow_reason(), Some(&BlowReason::Incomplete));
+        assert_eq!(&data, &[1]);
+
+        let (reader, mut writer) = pipe();
+        let (mut reader, writer_fuse) = fuse(reader);
+        reader.set_require_complete(true);
+
+        thread::spawn(move || {
+            let guard = writer_fuse.arm().unwrap();
+            writer.write_all(&[1]).unwrap();
+            guard.complete();
+        });
+
+        let mut data = Vec::new();
+        reader.read_to_end(&mut data).unwrap();
+        assert_eq!(&data, &[1]);
+    }
+
+    #[test]
+    fn test_fused_buf_read() {
+        let (reader, mut writer) = pipe();
+        let (mut reader, fuse) = fuse(io::BufReader::new(reader));
+
+        thread::spawn(move || {
+            let fuse = fuse.arm().unwrap();
+            writer.write_all(b"foo\nbar\n").unwrap();
+            fuse.blow(IoError::new(ErrorKind::InvalidData, "uh! oh!"));
+        });
+
+        let mut lines = reader.by_ref().lines();
+        assert_eq!(lines.next().unwrap().unwrap(), "foo");
+        assert_eq!(lines.next().unwrap().unwrap(), "bar");
+        assert_eq!(lines.next().unwrap().unwrap_err().kind(), ErrorKind::InvalidData);
+        assert_eq!(reader.position(), 8);
+    }
+
+    #[test]
+    fn test_fused_seek_write() {
+        let (mut reader, _fuse) = fuse(std::io::Cursor::new(Vec::new()));
+        reader.write_all(&[1, 2, 3]).unwrap();
+        reader.seek(SeekFrom::Start(1)).unwrap();
+
+        let mut data = Vec::new();
+        reader.read_to_end(&mut data).unwrap();
+        assert_eq!(&data, &[2, 3]);
+        assert_eq!(reader.get_ref().get_ref(), &vec![1, 2, 3]);
+        reader.get_mut().set_position(0);
+        assert_eq!(reader.read(&mut [0; 1]).unwrap(), 1);
+    }
+
+    #[test]
+    fn test_fused_read_vectored() {
+        let (mut reader, fuse) = fuse(&[1, 2, 3][..]);
+        fuse.arm().unwrap().blow(IoError::new(ErrorKind::InvalidData, "uh! oh!"));
+
+        let (mut first, mut second) = ([0; 2], [0; 2]);
+        let mut bufs = [IoSliceMut::new(&mut first), IoSliceMut::new(&mut second)];
+        assert_eq!(reader.read_vectored(&mut bufs).unwrap(), 3);
+        assert_eq!((first, second), ([1, 2], [3, 0]));
+        assert_eq!(reader.read_vectored(&mut [IoSliceMut::new(&mut first)]).unwrap_err().kind(), ErrorKind::InvalidData);
+    }
+
+    #[test]
+    fn test_fused_read_to_string() {
+        let (mut reader, fuse) = fuse(&b"foo"[..]);
+        fuse.arm().unwrap().blow(IoError::new(ErrorKind::InvalidData, "uh! oh!"));
+
+        let mut text = String::new();
+        assert_eq!(reader.read_to_string(&mut text).unwrap_err().kind(), ErrorKind::InvalidData);
+        assert_eq!(text, "foo");
+        assert_eq!(reader.position(), 3);
+
+        let (mut reader, _fuse) = fuse_with(&b"foo"[..], FusePolicy::FailFast);
+        let mut text = String::new();
+        assert_eq!(reader.read_to_string(&mut text).unwrap(), 3);
+        assert_eq!(text, "foo");
+    }
+
+    #[test]
+    fn test_fused_sticky_status() {
+        let (mut reader, fuse) = fuse(std::io::empty());
+        fuse.arm().unwrap().blow(IoError::new(ErrorKind::InvalidData, "uh! oh!"));
+
+        let monitor = &reader;
+        thread::scope(|scope| {
+            scope.spawn(|| assert!(matches!(monitor.check_fuse(), FuseStatus::Blown(err) if err.kind() == ErrorKind::InvalidData)));
+        });
+        assert!(matches!(reader.check_fuse(), FuseStatus::Blown(_)));
+        assert_eq!(reader.read(&mut [0; 1]).unwrap_err().kind(), ErrorKind::InvalidData);
+        assert_eq!(reader.read(&mut [0; 1]).unwrap_err().kind(), ErrorKind::InvalidData);
+
+        assert_eq!(reader.take_error().unwrap().to_string(), "uh! oh!");
+        assert!(reader.take_error().is_none());
+        assert!(matches!(reader.check_fuse(), FuseStatus::Unarmed));
+        assert_eq!(reader.read(&mut [0; 1]).unwrap(), 0);
+    }
+
+    #[test]
+    fn test_fuse_on_blow() {
+        let (reader, mut writer) = pipe();
+        let (mut reader, fuse) = fuse(reader);
+        let (blown_tx, blown_rx) = std::sync::mpsc::channel();
+        let blown_tx = Mutex::new(blown_tx);
+        reader.on_blow(move |err| blown_tx.lock().unwrap().send(err.kind()).unwrap());
+
+        let writer = thread::spawn(move || {
+            let fuse = fuse.arm().unwrap();
+            fuse.blow(IoError::new(ErrorKind::InvalidData, "uh! oh!"));
+            // keep the stream open
+            writer.write_all(&[1]).unwrap();
+        });
+
+        // notified before EOF was reached
+        assert_eq!(blown_rx.recv().unwrap(), ErrorKind::InvalidData);
+        let mut data = Vec::new();
+        assert_eq!(reader.read_to_end(&mut data).unwrap_err().kind(), ErrorKind::InvalidData);
+        writer.join().unwrap();
+
+        let (called_tx, called_rx) = std::sync::mpsc::channel();
+        let called_tx = Mutex::new(called_tx);
+        reader.on_blow(move |_err| called_tx.lock().unwrap().send(()).unwrap());
+        called_rx.try_recv().unwrap();
+    }
+
+    #[test]
+    fn test_fuse_expect_len() {
+        let (reader, mut writer) = pipe();
+        let (mut reader, writer_fuse) = fuse(reader);
+
+        thread::spawn(move || {
+            let fuse = writer_fuse.arm().unwrap();
+            fuse.expect_len(3);
+            writer.write_all(&[1, 2]).unwrap();
+        });
+
+        let mut data = Vec::new();
+        let err = reader.read_to_end(&mut data).unwrap_err();
+        assert_eq!(err.kind(), ErrorKind::UnexpectedEof);
+        assert_eq!(err.to_string(), "stream ended after 2 of 3 expected bytes");
+        assert_eq!(&data, &[1, 2]);
+
+        let (mut reader, fuse) = fuse(&[1, 2, 3][..]);
+        let guard = fuse.arm().unwrap();
+        guard.add_expected(1);
+        guard.add_expected(2);
+        drop(guard);
+        reader.read_to_end(&mut Vec::new()).unwrap();
+    }
+
+    #[test]
+    fn test_fused_progress() {
+        let (reader, mut writer) = pipe();
+        let (mut reader, fuse) = fuse(reader);
+        let progress = fuse.progress();
+
+        let writer = thread::spawn(move || {
+            let _fuse = fuse.arm().unwrap();
+            writer.write_all(&[1, 2, 3]).unwrap();
+        });
+
+        assert_eq!(progress.bytes_read(), 0);
+        let mut data = Vec::new();
+        reader.read_to_end(&mut data).unwrap();
+        writer.join().unwrap();
+        assert_eq!(reader.bytes_read(), 3);
+        assert_eq!(progress.bytes_read(), 3);
+        assert_eq!(reader.progress().bytes_read(), 3);
+    }
+
+    #[test]
+    fn test_fuse_try_arm() {
+        let (_reader, fuse) = fuse(std::io::Cursor::new(vec![1]));
+        let guard = fuse.try_arm().unwrap().unwrap();
+        assert!(fuse.try_arm().unwrap().is_none());
+        assert!(fuse.arm_timeout(Duration::from_millis(10)).unwrap().is_none());
+        assert!(fuse.clone().try_arm().unwrap().is_some());
+
+        thread::scope(|scope| {
+            scope.spawn(|| {
+                thread::sleep(Duration::from_millis(10));
+                drop(guard);
+            });
+            assert!(fuse.arm_timeout(Duration::from_secs(10)).unwrap().is_some());
+        });
+    }
+
+    #[test]
+    fn test_fuse_named() {
+        let (mut reader, fuse) = fuse_named("upload-worker", std::io::Cursor::new(vec![1]));
+        assert_eq!(fuse.name(), Some("upload-worker"));
+        fuse.arm().unwrap().blow(IoError::new(ErrorKind::InvalidData, "uh! oh!"));
+        assert_eq!(reader.read_to_end(&mut Vec::new()).unwrap_err().kind(), ErrorKind::InvalidData);
+    }
+
+    #[test]
+    fn test_fuse_status() {
+        install_panic_hook();
+        let (reader, writer_fuse) = fuse(std::io::Cursor::new(vec![1]));
+        assert!(matches!(writer_fuse.status(), FuseStatus::Unarmed));
+
+        let guard = writer_fuse.arm().unwrap();
+        assert!(matches!(reader.check_fuse(), FuseStatus::Armed));
+        guard.complete();
+        assert!(matches!(writer_fuse.status(), FuseStatus::Completed));
+        assert!(matches!(reader.check_fuse(), FuseStatus::Completed));
+
+        let (reader, fuse) = fuse(std::io::Cursor::new(vec![1]));
+        thread::spawn(move || {
+            let _fuse = fuse.arm().unwrap();
+            panic!("boom");
+        }).join().unwrap_err();
+        match reader.check_fuse() {
+            FuseStatus::Poisoned(panic) => {
+                assert_eq!(panic.msg.as_deref(), Some("boom"));
+                assert!(panic.location.unwrap().file.ends_with("lib.rs"));
+            }
+            status => panic!("unexpected status: {:?}", status),
+        }
+    }
+
+    #[test]
+    fn test_fuse_reset() {
+        let (mut reader, fuse) = fuse(std::io::Cursor::new(vec![1, 2, 3]));
+        let retry = fuse.clone();
+        let hung = fuse.arm().unwrap();
+
+        thread::scope(|scope| {
+            scope.spawn(|| {
+                let _guard = retry.arm().unwrap();
+                panic!("boom");
+            }).join().unwrap_err();
+        });
+        assert!(matches!(reader.check_fuse(), FuseStatus::Poisoned(_)));
+
+        assert_eq!(fuse.reset(), 1);
+        assert!(matches!(reader.check_fuse(), FuseStatus::Unarmed));
+        let guard = retry.arm().unwrap();
+        assert_eq!(guard.generation(), 1);
+        guard.blow(IoError::new(ErrorKind::InvalidData, "uh! oh!"));
+
+        // error of the previous generation is discarded
+        hung.blow(IoError::new(ErrorKind::TimedOut, "stale"));
+        let mut data = Vec::new();
+        assert_eq!(reader.read_to_end(&mut data).unwrap_err().kind(), ErrorKind::InvalidData);
+        assert_eq!(data, vec![1, 2, 3]);
+    }
+
+    #[derive(Debug, PartialEq)]
+    struct DbError(u32);
+
+    #[test]
+    fn test_fuse_typed() {
+        let (mut reader, fuse) = fuse_typed::<_, DbError>(std::io::empty());
+        fuse.arm().unwrap().blow(DbError(42));
+
+        let err = reader.read(&mut [0; 1]).unwrap_err();
+        assert_eq!(err.kind(), ErrorKind::Other);
+        assert!(err.to_string().ends_with("DbError"));
+        assert_eq!(reader.take_typed_error::<IoError>().map(|err| err.kind()), None);
+        assert_eq!(reader.take_typed_error::<DbError>(), Some(DbError(42)));
+        assert_eq!(reader.take_typed_error::<DbError>(), None);
+    }
+
+    #[test]
+    fn test_fuse_armed() {
+        let (reader, mut writer) = pipe();
+        let (mut reader, fuse) = fuse(reader);
+
+        let producer = thread::spawn(move || {
+            fuse.armed(|_fuse| {
+                writer.write_all(&[1])?;
+                Err::<(), _>(IoError::new(ErrorKind::InvalidData, "uh! oh!"))
+            })
+        });
+
+        let mut data = Vec::new();
+        assert_eq!(reader.read_to_end(&mut data).unwrap_err().kind(), ErrorKind::InvalidData);
+        assert_eq!(data, vec![1]);
+        assert_eq!(producer.join().unwrap().unwrap_err().kind(), ErrorKind::InvalidData);
+    }
+
+    #[test]
+    fn test_fuse_armed_panic() {
+        let (mut reader, fuse) = fuse(std::io::empty());
+
+        thread::spawn(move || {
+            fuse.armed(|_fuse| -> Result<(), IoError> { panic!("boom") })
+        }).join().unwrap_err();
+
+        let err = reader.read(&mut [0; 1]).unwrap_err();
+        assert_eq!(err.kind(), ErrorKind::BrokenPipe);
+        assert!(err.to_string().ends_with(": boom"));
+    }
+
+    #[test]
+    fn test_fused_catching() {
+        let (mut reader, fuse) = fuse(std::io::empty());
+
+        thread::spawn(move || {
+            let fuse = fuse.arm().unwrap();
+            fuse.catching(|| panic!("boom {}", 42));
+        }).join().unwrap_err();
+
+        let err = reader.read(&mut [0; 1]).unwrap_err();
+        assert_eq!(err.kind(), ErrorKind::BrokenPipe);
+        assert!(err.to_string().ends_with(": boom 42"));
+        assert!(matches!(reader.blow_reason(), Some(BlowReason::Panic { msg: Some(msg), .. }) if msg == "boom 42"));
+    }
+
+    #[test]
+    fn test_fused_also_fused_by() {
+        let (reader, mut writer) = pipe::pipe();
+        let (mut reader, fetcher) = fuse(reader);
+        let transformer = Fuse::new();
+        reader.also_fused_by(&transformer);
+
+        thread::spawn(move || {
+            let _fetcher = fetcher.arm().unwrap();
+            let transformer = transformer.arm().unwrap();
+            writer.write_all(&[1]).unwrap();
+            transformer.blow(IoError::new(ErrorKind::InvalidData, "uh! oh!"));
+        });
+
+        let mut data = Vec::new();
+        assert_eq!(reader.read_to_end(&mut data).unwrap_err().kind(), ErrorKind::InvalidData);
+        assert_eq!(&data, &[1]);
+        assert_eq!(reader.failed_fuse(), Some(1));
+    }
+
+    #[test]
+    fn test_fused_unfuse() {
+        let (reader, writer_fuse) = fuse(std::io::Cursor::new(vec![1, 2]));
+        writer_fuse.arm().unwrap().blow(IoError::new(ErrorKind::InvalidData, "uh! oh!"));
+
+        let (mut reader, err) = reader.unfuse();
+        assert_eq!(err.unwrap().kind(), ErrorKind::InvalidData);
+        let mut data = Vec::new();
+        reader.read_to_end(&mut data).unwrap();
+        assert_eq!(&data, &[1, 2]);
+
+        let (reader, _fuse) = fuse(std::io::empty());
+        assert!(reader.unfuse().1.is_none());
+    }
+
+    #[test]
+    fn test_fused_replace_fuse() {
+        let (reader, mut writer) = pipe::pipe();
+        let (mut reader, old_fuse) = fuse(reader);
+
+        old_fuse.arm().unwrap().blow(IoError::new(ErrorKind::InvalidData, "uh! oh!"));
+        let new_fuse = reader.replace_fuse();
+
+        thread::spawn(move || {
+            let _old_fuse = old_fuse.arm().unwrap();
+            let _new_fuse = new_fuse.arm().unwrap();
+            writer.write_all(&[1]).unwrap();
+            panic!("boom");
+        });
+
+        let mut data = Vec::new();
+        assert_eq!(reader.read_to_end(&mut data).unwrap_err().kind(), ErrorKind::BrokenPipe);
         assert_eq!(&data, &[1]);
     }
 }
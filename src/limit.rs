@@ -0,0 +1,91 @@
+use std::io::{Read, Error as IoError, ErrorKind};
+
+use crate::{FusedReader, FuseStatus};
+
+impl<R: Read> FusedReader<R> {
+    /// Wraps reader so that it fails with `InvalidData` error once the stream turns out to be
+    /// longer than `limit` bytes.
+    ///
+    /// Unlike `Read::take` exceeding the limit is an error and not a truncation. All bytes up to
+    /// the limit are delivered before the error is reported.
+    pub fn limit_total(self, limit: u64) -> LimitedReader<R> {
+        LimitedReader {
+            reader: self,
+            limit,
+            remaining: limit,
+        }
+    }
+}
+
+/// Fused reader failing if the stream is longer than given limit.
+#[derive(Debug)]
+pub struct LimitedReader<R: Read> {
+    reader: FusedReader<R>,
+    limit: u64,
+    remaining: u64,
+}
+
+impl<R: Read> LimitedReader<R> {
+    /// Maximum number of bytes the stream may have.
+    pub fn limit(&self) -> u64 {
+        self.limit
+    }
+
+    /// Checks status of the fuse; see `FusedReader::check_fuse`.
+    pub fn check_fuse(&self) -> FuseStatus {
+        self.reader.check_fuse()
+    }
+
+    /// Returns inner fused reader.
+    pub fn into_inner(self) -> FusedReader<R> {
+        self.reader
+    }
+}
+
+impl<R: Read> Read for LimitedReader<R> {
+    fn read(&mut self, buf: &mut [u8]) -> Result<usize, IoError> {
+        if buf.is_empty() {
+            return Ok(0)
+        }
+
+        if self.remaining == 0 {
+            // probe if the stream ends here
+            let mut probe = [0; 1];
+            return match self.reader.read(&mut probe)? {
+                0 => Ok(0),
+                _ => Err(IoError::new(ErrorKind::InvalidData, format!("stream exceeded size limit of {} bytes", self.limit))),
+            }
+        }
+
+        let len = (buf.len() as u64).min(self.remaining) as usize;
+        let bytes = self.reader.read(&mut buf[..len])?;
+        self.remaining -= bytes as u64;
+        Ok(bytes)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::fuse;
+    use std::io::{Cursor, Read, ErrorKind};
+
+    #[test]
+    fn test_limit_total_within() {
+        let (reader, _fuse) = fuse(Cursor::new(vec![1; 10]));
+        let mut reader = reader.limit_total(10);
+
+        let mut data = Vec::new();
+        reader.read_to_end(&mut data).unwrap();
+        assert_eq!(data.len(), 10);
+    }
+
+    #[test]
+    fn test_limit_total_exceeded() {
+        let (reader, _fuse) = fuse(Cursor::new(vec![1; 11]));
+        let mut reader = reader.limit_total(10);
+
+        let mut data = Vec::new();
+        assert_eq!(reader.read_to_end(&mut data).unwrap_err().kind(), ErrorKind::InvalidData);
+        assert_eq!(data.len(), 10);
+    }
+}
@@ -0,0 +1,251 @@
+use std::cell::RefCell;
+use std::io::{BufRead, Read, Error as IoError, ErrorKind};
+use std::rc::Rc;
+use std::thread;
+
+use crate::panic::take_panic;
+use crate::{panic_error, BlowReason, CodedError, FuseError, FuseStatus, PanicInfo};
+
+#[derive(Debug)]
+enum Failure {
+    Blown(IoError),
+    Poisoned(PanicInfo),
+}
+
+#[derive(Debug, Default)]
+struct LocalShared {
+    // number of live `LocalFuseGuard`s
+    armed: usize,
+    completed: bool,
+    failure: Option<Failure>,
+}
+
+type LocalFuseState = Rc<RefCell<LocalShared>>;
+
+impl LocalShared {
+    fn status(&self) -> FuseStatus {
+        match &self.failure {
+            Some(Failure::Poisoned(panic)) => FuseStatus::Poisoned(panic.clone()),
+            _ if self.armed > 0 => FuseStatus::Armed,
+            Some(Failure::Blown(err)) => FuseStatus::Blown(FuseError::duplicate(err)),
+            None if self.completed => FuseStatus::Completed,
+            None => FuseStatus::Unarmed,
+        }
+    }
+}
+
+/// Fuses reader used within a single thread, like an event loop or on `wasm32-unknown-unknown`.
+///
+/// Works like `fuse` but the fuse state is not shared with atomics and locks so neither the
+/// fused reader nor the fuse are `Send`. Arming the fuse again while it is armed does not wait
+/// for the other guard to be released.
+pub fn fuse_local<R: Read>(reader: R) -> (LocalFusedReader<R>, LocalFuse) {
+    let state = LocalFuseState::default();
+    (LocalFusedReader {
+            reader,
+            fuse: state.clone(),
+        },
+        LocalFuse(state),
+    )
+}
+
+/// Reader that will fail with I/O error if `LocalFuse` was blown.
+#[derive(Debug)]
+pub struct LocalFusedReader<R> {
+    reader: R,
+    fuse: LocalFuseState,
+}
+
+impl<R> LocalFusedReader<R> {
+    /// Checks status of the fuse.
+    ///
+    /// Once blown the fuse is reported as `FuseStatus::Blown` with a copy of its error, and the
+    /// reader fails with it at EOF, until the error is taken with `take_error`.
+    pub fn check_fuse(&self) -> FuseStatus {
+        self.fuse.borrow().status()
+    }
+
+    /// Takes the error the fuse was blown with, if any.
+    pub fn take_error(&mut self) -> Option<IoError> {
+        let mut fuse = self.fuse.borrow_mut();
+        if fuse.armed > 0 {
+            return None
+        }
+        match fuse.failure.take() {
+            Some(Failure::Blown(err)) => Some(err),
+            other => {
+                fuse.failure = other;
+                None
+            }
+        }
+    }
+
+    /// Returns reference to the inner reader.
+    pub fn get_ref(&self) -> &R {
+        &self.reader
+    }
+
+    /// Returns mutable reference to the inner reader.
+    pub fn get_mut(&mut self) -> &mut R {
+        &mut self.reader
+    }
+
+    /// Returns inner reader.
+    pub fn into_inner(self) -> R {
+        self.reader
+    }
+
+    fn eof_error(&self) -> Option<IoError> {
+        match self.check_fuse() {
+            FuseStatus::Blown(err) => Some(err),
+            FuseStatus::Poisoned(panic) => Some(panic_error(panic)),
+            _ => None,
+        }
+    }
+}
+
+impl<R: Read> Read for LocalFusedReader<R> {
+    fn read(&mut self, buf: &mut [u8]) -> Result<usize, IoError> {
+        // let it read to end before checking fuse
+        match self.reader.read(buf)? {
+            0 if !buf.is_empty() => self.eof_error().map_or(Ok(0), Err),
+            bytes => Ok(bytes),
+        }
+    }
+}
+
+impl<R: BufRead> BufRead for LocalFusedReader<R> {
+    fn fill_buf(&mut self) -> Result<&[u8], IoError> {
+        if self.reader.fill_buf()?.is_empty() {
+            if let Some(err) = self.eof_error() {
+                return Err(err)
+            }
+        }
+        self.reader.fill_buf()
+    }
+
+    fn consume(&mut self, amt: usize) {
+        self.reader.consume(amt)
+    }
+}
+
+/// Fuse of `LocalFusedReader` that can be armed.
+#[derive(Debug, Clone)]
+pub struct LocalFuse(LocalFuseState);
+
+impl LocalFuse {
+    /// Arms the fuse.
+    ///
+    /// Returns `BrokenPipe` error if the fuse was poisoned.
+    pub fn arm(&self) -> Result<LocalFuseGuard<'_>, IoError> {
+        let mut shared = self.0.borrow_mut();
+        if let Some(Failure::Poisoned(_)) = shared.failure {
+            return Err(FuseError::new_io(ErrorKind::BrokenPipe, BlowReason::ReaderGone, "fuse was poisoned by panic"))
+        }
+        shared.armed += 1;
+        Ok(LocalFuseGuard {
+            fuse: self,
+            result: Ok(()),
+        })
+    }
+
+    /// Returns status of the fuse as seen by the reader end.
+    pub fn status(&self) -> FuseStatus {
+        self.0.borrow().status()
+    }
+
+    /// Signals that the stream was written in full without arming the fuse.
+    pub fn complete(self) {
+        self.0.borrow_mut().completed = true;
+    }
+}
+
+/// Armed `LocalFuse`.
+///
+/// If dropped due to panic the reader end will fail with `BrokenPipe` error.
+#[derive(Debug)]
+pub struct LocalFuseGuard<'a> {
+    fuse: &'a LocalFuse,
+    result: Result<(), IoError>,
+}
+
+impl LocalFuseGuard<'_> {
+    /// Blows the fuse with given error.
+    ///
+    /// The reader end will fail with this error after reaching EOF.
+    pub fn blow(mut self, err: IoError) {
+        self.result = Err(FuseError::explicit(err));
+    }
+
+    /// Blows the fuse with numeric failure code and message.
+    ///
+    /// The reader end will fail with `ErrorKind::Other` error; the code can be retrieved with `error_code`.
+    pub fn blow_code(self, code: i32, msg: impl Into<String>) {
+        self.blow(CodedError::into_io_error(code, msg.into()))
+    }
+
+    /// Disarms the fuse signalling that the stream was written in full.
+    pub fn complete(self) {
+        self.fuse.0.borrow_mut().completed = true;
+    }
+}
+
+impl Drop for LocalFuseGuard<'_> {
+    fn drop(&mut self) {
+        let mut shared = self.fuse.0.borrow_mut();
+        shared.armed -= 1;
+        if thread::panicking() {
+            shared.failure = Some(Failure::Poisoned(take_panic()));
+        } else if let Err(err) = std::mem::replace(&mut self.result, Ok(())) {
+            // error waiting for the reader is kept
+            if shared.failure.is_none() {
+                shared.failure = Some(Failure::Blown(err));
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Cursor;
+    use std::panic::{self, AssertUnwindSafe};
+
+    #[test]
+    fn test_local_fuse_blow() {
+        let (mut reader, fuse) = fuse_local(Cursor::new(vec![1, 2]));
+        let guard = fuse.arm().unwrap();
+        assert!(matches!(reader.check_fuse(), FuseStatus::Armed));
+        guard.blow(IoError::new(ErrorKind::InvalidData, "uh! oh!"));
+
+        let mut data = Vec::new();
+        assert_eq!(reader.read_to_end(&mut data).unwrap_err().kind(), ErrorKind::InvalidData);
+        assert_eq!(data, vec![1, 2]);
+        assert_eq!(reader.take_error().unwrap().kind(), ErrorKind::InvalidData);
+        assert_eq!(reader.read(&mut [0; 1]).unwrap(), 0);
+    }
+
+    #[test]
+    fn test_local_fuse_panic() {
+        let (mut reader, fuse) = fuse_local(Cursor::new(vec![1]));
+        panic::catch_unwind(AssertUnwindSafe(|| {
+            let _guard = fuse.arm().unwrap();
+            panic!("boom");
+        })).unwrap_err();
+
+        assert!(matches!(fuse.status(), FuseStatus::Poisoned(_)));
+        assert_eq!(reader.read_to_end(&mut Vec::new()).unwrap_err().kind(), ErrorKind::BrokenPipe);
+        assert!(fuse.arm().is_err());
+    }
+
+    #[test]
+    fn test_local_fuse_complete() {
+        let (mut reader, fuse) = fuse_local(Cursor::new(vec![1]));
+        fuse.arm().unwrap().complete();
+        assert!(matches!(reader.check_fuse(), FuseStatus::Completed));
+
+        let mut data = Vec::new();
+        reader.read_to_end(&mut data).unwrap();
+        assert_eq!(data, vec![1]);
+    }
+}
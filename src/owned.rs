@@ -0,0 +1,134 @@
+use std::io::{Error as IoError, ErrorKind};
+use std::sync::atomic::Ordering;
+use std::time::Instant;
+
+use crate::{CodedError, Fuse, FuseError, FuseGuard, FuseState, BLOWN};
+
+/// Armed fuse not borrowing the `Fuse` that can be sent to another thread or task.
+///
+/// Like `FuseGuard` it signals the reader to fail with `BrokenPipe` error if dropped due to panic,
+/// in whichever thread that happens. Unlike `FuseGuard` it does not prevent the fuse from being
+/// armed again while it is alive.
+#[derive(Debug)]
+pub struct OwnedFuseGuard {
+    shared: FuseState,
+    generation: u64,
+    deadline: Option<Instant>,
+}
+
+impl Fuse {
+    /// Arms the fuse returning guard that can be sent to another thread.
+    ///
+    /// Returns `BrokenPipe` error if reader was dropped due to panic.
+    pub fn arm_owned(&self) -> Result<OwnedFuseGuard, IoError> {
+        self.arm().map(FuseGuard::transfer)
+    }
+}
+
+impl OwnedFuseGuard {
+    pub(crate) fn new(shared: FuseState, generation: u64, deadline: Option<Instant>) -> OwnedFuseGuard {
+        shared.add_owned(generation);
+        OwnedFuseGuard { shared, generation, deadline }
+    }
+
+    pub(crate) fn set_error(&self, err: IoError) {
+        self.shared.set_error(self.generation, err, BLOWN);
+    }
+
+    /// Blows the fuse with given error.
+    ///
+    /// The reader end will fail with this error after reaching EOF.
+    pub fn blow(self, err: IoError) {
+        self.set_error(FuseError::explicit(err));
+    }
+
+    /// Blows the fuse with numeric failure code and message.
+    ///
+    /// The reader end will fail with `ErrorKind::Other` error after reaching EOF; the code can be
+    /// retrieved with `error_code`.
+    pub fn blow_code(self, code: i32, msg: impl Into<String>) {
+        self.blow(CodedError::into_io_error(code, msg.into()))
+    }
+
+    /// Blows the fuse with error of given kind and message caused by `source`; see
+    /// `FuseGuard::blow_with_source`.
+    pub fn blow_with_source(self, kind: ErrorKind, msg: impl Into<String>, source: impl Into<Box<dyn std::error::Error + Send + Sync>>) {
+        self.set_error(FuseError::with_source(kind, msg.into(), source.into()));
+    }
+
+    /// Returns `true` if the reader end cancelled the stream; see `FuseGuard::is_cancelled`.
+    pub fn is_cancelled(&self) -> bool {
+        self.shared.cancelled.load(Ordering::Acquire)
+    }
+
+    /// Returns error if the reader end cancelled the stream; see `FuseGuard::check_cancel`.
+    pub fn check_cancel(&self) -> Result<(), IoError> {
+        self.shared.check_cancel()
+    }
+}
+
+impl Drop for OwnedFuseGuard {
+    fn drop(&mut self) {
+        self.shared.drop_guard(self.generation, self.deadline, Ok(()), BLOWN);
+        self.shared.remove_owned(self.generation);
+        if let Some(deadline) = self.deadline {
+            self.shared.clear_deadline(deadline);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{fuse, BlowReason, FuseStatus};
+    use std::io::{Read, Write};
+    use std::thread;
+    use std::time::Duration;
+
+    #[test]
+    fn test_owned_guard_transfer() {
+        let (reader, mut writer) = pipe::pipe();
+        let (mut reader, fuse) = fuse(reader);
+
+        let guard = fuse.arm().unwrap().transfer();
+        assert!(matches!(reader.check_fuse(), FuseStatus::Armed));
+
+        thread::spawn(move || {
+            let _guard = guard;
+            writer.write_all(&[1]).unwrap();
+            panic!("boom");
+        });
+
+        let mut data = Vec::new();
+        assert_eq!(reader.read_to_end(&mut data).unwrap_err().kind(), ErrorKind::BrokenPipe);
+        assert_eq!(&data, &[1]);
+        assert!(matches!(reader.blow_reason(), Some(BlowReason::Panic { .. })));
+    }
+
+    #[test]
+    fn test_owned_guard_blow() {
+        let (mut reader, fuse) = fuse(std::io::empty());
+
+        let guard = fuse.arm_owned().unwrap();
+        thread::spawn(move || guard.blow(IoError::new(ErrorKind::InvalidData, "uh! oh!"))).join().unwrap();
+
+        assert_eq!(reader.read(&mut [0; 1]).unwrap_err().kind(), ErrorKind::InvalidData);
+        assert!(matches!(reader.check_fuse(), FuseStatus::Blown(err) if err.kind() == ErrorKind::InvalidData));
+        assert_eq!(reader.take_error().unwrap().kind(), ErrorKind::InvalidData);
+        assert!(matches!(reader.check_fuse(), FuseStatus::Unarmed));
+    }
+
+    #[test]
+    fn test_owned_guard_ttl_expired() {
+        let (mut reader, fuse) = fuse(std::io::empty());
+
+        let guard = fuse.arm_with_ttl(Duration::from_millis(10)).unwrap().transfer();
+        thread::spawn(move || {
+            thread::sleep(Duration::from_millis(20));
+            drop(guard);
+        }).join().unwrap();
+
+        assert_eq!(reader.read(&mut [0; 1]).unwrap_err().kind(), ErrorKind::TimedOut);
+        assert!(matches!(reader.blow_reason(), Some(BlowReason::Timeout)));
+    }
+}
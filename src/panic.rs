@@ -0,0 +1,92 @@
+use std::any::Any;
+use std::cell::RefCell;
+use std::fmt;
+use std::panic::{self, Location};
+use std::sync::Once;
+
+/// Source location of the panic that blew the fuse.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PanicLocation {
+    /// Source file.
+    pub file: String,
+    /// Line in the source file.
+    pub line: u32,
+    /// Column in the source line.
+    pub column: u32,
+}
+
+impl From<&Location<'_>> for PanicLocation {
+    fn from(location: &Location<'_>) -> PanicLocation {
+        PanicLocation {
+            file: location.file().to_owned(),
+            line: location.line(),
+            column: location.column(),
+        }
+    }
+}
+
+impl fmt::Display for PanicLocation {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}:{}:{}", self.file, self.line, self.column)
+    }
+}
+
+/// Location and message of the panic that poisoned the fuse.
+///
+/// Both are only known with the panic hook installed; see `install_panic_hook`.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct PanicInfo {
+    /// Source location of the panic.
+    pub location: Option<PanicLocation>,
+    /// Panic message.
+    pub msg: Option<String>,
+}
+
+thread_local! {
+    static LAST_PANIC: RefCell<PanicInfo> = RefCell::new(PanicInfo::default());
+}
+
+/// Installs panic hook recording where and with what message writers panicked.
+///
+/// With the hook installed errors caused by writer panics carry the panic location and message in
+/// `BlowReason::Panic` and their message. Previously installed hook is still called; installing
+/// the hook more than once has no effect.
+pub fn install_panic_hook() {
+    static INSTALL: Once = Once::new();
+    INSTALL.call_once(|| {
+        let previous = panic::take_hook();
+        panic::set_hook(Box::new(move |info| {
+            let panic = PanicInfo {
+                location: info.location().map(PanicLocation::from),
+                msg: Some(panic_message(info.payload())),
+            };
+            let _ = LAST_PANIC.try_with(|last| *last.borrow_mut() = panic);
+            previous(info)
+        }));
+    });
+}
+
+/// Takes location and message of the last panic of the current thread recorded by the hook or
+/// `record_panic_message`.
+pub(crate) fn take_panic() -> PanicInfo {
+    LAST_PANIC.try_with(|last| last.take()).unwrap_or_default()
+}
+
+/// Runs `f` recording message of its panic before the panic continues to unwind.
+pub(crate) fn catch_panic_message<T>(f: impl FnOnce() -> T) -> T {
+    match panic::catch_unwind(panic::AssertUnwindSafe(f)) {
+        Ok(value) => value,
+        Err(payload) => {
+            let msg = panic_message(&*payload);
+            let _ = LAST_PANIC.try_with(|last| last.borrow_mut().msg = Some(msg));
+            panic::resume_unwind(payload)
+        }
+    }
+}
+
+/// Returns message of panic with given payload.
+pub(crate) fn panic_message(payload: &(dyn Any + Send)) -> String {
+    payload.downcast_ref::<&str>().map(|msg| msg.to_string())
+        .or_else(|| payload.downcast_ref::<String>().cloned())
+        .unwrap_or_else(|| "Box<dyn Any>".to_owned())
+}
@@ -0,0 +1,87 @@
+use std::io::{Read, ErrorKind};
+use std::thread;
+
+use crate::{fuse, FusedReader, FuseError};
+use crate::ring_pipe::{pipe_with_capacity, PipeReader, WriteFromError};
+
+impl<R: Read + Send + 'static> FusedReader<R> {
+    /// Spawns a thread reading ahead from this reader into buffer of given size in bytes.
+    ///
+    /// Returned reader is protected by its own fuse armed by the prefetching thread; errors of this
+    /// reader, including its fuse errors, are delivered after the prefetched data, and if the
+    /// prefetching thread dies the reader gets `BrokenPipe` error. The thread stops once the
+    /// returned reader is dropped.
+    ///
+    /// Panics if `buffer_size` is zero.
+    pub fn prefetch(mut self, buffer_size: usize) -> FusedReader<PipeReader> {
+        let (reader, mut writer) = pipe_with_capacity(buffer_size);
+        let (mut reader, fuse) = fuse(reader);
+        reader.position = self.position;
+
+        thread::Builder::new().name("fused-prefetch".into()).spawn(move || {
+            let mut guard = match fuse.arm() {
+                Ok(guard) => guard,
+                Err(_) => return,
+            };
+            loop {
+                match writer.write_all_from(&mut self, u64::MAX) {
+                    Ok(_) => return,
+                    Err(WriteFromError::Source(err)) if err.kind() == ErrorKind::Interrupted => (),
+                    // keep reason of errors coming from the fuse of this reader
+                    Err(WriteFromError::Source(err)) if FuseError::from_io(&err).is_some() => {
+                        guard.result = Err(err);
+                        return
+                    }
+                    Err(WriteFromError::Source(err)) => return guard.blow(err),
+                    // prefetched reader is gone
+                    Err(WriteFromError::Pipe(_)) => return,
+                }
+            }
+        }).expect("failed to spawn prefetching thread");
+
+        reader
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::BlowReason;
+    use pipe::pipe;
+    use std::io::Write;
+
+    #[test]
+    fn test_prefetch() {
+        let (reader, mut writer) = pipe();
+        let (reader, fuse) = fuse(reader);
+        let mut reader = reader.prefetch(16);
+
+        thread::spawn(move || {
+            let _fuse = fuse.arm().unwrap();
+            writer.write_all(&[1; 100]).unwrap();
+        });
+
+        let mut data = Vec::new();
+        reader.read_to_end(&mut data).unwrap();
+        assert_eq!(data, vec![1; 100]);
+        assert_eq!(reader.position(), 100);
+    }
+
+    #[test]
+    fn test_prefetch_panic() {
+        let (reader, mut writer) = pipe();
+        let (reader, fuse) = fuse(reader);
+        let mut reader = reader.prefetch(16);
+
+        thread::spawn(move || {
+            let _fuse = fuse.arm().unwrap();
+            writer.write_all(&[1; 100]).unwrap();
+            panic!("boom");
+        });
+
+        let mut data = Vec::new();
+        assert_eq!(reader.read_to_end(&mut data).unwrap_err().kind(), ErrorKind::BrokenPipe);
+        assert_eq!(data, vec![1; 100]);
+        assert!(matches!(reader.blow_reason(), Some(BlowReason::Panic { .. })));
+    }
+}
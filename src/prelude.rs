@@ -0,0 +1,41 @@
+//! Re-exports of commonly used types and traits so that typical usage is a single import.
+//!
+//! ```rust
+//! use fused_reader::prelude::*;
+//! ```
+pub use crate::{fuse, fuse_named, Fuse, FuseGuard, FusedReader, FuseStatus};
+pub use crate::{IoFuse, IoFuseGuard};
+pub use crate::fuse_typed;
+pub use crate::{fuse_with, FusePolicy};
+pub use crate::{fuse_writer, FusedWriter};
+pub use crate::fused_pipe;
+pub use crate::OwnedFuseGuard;
+pub use crate::ArmedWriter;
+pub use crate::FusedTaskGroup;
+pub use crate::{fused_scope, FusedScope};
+pub use crate::{fuse_bundle, FuseBundle};
+pub use crate::{fuse_local, LocalFuse, LocalFusedReader};
+pub use crate::{DropBehavior, FuseHandle};
+pub use crate::SharedFusedReader;
+pub use crate::{fused_stdin, StdinReader};
+pub use crate::{fuse_child, ChildFuse};
+pub use crate::ReadTimeout;
+pub use crate::{error_code, CodedError, BlowReason};
+pub use crate::{extract_fuse_error, is_fuse_error, FuseError};
+
+#[cfg(feature = "stream")]
+pub use crate::{fuse_stream, FusedStream, AsyncFuse, AsyncFuseGuard};
+#[cfg(feature = "stream")]
+pub use crate::{fuse_stream_local, LocalAsyncFuse, LocalAsyncFuseGuard};
+
+#[cfg(feature = "crossbeam")]
+pub use crate::{fuse_receiver, FusedReceiver};
+
+#[cfg(any(feature = "futures-io", feature = "tokio"))]
+pub use crate::{fuse_async_read, FusedAsyncReader};
+
+#[cfg(feature = "async-std")]
+pub use crate::spawn_fused;
+
+#[cfg(all(feature = "iocp", windows))]
+pub use crate::fuse_named_pipe;
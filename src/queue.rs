@@ -0,0 +1,120 @@
+use std::collections::BTreeSet;
+use std::io::Error as IoError;
+use std::sync::MutexGuard;
+
+use crate::{Fuse, FuseGuard};
+
+/// Tickets of callers waiting to arm the fuse with `Fuse::arm_queued`.
+#[derive(Debug, Default)]
+pub(crate) struct ArmQueue {
+    next: u64,
+    serving: u64,
+    // tickets dropped before their turn
+    abandoned: BTreeSet<u64>,
+}
+
+impl ArmQueue {
+    fn advance(&mut self) {
+        self.serving += 1;
+        while self.abandoned.remove(&self.serving) {
+            self.serving += 1;
+        }
+    }
+}
+
+/// Place in the queue of callers waiting to arm the fuse in FIFO order.
+///
+/// Dropping the ticket gives up the place in the queue.
+#[derive(Debug)]
+pub struct ArmTicket<'a> {
+    fuse: &'a Fuse,
+    ticket: u64,
+}
+
+impl Fuse {
+    /// Queues for arming the fuse.
+    ///
+    /// Callers arming the fuse with tickets returned by this method get it in order of calling
+    /// this method rather than in order of winning the lock. Callers using `arm` directly are not
+    /// queued and may arm the fuse out of order.
+    pub fn arm_queued(&self) -> ArmTicket<'_> {
+        let mut queue = self.queue();
+        let ticket = queue.next;
+        queue.next += 1;
+        ArmTicket { fuse: self, ticket }
+    }
+
+    fn queue(&self) -> MutexGuard<'_, ArmQueue> {
+        self.0.queue.lock().unwrap_or_else(|err| err.into_inner())
+    }
+}
+
+impl<'a> ArmTicket<'a> {
+    /// Number of queued callers that will arm the fuse before this one.
+    pub fn position(&self) -> usize {
+        let queue = self.fuse.queue();
+        (queue.serving..self.ticket).filter(|ticket| !queue.abandoned.contains(ticket)).count()
+    }
+
+    /// Waits for the turn of this ticket and arms the fuse.
+    ///
+    /// Returns `BrokenPipe` error if reader was dropped due to panic.
+    pub fn arm(self) -> Result<FuseGuard<'a>, IoError> {
+        let mut queue = self.fuse.queue();
+        while queue.serving != self.ticket {
+            queue = self.fuse.0.queue_turn.wait(queue).unwrap_or_else(|err| err.into_inner());
+        }
+        drop(queue);
+        // the next ticket is served after dropping this one and will wait for the guard
+        self.fuse.arm()
+    }
+}
+
+impl<'a> Drop for ArmTicket<'a> {
+    fn drop(&mut self) {
+        let mut queue = self.fuse.queue();
+        if queue.serving == self.ticket {
+            queue.advance();
+            self.fuse.0.queue_turn.notify_all();
+        } else {
+            queue.abandoned.insert(self.ticket);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::fuse;
+    use std::sync::Mutex;
+    use std::thread;
+    use std::time::Duration;
+
+    #[test]
+    fn test_arm_queued_order() {
+        let (_reader, fuse) = fuse(std::io::empty());
+        let order = Mutex::new(Vec::new());
+
+        let first = fuse.arm_queued();
+        let tickets: Vec<_> = (1..4).map(|phase| {
+            let ticket = fuse.arm_queued();
+            assert_eq!(ticket.position(), phase);
+            (phase, ticket)
+        }).collect();
+        drop(fuse.arm_queued());
+
+        thread::scope(|scope| {
+            for (phase, ticket) in tickets.into_iter().rev() {
+                let order = &order;
+                scope.spawn(move || {
+                    let _guard = ticket.arm().unwrap();
+                    order.lock().unwrap().push(phase);
+                });
+            }
+
+            thread::sleep(Duration::from_millis(50));
+            let _guard = first.arm().unwrap();
+            order.lock().unwrap().push(0);
+        });
+        assert_eq!(*order.lock().unwrap(), vec![0, 1, 2, 3]);
+    }
+}
@@ -0,0 +1,184 @@
+use std::error::Error;
+use std::fmt;
+use std::io::{Error as IoError, ErrorKind};
+use std::sync::Arc;
+
+use crate::{CodedError, PanicLocation};
+
+/// Reason why the fuse was blown.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum BlowReason {
+    /// Writer blew the fuse with an explicit error.
+    ExplicitError,
+    /// Writer panicked while holding armed fuse.
+    Panic {
+        /// Panic message if it was captured.
+        msg: Option<String>,
+        /// Where the writer panicked if `install_panic_hook` was called.
+        location: Option<PanicLocation>,
+    },
+    /// Fuse was armed past its TTL.
+    Timeout,
+    /// Writer task was dropped while holding armed fuse.
+    Cancelled,
+    /// Stream ended before the writer signalled completion.
+    Incomplete,
+    /// Reader end is gone.
+    ReaderGone,
+    /// Reader end cancelled the stream.
+    ReaderCancelled,
+    /// Another producer of the same `FusedTaskGroup` failed.
+    SiblingFailed,
+    /// Pipe memory limit was exceeded; see `ring_pipe::PipeReader::set_memory_limit`.
+    MemoryLimit {
+        /// The limit in bytes.
+        limit: usize,
+    },
+}
+
+/// Inner error of I/O errors produced by the fuse carrying the `BlowReason`.
+///
+/// Errors delivered by fused readers are plain `io::Error`s; use `is_fuse_error` and
+/// `extract_fuse_error` to identify them in generic error handling code. The error the writer
+/// blew the fuse with, including the source given to `FuseGuard::blow_with_source`, is the
+/// `Error::source` of both the `FuseError` and the `io::Error` carrying it, so it can be downcast
+/// to its original type. Panic message and location are available in `BlowReason::Panic`.
+#[derive(Debug)]
+pub struct FuseError {
+    reason: BlowReason,
+    message: String,
+    // shared by copies of the error delivered by sticky fuse
+    source: Option<Arc<dyn Error + Send + Sync>>,
+}
+
+impl FuseError {
+    pub(crate) fn new_io(kind: ErrorKind, reason: BlowReason, message: impl Into<String>) -> IoError {
+        IoError::new(kind, FuseError {
+            reason,
+            message: message.into(),
+            source: None,
+        })
+    }
+
+    /// Wraps error writer blew the fuse with keeping its kind.
+    pub(crate) fn explicit(err: IoError) -> IoError {
+        IoError::new(err.kind(), FuseError {
+            reason: BlowReason::ExplicitError,
+            message: err.to_string(),
+            source: Some(Arc::new(err)),
+        })
+    }
+
+    /// Creates error the writer blew the fuse with from its description and source error.
+    pub(crate) fn with_source(kind: ErrorKind, message: String, source: Box<dyn Error + Send + Sync>) -> IoError {
+        IoError::new(kind, FuseError {
+            reason: BlowReason::ExplicitError,
+            message,
+            source: Some(Arc::from(source)),
+        })
+    }
+
+    pub(crate) fn from_io(err: &IoError) -> Option<&FuseError> {
+        err.get_ref().and_then(|err| err.downcast_ref::<FuseError>())
+    }
+
+    /// Creates copy of I/O error with the same kind, message, reason, explicit error and failure
+    /// code.
+    pub(crate) fn duplicate(err: &IoError) -> IoError {
+        if let Some(fuse_err) = FuseError::from_io(err) {
+            return IoError::new(err.kind(), FuseError {
+                reason: fuse_err.reason.clone(),
+                message: fuse_err.message.clone(),
+                source: fuse_err.source.clone(),
+            })
+        }
+        if let Some(coded) = err.get_ref().and_then(|err| err.downcast_ref::<CodedError>()) {
+            return IoError::new(err.kind(), coded.clone())
+        }
+        match err.raw_os_error() {
+            Some(code) => IoError::from_raw_os_error(code),
+            None => IoError::new(err.kind(), err.to_string()),
+        }
+    }
+
+    /// Reason why the fuse was blown.
+    pub fn reason(&self) -> &BlowReason {
+        &self.reason
+    }
+
+    /// Original error the writer blew the fuse with if the reason is `BlowReason::ExplicitError`
+    /// and it was an `io::Error`.
+    pub fn explicit_error(&self) -> Option<&IoError> {
+        self.source.as_deref().and_then(|err| err.downcast_ref())
+    }
+}
+
+/// Returns `true` if the I/O error originated from a fuse.
+pub fn is_fuse_error(err: &IoError) -> bool {
+    FuseError::from_io(err).is_some()
+}
+
+/// Returns `FuseError` carried by the I/O error if it originated from a fuse.
+pub fn extract_fuse_error(err: &IoError) -> Option<&FuseError> {
+    FuseError::from_io(err)
+}
+
+impl fmt::Display for FuseError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.message)
+    }
+}
+
+impl Error for FuseError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        self.source.as_deref().map(|err| err as &(dyn Error + 'static))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::fuse;
+    use std::io::Read;
+
+    #[test]
+    fn test_extract_fuse_error() {
+        let (mut reader, fuse) = fuse(std::io::empty());
+        fuse.arm().unwrap().blow(IoError::new(ErrorKind::UnexpectedEof, "uh! oh!"));
+
+        let err = reader.read(&mut [0; 1]).unwrap_err();
+        assert!(is_fuse_error(&err));
+        let fuse_err = extract_fuse_error(&err).unwrap();
+        assert_eq!(fuse_err.reason(), &BlowReason::ExplicitError);
+        assert_eq!(fuse_err.explicit_error().unwrap().kind(), ErrorKind::UnexpectedEof);
+
+        assert!(!is_fuse_error(&IoError::new(ErrorKind::UnexpectedEof, "uh! oh!")));
+    }
+
+    #[derive(Debug)]
+    struct ParseError(usize);
+
+    impl fmt::Display for ParseError {
+        fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+            write!(f, "parse error at {}", self.0)
+        }
+    }
+
+    impl Error for ParseError {}
+
+    #[test]
+    fn test_blow_with_source() {
+        let (mut reader, fuse) = fuse(std::io::empty());
+        fuse.arm().unwrap().blow_with_source(ErrorKind::InvalidData, "bad record", ParseError(7));
+
+        for _ in 0..2 {
+            let err = reader.read(&mut [0; 1]).unwrap_err();
+            assert_eq!(err.kind(), ErrorKind::InvalidData);
+            assert_eq!(err.to_string(), "bad record");
+            assert_eq!(err.source().unwrap().downcast_ref::<ParseError>().unwrap().0, 7);
+            let fuse_err = extract_fuse_error(&err).unwrap();
+            assert_eq!(fuse_err.reason(), &BlowReason::ExplicitError);
+            assert!(fuse_err.explicit_error().is_none());
+        }
+    }
+}
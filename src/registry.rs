@@ -0,0 +1,125 @@
+use std::io::Read;
+use std::sync::{Arc, Mutex, MutexGuard, Weak};
+use std::sync::atomic::Ordering;
+use std::time::{Duration, Instant};
+
+use crate::{FusedReader, FuseShared, BLOWN, POISONED};
+
+static REGISTRY: Mutex<Vec<Weak<FuseShared>>> = Mutex::new(Vec::new());
+
+#[derive(Debug)]
+pub(crate) struct Registration {
+    name: String,
+    registered: Instant,
+}
+
+/// State of a registered fuse at the time `live_fuses` was called.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LiveFuseState {
+    /// Fuse is not armed.
+    Unarmed,
+    /// Fuse is armed.
+    Armed,
+    /// Writer signalled completion and released the fuse.
+    Completed,
+    /// Fuse was blown and the reader did not get the error yet.
+    Blown,
+    /// Writer panicked while holding armed fuse.
+    Poisoned,
+}
+
+/// Diagnostic information about a registered fuse.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LiveFuse {
+    /// Name given with `FusedReader::register`.
+    pub name: String,
+    /// State of the fuse.
+    pub state: LiveFuseState,
+    /// Time since the fuse was registered.
+    pub age: Duration,
+    /// Number of bytes the reader consumed from the stream.
+    pub bytes: u64,
+}
+
+impl<R: Read> FusedReader<R> {
+    /// Registers the fuse of this reader under given name so it is listed by `live_fuses`.
+    ///
+    /// The fuse is listed until both the reader and the `Fuse` are dropped. Registering again has
+    /// no effect.
+    pub fn register(&mut self, name: impl Into<String>) {
+        let registration = Registration {
+            name: name.into(),
+            registered: Instant::now(),
+        };
+        if self.fuse.registration.set(registration).is_ok() {
+            let mut registry = registry();
+            // so that the registry does not grow with readers registered over time
+            registry.retain(|fuse| fuse.strong_count() > 0);
+            registry.push(Arc::downgrade(&self.fuse));
+        }
+    }
+}
+
+fn registry() -> MutexGuard<'static, Vec<Weak<FuseShared>>> {
+    REGISTRY.lock().unwrap_or_else(|err| err.into_inner())
+}
+
+fn state(shared: &FuseShared) -> LiveFuseState {
+    match shared.status() {
+        _ if shared.is_guarded() => LiveFuseState::Armed,
+        POISONED => LiveFuseState::Poisoned,
+        BLOWN => LiveFuseState::Blown,
+        _ if shared.is_armed() => LiveFuseState::Armed,
+        _ if shared.completed.load(Ordering::Acquire) => LiveFuseState::Completed,
+        _ => LiveFuseState::Unarmed,
+    }
+}
+
+/// Lists fuses of readers registered with `FusedReader::register` that are still alive.
+///
+/// This does not consume pending fuse errors and can be used to dump in-flight streams from a
+/// debug endpoint or signal handling thread.
+pub fn live_fuses() -> Vec<LiveFuse> {
+    let mut registry = registry();
+    registry.retain(|fuse| fuse.strong_count() > 0);
+    registry.iter().filter_map(Weak::upgrade).filter_map(|shared| {
+        let registration = shared.registration.get()?;
+        Some(LiveFuse {
+            name: registration.name.clone(),
+            state: state(&shared),
+            age: registration.registered.elapsed(),
+            bytes: shared.consumed.load(Ordering::Relaxed),
+        })
+    }).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::fuse;
+    use std::io::{Cursor, Error as IoError, ErrorKind};
+
+    fn find(name: &str) -> Option<LiveFuse> {
+        live_fuses().into_iter().find(|fuse| fuse.name == name)
+    }
+
+    #[test]
+    fn test_live_fuses() {
+        let (mut reader, fuse) = fuse(Cursor::new(vec![1, 2, 3]));
+        reader.register("test_live_fuses");
+        assert_eq!(find("test_live_fuses").unwrap().state, LiveFuseState::Unarmed);
+
+        let guard = fuse.arm().unwrap();
+        reader.read_exact(&mut [0; 2]).unwrap();
+        let live = find("test_live_fuses").unwrap();
+        assert_eq!(live.state, LiveFuseState::Armed);
+        assert_eq!(live.bytes, 2);
+
+        guard.blow(IoError::new(ErrorKind::InvalidData, "uh! oh!"));
+        assert_eq!(find("test_live_fuses").unwrap().state, LiveFuseState::Blown);
+
+        drop(reader);
+        drop(fuse);
+        assert!(find("test_live_fuses").is_none());
+    }
+}
@@ -0,0 +1,1162 @@
+//! In-crate ring buffer pipe for streaming data between threads.
+//!
+//! The pipe has two writer lanes: normal and high-priority. Data written to the high-priority lane
+//! is delivered to the reader ahead of any queued normal data, which allows interleaving control
+//! records into a bulk stream. Each lane has its own capacity so a full normal lane does not block
+//! high-priority writers. Framing of the interleaved data is up to the user.
+//!
+//! Either end can be closed explicitly with `PipeWriter::close_write` and `PipeReader::close_read`.
+//! Which side closed first and how is recorded on the fuse of the pipe, see `first_close`, so that
+//! fused reader of the pipe reports it like with any other transport.
+//!
+//! Pipes created with `pipe_with_growth` start small and double their capacity, up to a maximum,
+//! whenever a writer stays blocked on a full lane for a while; see `capacity_stats`.
+//!
+//! Pipes created with `double_buffered_pipe` minimize synchronization for high-throughput streams:
+//! the writer fills its own buffer and hands it over to the pipe as a whole while the reader drains
+//! the buffer it took over before; see `DoubleBufferedWriter`.
+//!
+//! Readers of pipes with coalescing set (see `PipeReader::set_coalescing`) wait for small writes to
+//! accumulate into larger chunks before returning data.
+//!
+//! Pipes created with `fused_pipe` come with both ends fused: the reader fails if the writing thread
+//! dies while holding armed fuse and the writer fails once the reader end is dropped due to panic.
+//!
+//! Pipes created with `unbounded_pipe` never block writers. To protect the process from running out
+//! of memory a hard memory limit can be set on any pipe; once a write would exceed it the pipe fails
+//! with `OutOfMemory` error for the writers and for the reader after it consumes buffered data, and
+//! the fuse attached to the pipe is blown with it; see `PipeReader::attach_fuse`.
+//!
+//! ```rust
+//! use fused_reader::ring_pipe::pipe;
+//! use std::io::{Read, Write};
+//!
+//! let (mut reader, mut writer) = pipe();
+//! let mut control = writer.priority_lane();
+//!
+//! writer.write_all(b"bulk").unwrap();
+//! control.write_all(b"ctl").unwrap();
+//! drop(writer);
+//! drop(control);
+//!
+//! let mut data = String::new();
+//! reader.read_to_string(&mut data).unwrap();
+//! assert_eq!(data, "ctlbulk");
+//! ```
+use std::collections::VecDeque;
+use std::error::Error;
+use std::fmt;
+use std::io::{BufRead, Read, Write, IoSlice, Error as IoError, ErrorKind};
+use std::sync::{Arc, Condvar, Mutex, MutexGuard};
+use std::time::{Duration, Instant};
+
+use crate::{fuse, fuse_writer, new_fuse_state, BlowReason, Fuse, FuseError, FuseState, FusedReader, FusedWriter, OwnedFuseGuard, BLOWN};
+
+/// Default capacity of each lane of the pipe in bytes.
+pub const DEFAULT_CAPACITY: usize = 64 * 1024;
+
+// how long a writer waits on full lane before growable pipe grows
+const GROWTH_DELAY: Duration = Duration::from_millis(10);
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Lane {
+    Normal,
+    Priority,
+}
+
+/// Event that closed one side of the pipe.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CloseEvent {
+    /// All writers were closed or dropped and at least the last one was closed with `close_write`.
+    WriteClosed,
+    /// Last writer was dropped without calling `close_write`.
+    WriterDropped,
+    /// Reader was closed with `close_read`.
+    ReadClosed,
+    /// Reader was dropped.
+    ReaderDropped,
+}
+
+/// Coalescing of small writes into larger reads; see `PipeReader::set_coalescing`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Coalescing {
+    /// Number of buffered bytes at which reads return without waiting; capped by lane capacity.
+    pub min_bytes: usize,
+    /// Maximal time the buffered data waits for more data before it is returned.
+    pub max_latency: Duration,
+}
+
+/// Capacity of the pipe and its growth history.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct CapacityStats {
+    /// Current capacity of each lane in bytes.
+    pub capacity: usize,
+    /// Capacity the lanes can grow to.
+    pub max_capacity: usize,
+    /// Number of times the capacity was doubled.
+    pub growths: u64,
+}
+
+#[derive(Debug)]
+struct State {
+    normal: VecDeque<u8>,
+    priority: VecDeque<u8>,
+    capacity: usize,
+    max_capacity: usize,
+    growths: u64,
+    memory_limit: Option<usize>,
+    coalescing: Option<Coalescing>,
+    // when the normal lane became non-empty
+    normal_since: Option<Instant>,
+    failed: Option<(ErrorKind, String)>,
+    // of blocked reads; see `ReadTimeout`
+    read_timeout: Option<Duration>,
+    writers: usize,
+    reader: bool,
+    // armed for the writer of fused pipe while the reader is alive
+    reader_fuse: Option<OwnedFuseGuard>,
+    // fuse of the reader of the pipe and its generation when attached, or own fuse of the pipe;
+    // see `attach_fuse`
+    fuse: (FuseState, u64),
+}
+
+impl State {
+    fn buffered(&self) -> usize {
+        self.normal.len() + self.priority.len()
+    }
+
+    fn closed(&mut self, event: CloseEvent) {
+        let (fuse, generation) = &self.fuse;
+        fuse.set_first_close(*generation, event);
+    }
+
+    fn first_close(&self) -> Option<CloseEvent> {
+        let (fuse, generation) = &self.fuse;
+        fuse.first_close().filter(|_| fuse.generation() == *generation)
+    }
+
+    fn failure(&self) -> Option<IoError> {
+        self.failed.as_ref().map(|(kind, message)| IoError::new(*kind, message.as_str()))
+    }
+
+    fn lane(&mut self, lane: Lane) -> &mut VecDeque<u8> {
+        match lane {
+            Lane::Normal => &mut self.normal,
+            Lane::Priority => &mut self.priority,
+        }
+    }
+
+    /// Records that data was written to the lane.
+    fn filled(&mut self, lane: Lane) {
+        if lane == Lane::Normal {
+            self.normal_since.get_or_insert_with(Instant::now);
+        }
+    }
+
+    /// Records that data was read from the lane.
+    fn drained(&mut self, lane: Lane) {
+        if lane == Lane::Normal && self.normal.is_empty() {
+            self.normal_since = None;
+        }
+    }
+
+    /// Returns how long the reader should wait for more data to coalesce in the normal lane.
+    fn coalescing_wait(&self) -> Option<Duration> {
+        let coalescing = self.coalescing?;
+        if self.normal.len() >= coalescing.min_bytes.min(self.capacity) || self.writers == 0 || !self.reader || self.failed.is_some() {
+            return None
+        }
+        let waited = self.normal_since?.elapsed();
+        coalescing.max_latency.checked_sub(waited).filter(|wait| *wait > Duration::from_secs(0))
+    }
+}
+
+#[derive(Debug)]
+struct Shared {
+    state: Mutex<State>,
+    readable: Condvar,
+    writable: Condvar,
+}
+
+impl Shared {
+    fn lock(&self) -> MutexGuard<'_, State> {
+        self.state.lock().unwrap_or_else(|err| err.into_inner())
+    }
+
+    fn memory_limit(&self) -> Option<usize> {
+        self.lock().memory_limit
+    }
+
+    fn capacity_stats(&self) -> CapacityStats {
+        let state = self.lock();
+        CapacityStats {
+            capacity: state.capacity,
+            max_capacity: state.max_capacity,
+            growths: state.growths,
+        }
+    }
+
+    fn set_memory_limit(&self, limit: Option<usize>) {
+        self.lock().memory_limit = limit;
+    }
+
+    fn first_close(&self) -> Option<CloseEvent> {
+        self.lock().first_close()
+    }
+
+    fn close_read(&self, event: CloseEvent) {
+        let mut state = self.lock();
+        // signal panic to the writer before it sees the pipe closed
+        drop(state.reader_fuse.take());
+        if state.reader {
+            state.reader = false;
+            state.normal.clear();
+            state.priority.clear();
+            state.closed(event);
+        }
+        self.writable.notify_all();
+    }
+
+    fn close_write(&self, event: CloseEvent) {
+        let mut state = self.lock();
+        state.writers -= 1;
+        if state.writers == 0 {
+            state.closed(event);
+        }
+        self.readable.notify_all();
+    }
+}
+
+/// Creates pipe with `DEFAULT_CAPACITY` per lane.
+pub fn pipe() -> (PipeReader, PipeWriter) {
+    pipe_with_capacity(DEFAULT_CAPACITY)
+}
+
+/// Creates pipe with given capacity in bytes per lane.
+///
+/// Panics if `capacity` is zero.
+pub fn pipe_with_capacity(capacity: usize) -> (PipeReader, PipeWriter) {
+    assert!(capacity > 0, "pipe capacity must be greater than zero");
+    new_pipe(capacity, capacity)
+}
+
+/// Creates pipe with `DEFAULT_CAPACITY` per lane and both ends fused.
+///
+/// The returned fuse is armed by the writing thread like with `fuse`. The writer end fails with
+/// `BrokenPipe` error once the reader end was dropped due to panic.
+pub fn fused_pipe() -> (FusedReader<PipeReader>, FusedWriter<PipeWriter>, Fuse) {
+    fused_pipe_with_capacity(DEFAULT_CAPACITY)
+}
+
+/// Creates pipe with given capacity in bytes per lane and both ends fused; see `fused_pipe`.
+///
+/// Panics if `capacity` is zero.
+pub fn fused_pipe_with_capacity(capacity: usize) -> (FusedReader<PipeReader>, FusedWriter<PipeWriter>, Fuse) {
+    let (reader, writer) = pipe_with_capacity(capacity);
+    let (writer, reader_fuse) = fuse_writer(writer);
+    reader.0.lock().reader_fuse = Some(reader_fuse.arm_owned().expect("new fuse can be armed"));
+    let (reader, fuse) = fuse(reader);
+    reader.get_ref().attach_fuse(&fuse);
+    (reader, writer, fuse)
+}
+
+/// Creates pipe with `initial` capacity in bytes per lane that grows up to `max_capacity`.
+///
+/// Capacity doubles each time a writer stays blocked on a full lane for more than 10ms.
+///
+/// Panics if `initial` is zero or greater than `max_capacity`.
+pub fn pipe_with_growth(initial: usize, max_capacity: usize) -> (PipeReader, PipeWriter) {
+    assert!(initial > 0, "pipe capacity must be greater than zero");
+    assert!(initial <= max_capacity, "initial pipe capacity must not exceed its maximum capacity");
+    new_pipe(initial, max_capacity)
+}
+
+/// Creates double-buffered pipe with buffers of given size in bytes.
+///
+/// Panics if `buffer_size` is zero.
+pub fn double_buffered_pipe(buffer_size: usize) -> (DoubleBufferedReader, DoubleBufferedWriter) {
+    let (reader, writer) = pipe_with_capacity(buffer_size);
+    (reader.double_buffered(), writer.double_buffered(buffer_size))
+}
+
+/// Creates pipe that never blocks writers.
+///
+/// Use `set_memory_limit` to cap the amount of buffered data.
+pub fn unbounded_pipe() -> (PipeReader, PipeWriter) {
+    new_pipe(usize::MAX, usize::MAX)
+}
+
+fn new_pipe(capacity: usize, max_capacity: usize) -> (PipeReader, PipeWriter) {
+    let shared = Arc::new(Shared {
+        state: Mutex::new(State {
+            normal: VecDeque::with_capacity(capacity.min(DEFAULT_CAPACITY)),
+            priority: VecDeque::new(),
+            capacity,
+            max_capacity,
+            growths: 0,
+            memory_limit: None,
+            coalescing: None,
+            normal_since: None,
+            failed: None,
+            read_timeout: None,
+            writers: 1,
+            reader: true,
+            reader_fuse: None,
+            fuse: (new_fuse_state(), 0),
+        }),
+        readable: Condvar::new(),
+        writable: Condvar::new(),
+    });
+
+    (PipeReader(shared.clone()), PipeWriter { shared, lane: Lane::Normal, closed: false })
+}
+
+/// Reading end of the pipe.
+///
+/// Reads return EOF once all writers were dropped and all data was consumed.
+#[derive(Debug)]
+pub struct PipeReader(Arc<Shared>);
+
+impl PipeReader {
+    /// Blocks until data is available returning lane to read from or `None` on EOF.
+    ///
+    /// Fails if the pipe failed and all buffered data was consumed, or with `TimedOut` error once
+    /// the read timeout elapsed.
+    fn wait_readable(&self) -> Result<(MutexGuard<'_, State>, Option<Lane>), IoError> {
+        let mut state = self.0.lock();
+        let deadline = state.read_timeout.map(|timeout| Instant::now() + timeout);
+        loop {
+            if !state.priority.is_empty() {
+                return Ok((state, Some(Lane::Priority)))
+            } else if !state.normal.is_empty() {
+                match state.coalescing_wait() {
+                    Some(wait) => {
+                        state = self.0.readable.wait_timeout(state, wait).unwrap_or_else(|err| err.into_inner()).0;
+                        continue
+                    }
+                    None => return Ok((state, Some(Lane::Normal))),
+                }
+            } else if let Some(err) = state.failure() {
+                return Err(err)
+            } else if state.writers == 0 || !state.reader {
+                return Ok((state, None))
+            }
+            state = match deadline {
+                Some(deadline) => {
+                    let timeout = deadline.saturating_duration_since(Instant::now());
+                    if timeout == Duration::from_secs(0) {
+                        return Err(IoError::new(ErrorKind::TimedOut, "timed out waiting for data"))
+                    }
+                    self.0.readable.wait_timeout(state, timeout).unwrap_or_else(|err| err.into_inner()).0
+                }
+                None => self.0.readable.wait(state).unwrap_or_else(|err| err.into_inner()),
+            };
+        }
+    }
+
+    /// Blocks until data is available and swaps the whole internal buffer of a lane with `spare`.
+    ///
+    /// Returns empty buffer once all writers were dropped and all data was consumed.
+    pub(crate) fn swap_buffer(&mut self, mut spare: VecDeque<u8>) -> Result<VecDeque<u8>, IoError> {
+        spare.clear();
+        if let (mut state, Some(lane)) = self.wait_readable()? {
+            std::mem::swap(state.lane(lane), &mut spare);
+            state.drained(lane);
+            self.0.writable.notify_all();
+        }
+        Ok(spare)
+    }
+
+    /// Returns hard limit of buffered bytes, if set.
+    pub fn memory_limit(&self) -> Option<usize> {
+        self.0.memory_limit()
+    }
+
+    /// Converts into reader that takes over whole internal buffers of the pipe and drains them
+    /// without locking.
+    pub fn double_buffered(self) -> DoubleBufferedReader {
+        DoubleBufferedReader {
+            reader: self,
+            buf: VecDeque::new(),
+        }
+    }
+
+    /// Returns current capacity of the pipe and its growth history.
+    pub fn capacity_stats(&self) -> CapacityStats {
+        self.0.capacity_stats()
+    }
+
+    /// Sets hard limit of buffered bytes; `None` removes the limit.
+    ///
+    /// Write that would make the pipe buffer more data fails the pipe with `OutOfMemory` error.
+    pub fn set_memory_limit(&self, limit: Option<usize>) {
+        self.0.set_memory_limit(limit)
+    }
+
+    /// Makes reads wait for small writes to coalesce; `None` disables coalescing.
+    ///
+    /// Reads of the normal lane return once `min_bytes` are buffered or the oldest buffered data
+    /// waited for `max_latency`, whichever comes first, and without waiting once all writers are
+    /// gone. High-priority data is returned without waiting.
+    pub fn set_coalescing(&self, coalescing: Option<Coalescing>) {
+        self.0.lock().coalescing = coalescing;
+        self.0.readable.notify_all();
+    }
+
+    pub(crate) fn read_timeout(&self) -> Option<Duration> {
+        self.0.lock().read_timeout
+    }
+
+    pub(crate) fn set_read_timeout(&self, timeout: Option<Duration>) {
+        self.0.lock().read_timeout = timeout;
+    }
+
+    /// Attaches the fuse of the fused reader reading this pipe so that it records which side
+    /// closed the pipe first and gets blown once the memory limit was exceeded.
+    ///
+    /// The fuse of `fused_pipe` is attached already. Pipe events after the fuse was reset are not
+    /// recorded on it.
+    pub fn attach_fuse(&self, fuse: &Fuse) {
+        let mut state = self.0.lock();
+        let first_close = state.first_close();
+        state.fuse = (fuse.0.clone(), fuse.generation());
+        if let Some(event) = first_close {
+            state.closed(event);
+        }
+    }
+
+    /// Closes the reading side of the pipe discarding buffered data.
+    ///
+    /// Subsequent and blocked writes fail with `BrokenPipe` error and reads return EOF.
+    pub fn close_read(&mut self) {
+        self.0.close_read(CloseEvent::ReadClosed)
+    }
+
+    /// Returns the event that closed one side of the pipe first, if any.
+    pub fn first_close(&self) -> Option<CloseEvent> {
+        self.0.first_close()
+    }
+
+    /// Blocks until at least `bytes` are buffered or all writers are gone, returning number of
+    /// buffered bytes.
+    ///
+    /// Fails with `TimedOut` error if `timeout` elapses first and with `InvalidInput` error if
+    /// `bytes` exceeds maximum lane capacity. The fuse attached to the pipe is not observed; use
+    /// `FusedReader::wait_available` to stop waiting once the fuse gets blown.
+    pub fn wait_available(&self, bytes: usize, timeout: Duration) -> Result<usize, IoError> {
+        let deadline = Instant::now() + timeout;
+        let mut state = self.0.lock();
+        if bytes > state.max_capacity {
+            return Err(IoError::new(ErrorKind::InvalidInput, "waiting for more bytes than pipe capacity"))
+        }
+        loop {
+            let buffered = state.buffered();
+            if buffered >= bytes || state.writers == 0 || !state.reader {
+                return Ok(buffered)
+            }
+            if let Some(err) = state.failure() {
+                return Err(err)
+            }
+            let timeout = deadline.saturating_duration_since(Instant::now());
+            if timeout == Duration::from_secs(0) {
+                return Err(IoError::new(ErrorKind::TimedOut, "timed out waiting for data"))
+            }
+            state = self.0.readable.wait_timeout(state, timeout).unwrap_or_else(|err| err.into_inner()).0;
+        }
+    }
+}
+
+impl Read for PipeReader {
+    fn read(&mut self, buf: &mut [u8]) -> Result<usize, IoError> {
+        if buf.is_empty() {
+            return Ok(0)
+        }
+
+        match self.wait_readable()? {
+            (mut state, Some(lane)) => {
+                let data = state.lane(lane);
+                let bytes = buf.len().min(data.len());
+                for (dst, src) in buf.iter_mut().zip(data.drain(..bytes)) {
+                    *dst = src;
+                }
+                state.drained(lane);
+                self.0.writable.notify_all();
+                Ok(bytes)
+            }
+            (_, None) => Ok(0),
+        }
+    }
+}
+
+impl Drop for PipeReader {
+    fn drop(&mut self) {
+        self.0.close_read(CloseEvent::ReaderDropped)
+    }
+}
+
+/// Writing end of the pipe.
+///
+/// Writes block while the lane is full and fail with `BrokenPipe` error once the reader was dropped.
+/// Cloned writers write to the same lane.
+#[derive(Debug)]
+pub struct PipeWriter {
+    shared: Arc<Shared>,
+    lane: Lane,
+    closed: bool,
+}
+
+impl PipeWriter {
+    /// Returns writer for high-priority lane of this pipe.
+    ///
+    /// Data written with it will be delivered to the reader ahead of queued normal data.
+    pub fn priority_lane(&self) -> PipeWriter {
+        self.shared.lock().writers += 1;
+        PipeWriter { shared: self.shared.clone(), lane: Lane::Priority, closed: false }
+    }
+
+    /// Returns `true` if this writer writes to high-priority lane.
+    pub fn is_priority(&self) -> bool {
+        self.lane == Lane::Priority
+    }
+
+    /// Returns hard limit of buffered bytes, if set.
+    pub fn memory_limit(&self) -> Option<usize> {
+        self.shared.memory_limit()
+    }
+
+    /// Converts into writer that fills local buffer of given size in bytes without locking and
+    /// hands it over to the pipe once it is full or flushed.
+    ///
+    /// For buffers matching the lane capacity the hand over swaps the buffer with the emptied lane
+    /// buffer so data is not copied.
+    ///
+    /// Panics if `buffer_size` is zero.
+    pub fn double_buffered(self, buffer_size: usize) -> DoubleBufferedWriter {
+        assert!(buffer_size > 0, "buffer size must be greater than zero");
+        DoubleBufferedWriter {
+            writer: self,
+            buf: Vec::new(),
+            buffer_size,
+        }
+    }
+
+    /// Returns current capacity of the pipe and its growth history.
+    pub fn capacity_stats(&self) -> CapacityStats {
+        self.shared.capacity_stats()
+    }
+
+    /// Sets hard limit of buffered bytes; `None` removes the limit.
+    ///
+    /// Write that would make the pipe buffer more data fails the pipe with `OutOfMemory` error.
+    pub fn set_memory_limit(&self, limit: Option<usize>) {
+        self.shared.set_memory_limit(limit)
+    }
+
+    /// Closes this writer signalling clean end of stream.
+    ///
+    /// Reader gets EOF once all writers were closed or dropped and buffered data was consumed.
+    /// Subsequent writes with this writer fail with `BrokenPipe` error.
+    pub fn close_write(&mut self) {
+        if !self.closed {
+            self.closed = true;
+            self.shared.close_write(CloseEvent::WriteClosed)
+        }
+    }
+
+    /// Returns the event that closed one side of the pipe first, if any.
+    pub fn first_close(&self) -> Option<CloseEvent> {
+        self.shared.first_close()
+    }
+}
+
+impl Clone for PipeWriter {
+    fn clone(&self) -> Self {
+        self.shared.lock().writers += 1;
+        PipeWriter { shared: self.shared.clone(), lane: self.lane, closed: false }
+    }
+}
+
+impl PipeWriter {
+    /// Transfers up to `limit` bytes from `source` to the pipe until `source` reaches EOF.
+    ///
+    /// Data is read into a buffer that is handed over to the pipe, becoming the lane buffer without
+    /// copying when the lane is empty, so no intermediate buffer is needed on the caller side.
+    /// Returns number of bytes transferred.
+    ///
+    /// Errors say which side failed: a `WriteFromError::Source` error is usually worth blowing the
+    /// fuse with so the reader learns why the stream ended, while `WriteFromError::Pipe` error
+    /// means that the reader is gone or the pipe failed.
+    pub fn write_all_from<R: Read + ?Sized>(&mut self, source: &mut R, limit: u64) -> Result<u64, WriteFromError> {
+        let mut buf = Vec::new();
+        let mut transferred = 0;
+        while transferred < limit {
+            let available = self.wait_writable().map_err(WriteFromError::Pipe)?.1;
+            let len = (limit - transferred).min(available.min(DEFAULT_CAPACITY) as u64) as usize;
+            buf.resize(len, 0);
+            let bytes = loop {
+                match source.read(&mut buf) {
+                    Ok(bytes) => break bytes,
+                    Err(err) if err.kind() == ErrorKind::Interrupted => (),
+                    Err(err) => return Err(WriteFromError::Source(err)),
+                }
+            };
+            if bytes == 0 {
+                break
+            }
+            buf.truncate(bytes);
+            buf = self.push_chunk(buf).map_err(WriteFromError::Pipe)?;
+            transferred += bytes as u64;
+        }
+        Ok(transferred)
+    }
+
+    /// Blocks until the lane has free space returning number of bytes that can be written.
+    fn wait_writable(&self) -> Result<(MutexGuard<'_, State>, usize), IoError> {
+        if self.closed {
+            return Err(IoError::new(ErrorKind::BrokenPipe, "pipe writer was closed"))
+        }
+
+        let mut state = self.shared.lock();
+        loop {
+            match state.first_close() {
+                _ if state.reader => (),
+                Some(CloseEvent::ReadClosed) => return Err(IoError::new(ErrorKind::BrokenPipe, "pipe reader was closed")),
+                _ => return Err(IoError::new(ErrorKind::BrokenPipe, "pipe reader was dropped")),
+            }
+            if let Some(err) = state.failure() {
+                return Err(err)
+            }
+
+            let capacity = state.capacity;
+            let available = capacity.saturating_sub(state.lane(self.lane).len());
+            if available > 0 {
+                return Ok((state, available))
+            }
+            if capacity < state.max_capacity {
+                let (guard, wait) = self.shared.writable.wait_timeout(state, GROWTH_DELAY).unwrap_or_else(|err| err.into_inner());
+                state = guard;
+                if wait.timed_out() && state.capacity == capacity && state.lane(self.lane).len() >= capacity {
+                    state.capacity = capacity.saturating_mul(2).min(state.max_capacity);
+                    state.growths += 1;
+                }
+                continue
+            }
+            state = self.shared.writable.wait(state).unwrap_or_else(|err| err.into_inner());
+        }
+    }
+
+    /// Fails the pipe if buffering additional `bytes` would exceed the memory limit.
+    fn reserve<'s>(&'s self, mut state: MutexGuard<'s, State>, bytes: usize) -> Result<MutexGuard<'s, State>, IoError> {
+        if let Some(limit) = state.memory_limit {
+            if state.buffered() + bytes > limit {
+                let message = format!("pipe memory limit of {} bytes exceeded", limit);
+                let err = FuseError::new_io(ErrorKind::OutOfMemory, BlowReason::MemoryLimit { limit }, message.as_str());
+                state.failed = Some((ErrorKind::OutOfMemory, message));
+                let failure = state.failure().unwrap();
+                let (fuse, generation) = state.fuse.clone();
+                self.shared.readable.notify_all();
+                // `on_blow` callbacks may use the pipe
+                drop(state);
+                fuse.set_error(generation, err, BLOWN);
+                return Err(failure)
+            }
+        }
+        Ok(state)
+    }
+
+    fn write_bufs(&mut self, bufs: &[IoSlice<'_>]) -> Result<usize, IoError> {
+        let len: usize = bufs.iter().map(|buf| buf.len()).sum();
+        if len == 0 {
+            return Ok(0)
+        }
+
+        let (state, available) = self.wait_writable()?;
+        let bytes = len.min(available);
+        let mut state = self.reserve(state, bytes)?;
+
+        let data = state.lane(self.lane);
+        let mut remaining = bytes;
+        for buf in bufs {
+            let chunk = remaining.min(buf.len());
+            data.extend(&buf[..chunk]);
+            remaining -= chunk;
+            if remaining == 0 {
+                break
+            }
+        }
+        state.filled(self.lane);
+        self.shared.readable.notify_all();
+        Ok(bytes)
+    }
+
+    /// Writes whole chunk to the lane returning emptied buffer for reuse.
+    fn push_chunk(&mut self, chunk: Vec<u8>) -> Result<Vec<u8>, IoError> {
+        let mut chunk = VecDeque::from(chunk);
+        while !chunk.is_empty() {
+            let (state, available) = self.wait_writable()?;
+            let bytes = chunk.len().min(available);
+            let mut state = self.reserve(state, bytes)?;
+
+            let data = state.lane(self.lane);
+            if data.is_empty() && bytes == chunk.len() {
+                std::mem::swap(data, &mut chunk);
+            } else {
+                data.extend(chunk.drain(..bytes));
+            }
+            state.filled(self.lane);
+            self.shared.readable.notify_all();
+        }
+        Ok(Vec::from(chunk))
+    }
+}
+
+/// Error of `PipeWriter::write_all_from` telling which side of the transfer failed.
+#[derive(Debug)]
+pub enum WriteFromError {
+    /// Reading from the source failed.
+    Source(IoError),
+    /// Writing to the pipe failed.
+    Pipe(IoError),
+}
+
+impl WriteFromError {
+    /// Returns the underlying I/O error.
+    pub fn into_inner(self) -> IoError {
+        match self {
+            WriteFromError::Source(err) | WriteFromError::Pipe(err) => err,
+        }
+    }
+}
+
+impl fmt::Display for WriteFromError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            WriteFromError::Source(err) => write!(f, "failed to read from source: {}", err),
+            WriteFromError::Pipe(err) => write!(f, "failed to write to pipe: {}", err),
+        }
+    }
+}
+
+impl Error for WriteFromError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            WriteFromError::Source(err) | WriteFromError::Pipe(err) => Some(err),
+        }
+    }
+}
+
+impl From<WriteFromError> for IoError {
+    fn from(err: WriteFromError) -> IoError {
+        err.into_inner()
+    }
+}
+
+impl Write for PipeWriter {
+    fn write(&mut self, buf: &[u8]) -> Result<usize, IoError> {
+        self.write_bufs(&[IoSlice::new(buf)])
+    }
+
+    /// Writes data from all slices under single lock of the pipe without concatenating them first.
+    fn write_vectored(&mut self, bufs: &[IoSlice<'_>]) -> Result<usize, IoError> {
+        self.write_bufs(bufs)
+    }
+
+    fn flush(&mut self) -> Result<(), IoError> {
+        Ok(())
+    }
+}
+
+impl Drop for PipeWriter {
+    fn drop(&mut self) {
+        if !self.closed {
+            self.shared.close_write(CloseEvent::WriterDropped)
+        }
+    }
+}
+
+/// Reading end of double-buffered pipe.
+#[derive(Debug)]
+pub struct DoubleBufferedReader {
+    reader: PipeReader,
+    buf: VecDeque<u8>,
+}
+
+impl DoubleBufferedReader {
+    /// Returns inner pipe reader; data taken over from the pipe and not read yet is lost.
+    pub fn into_inner(self) -> PipeReader {
+        self.reader
+    }
+}
+
+impl Read for DoubleBufferedReader {
+    fn read(&mut self, buf: &mut [u8]) -> Result<usize, IoError> {
+        let data = self.fill_buf()?;
+        let bytes = buf.len().min(data.len());
+        buf[..bytes].copy_from_slice(&data[..bytes]);
+        self.consume(bytes);
+        Ok(bytes)
+    }
+}
+
+impl BufRead for DoubleBufferedReader {
+    fn fill_buf(&mut self) -> Result<&[u8], IoError> {
+        if self.buf.is_empty() {
+            let spare = std::mem::take(&mut self.buf);
+            self.buf = self.reader.swap_buffer(spare)?;
+        }
+        Ok(self.buf.as_slices().0)
+    }
+
+    fn consume(&mut self, amt: usize) {
+        self.buf.drain(..amt.min(self.buf.len()));
+    }
+}
+
+/// Writing end of double-buffered pipe.
+///
+/// Buffered data is handed over to the pipe when the writer is dropped; use `flush` to get errors.
+#[derive(Debug)]
+pub struct DoubleBufferedWriter {
+    writer: PipeWriter,
+    buf: Vec<u8>,
+    buffer_size: usize,
+}
+
+impl DoubleBufferedWriter {
+    fn hand_over(&mut self) -> Result<(), IoError> {
+        if !self.buf.is_empty() {
+            let chunk = std::mem::take(&mut self.buf);
+            self.buf = self.writer.push_chunk(chunk)?;
+        }
+        Ok(())
+    }
+}
+
+impl Write for DoubleBufferedWriter {
+    fn write(&mut self, buf: &[u8]) -> Result<usize, IoError> {
+        if self.buf.len() == self.buffer_size {
+            self.hand_over()?;
+        }
+        if self.buf.capacity() < self.buffer_size {
+            self.buf.reserve_exact(self.buffer_size - self.buf.len());
+        }
+        let bytes = buf.len().min(self.buffer_size - self.buf.len());
+        self.buf.extend_from_slice(&buf[..bytes]);
+        Ok(bytes)
+    }
+
+    fn flush(&mut self) -> Result<(), IoError> {
+        self.hand_over()
+    }
+}
+
+impl Drop for DoubleBufferedWriter {
+    fn drop(&mut self) {
+        let _ = self.hand_over();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::thread;
+
+    #[test]
+    fn test_pipe() {
+        let (mut reader, mut writer) = pipe_with_capacity(3);
+
+        let writer = thread::spawn(move || {
+            writer.write_all(&[1; 100]).unwrap();
+        });
+
+        let mut data = Vec::new();
+        reader.read_to_end(&mut data).unwrap();
+        assert_eq!(data, vec![1; 100]);
+        writer.join().unwrap();
+    }
+
+    #[test]
+    fn test_pipe_reader_dropped() {
+        let (reader, mut writer) = pipe();
+        drop(reader);
+
+        assert_eq!(writer.write(&[1]).unwrap_err().kind(), ErrorKind::BrokenPipe);
+    }
+
+    #[test]
+    fn test_pipe_priority_lane() {
+        let (mut reader, mut writer) = pipe_with_capacity(4);
+        let mut control = writer.priority_lane();
+
+        // normal lane is full but priority lane is not blocked
+        writer.write_all(&[1; 4]).unwrap();
+        control.write_all(&[2; 2]).unwrap();
+
+        let mut buf = [0; 4];
+        assert_eq!(reader.read(&mut buf).unwrap(), 2);
+        assert_eq!(&buf[..2], &[2, 2]);
+        assert_eq!(reader.read(&mut buf).unwrap(), 4);
+        assert_eq!(&buf, &[1; 4]);
+
+        drop(writer);
+        control.write_all(&[3]).unwrap();
+        drop(control);
+
+        let mut data = Vec::new();
+        reader.read_to_end(&mut data).unwrap();
+        assert_eq!(data, vec![3]);
+    }
+
+    #[test]
+    fn test_unbounded_pipe_memory_limit() {
+        let (mut reader, mut writer) = unbounded_pipe();
+        writer.write_all(&[1; DEFAULT_CAPACITY * 2]).unwrap();
+
+        reader.set_memory_limit(Some(DEFAULT_CAPACITY * 3));
+        assert_eq!(writer.memory_limit(), Some(DEFAULT_CAPACITY * 3));
+        assert_eq!(writer.write_all(&[1; DEFAULT_CAPACITY * 2]).unwrap_err().kind(), ErrorKind::OutOfMemory);
+        assert_eq!(writer.write(&[1]).unwrap_err().kind(), ErrorKind::OutOfMemory);
+
+        let mut data = Vec::new();
+        assert_eq!(reader.read_to_end(&mut data).unwrap_err().kind(), ErrorKind::OutOfMemory);
+        assert_eq!(data.len(), DEFAULT_CAPACITY * 2);
+    }
+
+    #[test]
+    fn test_memory_limit_blows_attached_fuse() {
+        let (reader, mut writer) = unbounded_pipe();
+        reader.set_memory_limit(Some(4));
+        let (mut reader, writer_fuse) = fuse(reader);
+        reader.get_ref().attach_fuse(&writer_fuse);
+
+        let guard = writer_fuse.arm().unwrap();
+        writer.write_all(&[1; 3]).unwrap();
+        assert_eq!(writer.write_all(&[1; 3]).unwrap_err().kind(), ErrorKind::OutOfMemory);
+        drop(guard);
+        drop(writer);
+
+        let mut buf = [0; 3];
+        reader.read_exact(&mut buf).unwrap();
+        assert_eq!(reader.read(&mut buf).unwrap_err().kind(), ErrorKind::OutOfMemory);
+        assert!(matches!(reader.check_fuse(), crate::FuseStatus::Blown(err)
+            if crate::extract_fuse_error(&err).unwrap().reason() == &BlowReason::MemoryLimit { limit: 4 }));
+    }
+
+    #[test]
+    fn test_memory_limit_on_blow_uses_pipe() {
+        let (reader, mut writer) = unbounded_pipe();
+        reader.set_memory_limit(Some(1));
+        let (reader, writer_fuse) = fuse(reader);
+        reader.get_ref().attach_fuse(&writer_fuse);
+
+        let other = Mutex::new(writer.clone());
+        let (closed_tx, closed_rx) = std::sync::mpsc::channel();
+        let closed_tx = Mutex::new(closed_tx);
+        reader.on_blow(move |_err| closed_tx.lock().unwrap().send(other.lock().unwrap().first_close()).unwrap());
+
+        assert_eq!(writer.write_all(&[1; 2]).unwrap_err().kind(), ErrorKind::OutOfMemory);
+        assert_eq!(closed_rx.recv().unwrap(), None);
+    }
+
+    #[test]
+    fn test_first_close_recorded_on_fuse() {
+        let (reader, mut writer) = pipe();
+        writer.close_write();
+        let (mut reader, fuse) = fuse(reader);
+        reader.get_ref().attach_fuse(&fuse);
+        assert_eq!(reader.first_close(), Some(CloseEvent::WriteClosed));
+
+        drop(writer);
+        reader.get_mut().close_read();
+        assert_eq!(reader.first_close(), Some(CloseEvent::WriteClosed));
+        assert_eq!(reader.get_ref().first_close(), Some(CloseEvent::WriteClosed));
+
+        fuse.reset();
+        assert_eq!(reader.first_close(), None);
+        assert_eq!(reader.get_ref().first_close(), None);
+    }
+
+    #[test]
+    fn test_pipe_close_write() {
+        let (mut reader, mut writer) = pipe();
+        let other = writer.clone();
+
+        writer.write_all(&[1]).unwrap();
+        writer.close_write();
+        assert_eq!(writer.write(&[1]).unwrap_err().kind(), ErrorKind::BrokenPipe);
+        assert_eq!(reader.first_close(), None);
+        drop(other);
+        assert_eq!(reader.first_close(), Some(CloseEvent::WriterDropped));
+
+        let mut data = Vec::new();
+        reader.read_to_end(&mut data).unwrap();
+        assert_eq!(&data, &[1]);
+
+        let (reader, mut writer) = pipe();
+        writer.close_write();
+        drop(reader);
+        assert_eq!(writer.first_close(), Some(CloseEvent::WriteClosed));
+    }
+
+    #[test]
+    fn test_pipe_close_read() {
+        let (mut reader, mut writer) = pipe_with_capacity(1);
+
+        let blocked = thread::spawn(move || {
+            let res = writer.write_all(&[1, 2]);
+            (res, writer)
+        });
+        reader.close_read();
+
+        let (res, writer) = blocked.join().unwrap();
+        assert_eq!(res.unwrap_err().kind(), ErrorKind::BrokenPipe);
+        assert_eq!(writer.first_close(), Some(CloseEvent::ReadClosed));
+        assert_eq!(reader.read(&mut [0; 1]).unwrap(), 0);
+    }
+
+    #[test]
+    fn test_pipe_write_vectored() {
+        let (mut reader, mut writer) = pipe_with_capacity(4);
+
+        let bufs = [IoSlice::new(&[1, 2]), IoSlice::new(&[]), IoSlice::new(&[3, 4, 5])];
+        assert_eq!(writer.write_vectored(&bufs).unwrap(), 4);
+        drop(writer);
+
+        let mut data = Vec::new();
+        reader.read_to_end(&mut data).unwrap();
+        assert_eq!(&data, &[1, 2, 3, 4]);
+    }
+
+    #[test]
+    fn test_pipe_write_all_from() {
+        let (mut reader, mut writer) = pipe_with_capacity(3);
+
+        let writer = thread::spawn(move || {
+            let mut source = std::io::Cursor::new(vec![1; 100]);
+            assert_eq!(writer.write_all_from(&mut source, 60).unwrap(), 60);
+            assert_eq!(writer.write_all_from(&mut source, 60).unwrap(), 40);
+        });
+
+        let mut data = Vec::new();
+        reader.read_to_end(&mut data).unwrap();
+        assert_eq!(data, vec![1; 100]);
+        writer.join().unwrap();
+    }
+
+    #[test]
+    fn test_pipe_write_all_from_errors() {
+        struct FailingReader;
+
+        impl Read for FailingReader {
+            fn read(&mut self, _buf: &mut [u8]) -> Result<usize, IoError> {
+                Err(IoError::new(ErrorKind::InvalidData, "uh! oh!"))
+            }
+        }
+
+        let (reader, mut writer) = pipe();
+        match writer.write_all_from(&mut FailingReader, 10).unwrap_err() {
+            WriteFromError::Source(err) => assert_eq!(err.kind(), ErrorKind::InvalidData),
+            err => panic!("unexpected error: {}", err),
+        }
+
+        drop(reader);
+        match writer.write_all_from(&mut FailingReader, 10).unwrap_err() {
+            WriteFromError::Pipe(err) => assert_eq!(err.kind(), ErrorKind::BrokenPipe),
+            err => panic!("unexpected error: {}", err),
+        }
+    }
+
+    #[test]
+    fn test_pipe_with_growth() {
+        let (mut reader, mut writer) = pipe_with_growth(2, 8);
+        assert_eq!(reader.capacity_stats(), CapacityStats { capacity: 2, max_capacity: 8, growths: 0 });
+
+        writer.write_all(&[1; 8]).unwrap();
+        assert_eq!(writer.capacity_stats(), CapacityStats { capacity: 8, max_capacity: 8, growths: 2 });
+
+        let blocked = thread::spawn(move || {
+            writer.write_all(&[2; 4]).unwrap();
+        });
+        let mut data = Vec::new();
+        reader.read_to_end(&mut data).unwrap();
+        blocked.join().unwrap();
+        assert_eq!(data.len(), 12);
+        assert_eq!(reader.capacity_stats().growths, 2);
+    }
+
+    #[test]
+    fn test_pipe_coalescing() {
+        let (mut reader, mut writer) = pipe();
+        reader.set_coalescing(Some(Coalescing { min_bytes: 4, max_latency: Duration::from_secs(10) }));
+
+        let writer = thread::spawn(move || {
+            for _ in 0..4 {
+                writer.write_all(&[1]).unwrap();
+                thread::sleep(Duration::from_millis(5));
+            }
+            writer
+        });
+
+        let mut buf = [0; 8];
+        assert_eq!(reader.read(&mut buf).unwrap(), 4);
+        let mut writer = writer.join().unwrap();
+        writer.write_all(&[1; 2]).unwrap();
+
+        reader.set_coalescing(Some(Coalescing { min_bytes: 4, max_latency: Duration::from_millis(20) }));
+        let start = Instant::now();
+        assert_eq!(reader.read(&mut buf).unwrap(), 2);
+        assert!(start.elapsed() < Duration::from_secs(5));
+
+        writer.write_all(&[1]).unwrap();
+        drop(writer);
+        assert_eq!(reader.read(&mut buf).unwrap(), 1);
+    }
+
+    #[test]
+    fn test_double_buffered_pipe() {
+        let (mut reader, mut writer) = double_buffered_pipe(16);
+
+        let writer = thread::spawn(move || {
+            for _ in 0..10 {
+                writer.write_all(&[1; 7]).unwrap();
+            }
+            writer.flush().unwrap();
+            writer.write_all(&[2; 3]).unwrap();
+        });
+
+        let mut data = Vec::new();
+        reader.read_to_end(&mut data).unwrap();
+        assert_eq!(data.len(), 73);
+        assert_eq!(&data[70..], &[2; 3]);
+        writer.join().unwrap();
+    }
+
+    #[test]
+    fn test_fused_pipe() {
+        let (mut reader, mut writer, fuse) = fused_pipe();
+
+        thread::spawn(move || {
+            let _fuse = fuse.arm().unwrap();
+            writer.write_all(&[1]).unwrap();
+            panic!("boom");
+        });
+
+        let mut data = Vec::new();
+        assert_eq!(reader.read_to_end(&mut data).unwrap_err().kind(), ErrorKind::BrokenPipe);
+        assert_eq!(data, vec![1]);
+    }
+
+    #[test]
+    fn test_fused_pipe_reader_panic() {
+        let (reader, mut writer, _fuse) = fused_pipe_with_capacity(1);
+
+        thread::spawn(move || {
+            let _reader = reader;
+            panic!("boom");
+        }).join().unwrap_err();
+
+        let err = writer.write_all(&[1; 10]).unwrap_err();
+        assert_eq!(err.kind(), ErrorKind::BrokenPipe);
+        assert!(matches!(crate::extract_fuse_error(&err).unwrap().reason(), crate::BlowReason::Panic { .. }));
+    }
+}
@@ -0,0 +1,81 @@
+use std::io::{Read, Error as IoError};
+use std::panic::{self, AssertUnwindSafe};
+use std::thread::{self, Scope};
+
+use crate::ring_pipe::{self, PipeReader, PipeWriter};
+use crate::{fuse, FuseGuard, FusedReader};
+
+/// Runs `f` with `FusedScope` spawning scoped writer threads feeding fused readers.
+///
+/// Like `std::thread::scope` all the spawned threads are joined before returning. Writer panics
+/// are reported to their readers instead of propagating out of the scope.
+pub fn fused_scope<'env, F, T>(f: F) -> T
+    where F: for<'scope> FnOnce(FusedScope<'scope, 'env>) -> T {
+    thread::scope(|scope| f(FusedScope { scope }))
+}
+
+/// Scope of `fused_scope` spawning writer threads that can borrow from the environment.
+#[derive(Debug, Clone, Copy)]
+pub struct FusedScope<'scope, 'env> {
+    scope: &'scope Scope<'scope, 'env>,
+}
+
+impl<'scope, 'env> FusedScope<'scope, 'env> {
+    /// Spawns scoped thread writing to `writer` the stream read by returned fused reader.
+    ///
+    /// The producer is called with the fuse armed. Returning an error blows the fuse with it and
+    /// panic blows it with the panic message. The writer is dropped after the fuse was disarmed or
+    /// blown so the reader sees the outcome at EOF.
+    pub fn spawn<R, W, F>(&self, reader: R, writer: W, producer: F) -> FusedReader<R>
+        where R: Read, W: Send + 'scope, F: FnOnce(&mut W, &FuseGuard<'_>) -> Result<(), IoError> + Send + 'scope {
+        let (reader, fuse) = fuse(reader);
+        self.scope.spawn(move || {
+            let mut writer = writer;
+            // the outcome is reported to the reader
+            let _ = panic::catch_unwind(AssertUnwindSafe(|| fuse.armed(|guard| producer(&mut writer, guard))));
+            // EOF only after the outcome was recorded
+            drop(writer);
+        });
+        reader
+    }
+
+    /// Spawns scoped thread writing to a new `ring_pipe` the stream read by returned fused reader.
+    ///
+    /// See `spawn`.
+    pub fn spawn_writer<F>(&self, producer: F) -> FusedReader<PipeReader>
+        where F: FnOnce(&mut PipeWriter) -> Result<(), IoError> + Send + 'scope {
+        let (reader, writer) = ring_pipe::pipe();
+        self.spawn(reader, writer, |writer, _fuse| producer(writer))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::BlowReason;
+    use std::io::{Write, ErrorKind};
+
+    #[test]
+    fn test_fused_scope() {
+        let data = vec![1, 2, 3];
+
+        let (complete, failed, panicked) = fused_scope(|scope| {
+            let mut complete = scope.spawn_writer(|writer| writer.write_all(&data));
+            let mut failed = scope.spawn_writer(|writer| {
+                writer.write_all(&data[..1])?;
+                Err(IoError::new(ErrorKind::InvalidData, "uh! oh!"))
+            });
+            let mut panicked = scope.spawn_writer(|_writer| panic!("boom"));
+
+            let read = |reader: &mut FusedReader<PipeReader>| {
+                let mut data = Vec::new();
+                reader.read_to_end(&mut data).map(|_| data)
+            };
+            (read(&mut complete), read(&mut failed).map_err(|err| err.kind()), read(&mut panicked).map_err(|_| panicked.blow_reason().cloned()))
+        });
+
+        assert_eq!(complete.unwrap(), data);
+        assert_eq!(failed.unwrap_err(), ErrorKind::InvalidData);
+        assert!(matches!(panicked.unwrap_err(), Some(BlowReason::Panic { msg: Some(msg), .. }) if msg == "boom"));
+    }
+}
@@ -0,0 +1,105 @@
+use std::io::{Read, Error as IoError};
+use std::sync::{Arc, Mutex};
+
+use crate::FusedReader;
+
+/// Fused reader that can be cloned and read from multiple consumer threads.
+///
+/// Each `read` call gets the next available chunk of the stream so consumers receive disjoint
+/// parts of it. Once EOF is reached all consumers observe it; if the fuse was blown each of them
+/// fails with the error, see `FusedReader::check_fuse`.
+#[derive(Debug)]
+pub struct SharedFusedReader<R: Read>(Arc<Mutex<FusedReader<R>>>);
+
+impl<R: Read> Clone for SharedFusedReader<R> {
+    fn clone(&self) -> Self {
+        SharedFusedReader(self.0.clone())
+    }
+}
+
+impl<R: Read> FusedReader<R> {
+    /// Converts into reader that can be shared between multiple consumer threads.
+    pub fn into_shared(self) -> SharedFusedReader<R> {
+        SharedFusedReader(Arc::new(Mutex::new(self)))
+    }
+}
+
+impl<R: Read> Read for SharedFusedReader<R> {
+    fn read(&mut self, buf: &mut [u8]) -> Result<usize, IoError> {
+        self.0.lock().unwrap_or_else(|err| err.into_inner()).read(buf)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::fuse;
+    use pipe::pipe;
+    use std::io::{ErrorKind, Write};
+    use std::thread;
+
+    fn consume<R: Read + Send + 'static>(reader: &SharedFusedReader<R>, consumers: usize) -> Vec<Result<Vec<u8>, IoError>> {
+        (0..consumers).map(|_| {
+            let mut reader = reader.clone();
+            thread::spawn(move || {
+                let mut data = Vec::new();
+                let mut buf = [0; 3];
+                loop {
+                    match reader.read(&mut buf) {
+                        Ok(0) => return Ok(data),
+                        Ok(bytes) => data.extend_from_slice(&buf[..bytes]),
+                        Err(err) => return Err(err),
+                    }
+                }
+            })
+        }).collect::<Vec<_>>().into_iter().map(|consumer| consumer.join().unwrap()).collect()
+    }
+
+    #[test]
+    fn test_shared_nopanic() {
+        let (reader, mut writer) = pipe();
+        let (reader, fuse) = fuse(reader);
+        let reader = reader.into_shared();
+
+        thread::spawn(move || {
+            let _fuse = fuse.arm().unwrap();
+            writer.write_all(&[1; 100]).unwrap();
+        });
+
+        let results = consume(&reader, 4);
+        let total: usize = results.into_iter().map(|data| data.unwrap().len()).sum();
+        assert_eq!(total, 100);
+    }
+
+    #[test]
+    fn test_shared_tracks_position() {
+        let (reader, fuse) = fuse(std::io::Cursor::new(vec![1; 10]));
+        let progress = fuse.progress();
+        let mut reader = reader.into_shared();
+        fuse.arm().unwrap().expect_len(20);
+
+        let err = consume(&reader, 2).into_iter().find_map(Result::err).unwrap();
+        assert_eq!(err.kind(), ErrorKind::UnexpectedEof);
+        assert_eq!(progress.bytes_read(), 10);
+        assert_eq!(reader.read(&mut [0; 1]).unwrap_err().kind(), ErrorKind::UnexpectedEof);
+    }
+
+    #[test]
+    fn test_shared_panic() {
+        let (reader, mut writer) = pipe();
+        let (reader, fuse) = fuse(reader);
+        let reader = reader.into_shared();
+
+        thread::spawn(move || {
+            let _fuse = fuse.arm().unwrap();
+            writer.write_all(&[1; 100]).unwrap();
+            panic!("boom");
+        });
+
+        for result in consume(&reader, 4) {
+            let err = result.unwrap_err();
+            assert_eq!(err.kind(), ErrorKind::BrokenPipe);
+            assert!(crate::is_fuse_error(&err));
+        }
+    }
+}
@@ -0,0 +1,465 @@
+//! Pipe backed by memory mapped file for streaming data between processes.
+//!
+//! The consumer process creates the pipe with `create` getting a `FusedReader` and the producer
+//! process attaches to it with `open` getting `ShmWriter` that holds the fuse armed until it is
+//! dropped. The fuse state lives in the shared region together with the ring buffer so the reader
+//! gets the error the writer blew the fuse with and `BrokenPipe` error if the writer panicked.
+//!
+//! While attached the writer keeps bumping a heartbeat counter from a background thread. If the
+//! producer process crashes the counter stops changing and the reader fails with `BrokenPipe`
+//! error once the heartbeat timeout elapses (see `ShmReader::set_heartbeat_timeout`).
+//!
+//! Both sides wait for each other by polling the shared region with backoff so the pipe is intended
+//! for bulk transfers rather than low latency messaging. Only a single writer can attach to a pipe.
+//!
+//! ```rust
+//! use fused_reader::shm_pipe;
+//! use std::io::{Read, Write};
+//!
+//! let path = std::env::temp_dir().join(format!("fused-reader-doc-{}", std::process::id()));
+//! let mut reader = shm_pipe::create(&path, 4096).unwrap();
+//!
+//! // normally done by the producer process
+//! let mut writer = shm_pipe::open(&path).unwrap();
+//! writer.write_all(b"hello").unwrap();
+//! drop(writer);
+//!
+//! let mut data = String::new();
+//! reader.read_to_string(&mut data).unwrap();
+//! assert_eq!(data, "hello");
+//! # std::fs::remove_file(&path).unwrap();
+//! ```
+use std::fs::{File, OpenOptions};
+use std::io::{Read, Write, Error as IoError, ErrorKind};
+use std::os::unix::io::AsRawFd;
+use std::path::Path;
+use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, AtomicU8, AtomicU32, AtomicU64, Ordering};
+use std::thread;
+use std::time::{Duration, Instant};
+
+use crate::{fuse, BlowReason, Fuse, FuseError, FusedReader};
+use crate::code::{kind_from_index, kind_to_index};
+
+const MAGIC: u64 = 0x6675_7365_6470_6970;
+const MAX_MESSAGE: usize = 256;
+const HEARTBEAT_INTERVAL: Duration = Duration::from_millis(100);
+
+/// Default time after which the writer that stopped sending heartbeats is considered dead.
+pub const DEFAULT_HEARTBEAT_TIMEOUT: Duration = Duration::from_secs(1);
+
+const WAITING: u32 = 0;
+const ARMED: u32 = 1;
+const CLOSED: u32 = 2;
+const BLOWN: u32 = 3;
+const POISONED: u32 = 4;
+
+#[repr(C)]
+struct Header {
+    magic: AtomicU64,
+    capacity: AtomicU64,
+    written: AtomicU64,
+    read: AtomicU64,
+    state: AtomicU32,
+    reader_closed: AtomicU32,
+    heartbeat: AtomicU64,
+    error_kind: AtomicU32,
+    error_len: AtomicU32,
+    error_message: [AtomicU8; MAX_MESSAGE],
+}
+
+const HEADER_SIZE: usize = std::mem::size_of::<Header>().div_ceil(64) * 64;
+
+#[derive(Debug)]
+struct Region {
+    ptr: *mut u8,
+    len: usize,
+}
+
+// all access to the region goes through atomics or is synchronized with them
+unsafe impl Send for Region {}
+unsafe impl Sync for Region {}
+
+impl Region {
+    fn map(file: &File, len: usize) -> Result<Region, IoError> {
+        let ptr = unsafe {
+            libc::mmap(std::ptr::null_mut(), len, libc::PROT_READ | libc::PROT_WRITE, libc::MAP_SHARED, file.as_raw_fd(), 0)
+        };
+        if ptr == libc::MAP_FAILED {
+            return Err(IoError::last_os_error())
+        }
+        Ok(Region { ptr: ptr as *mut u8, len })
+    }
+
+    fn header(&self) -> &Header {
+        unsafe { &*(self.ptr as *const Header) }
+    }
+
+    fn capacity(&self) -> u64 {
+        (self.len - HEADER_SIZE) as u64
+    }
+
+    /// Copies data out of the ring starting at given stream position.
+    fn copy_out(&self, pos: u64, buf: &mut [u8]) {
+        let offset = (pos % self.capacity()) as usize;
+        let first = buf.len().min(self.len - HEADER_SIZE - offset);
+        unsafe {
+            let data = self.ptr.add(HEADER_SIZE);
+            std::ptr::copy_nonoverlapping(data.add(offset), buf.as_mut_ptr(), first);
+            std::ptr::copy_nonoverlapping(data, buf.as_mut_ptr().add(first), buf.len() - first);
+        }
+    }
+
+    /// Copies data into the ring starting at given stream position.
+    fn copy_in(&self, pos: u64, buf: &[u8]) {
+        let offset = (pos % self.capacity()) as usize;
+        let first = buf.len().min(self.len - HEADER_SIZE - offset);
+        unsafe {
+            let data = self.ptr.add(HEADER_SIZE);
+            std::ptr::copy_nonoverlapping(buf.as_ptr(), data.add(offset), first);
+            std::ptr::copy_nonoverlapping(buf.as_ptr().add(first), data, buf.len() - first);
+        }
+    }
+
+    fn store_error(&self, err: &IoError) {
+        let header = self.header();
+        let message = err.to_string();
+        let mut len = message.len().min(MAX_MESSAGE);
+        while !message.is_char_boundary(len) {
+            len -= 1;
+        }
+        for (dst, src) in header.error_message.iter().zip(&message.as_bytes()[..len]) {
+            dst.store(*src, Ordering::Relaxed);
+        }
+        header.error_kind.store(kind_to_index(err.kind()), Ordering::Relaxed);
+        header.error_len.store(len as u32, Ordering::Relaxed);
+    }
+
+    fn load_error(&self) -> IoError {
+        let header = self.header();
+        let kind = kind_from_index(header.error_kind.load(Ordering::Relaxed));
+        let len = (header.error_len.load(Ordering::Relaxed) as usize).min(MAX_MESSAGE);
+        let message: Vec<u8> = header.error_message[..len].iter().map(|byte| byte.load(Ordering::Relaxed)).collect();
+        IoError::new(kind, String::from_utf8_lossy(&message).into_owned())
+    }
+}
+
+impl Drop for Region {
+    fn drop(&mut self) {
+        unsafe {
+            libc::munmap(self.ptr as *mut libc::c_void, self.len);
+        }
+    }
+}
+
+#[derive(Debug, Default)]
+struct Backoff(u32);
+
+impl Backoff {
+    fn wait(&mut self) {
+        if self.0 < 64 {
+            thread::yield_now();
+        } else {
+            thread::sleep(Duration::from_millis(1));
+        }
+        self.0 = self.0.saturating_add(1);
+    }
+}
+
+/// Creates pipe with given capacity in bytes at `path` returning its fused reader.
+///
+/// Existing file at `path` is replaced. Use a file on memory backed file system (e.g. `/dev/shm`)
+/// to avoid disk I/O.
+///
+/// Panics if `capacity` is zero.
+pub fn create(path: impl AsRef<Path>, capacity: usize) -> Result<FusedReader<ShmReader>, IoError> {
+    assert!(capacity > 0, "pipe capacity must be greater than zero");
+    let file = OpenOptions::new().read(true).write(true).create(true).truncate(true).open(path)?;
+    file.set_len((HEADER_SIZE + capacity) as u64)?;
+    let region = Region::map(&file, HEADER_SIZE + capacity)?;
+    region.header().capacity.store(capacity as u64, Ordering::Relaxed);
+    region.header().magic.store(MAGIC, Ordering::Release);
+
+    let (mut reader, fuse) = fuse(ShmReader {
+        region,
+        fuse: None,
+        heartbeat: None,
+        heartbeat_timeout: DEFAULT_HEARTBEAT_TIMEOUT,
+        done: false,
+    });
+    reader.reader.fuse = Some(fuse);
+    Ok(reader)
+}
+
+/// Attaches writer to the pipe created at `path` arming its fuse.
+///
+/// Fails with `InvalidData` error if the file is not a pipe and `AlreadyExists` error if the pipe
+/// already has a writer attached.
+pub fn open(path: impl AsRef<Path>) -> Result<ShmWriter, IoError> {
+    let file = OpenOptions::new().read(true).write(true).open(path)?;
+    let len = file.metadata()?.len() as usize;
+    if len <= HEADER_SIZE {
+        return Err(IoError::new(ErrorKind::InvalidData, "not a shared memory pipe"))
+    }
+    let region = Region::map(&file, len)?;
+    let header = region.header();
+    if header.magic.load(Ordering::Acquire) != MAGIC || header.capacity.load(Ordering::Relaxed) != region.capacity() {
+        return Err(IoError::new(ErrorKind::InvalidData, "not a shared memory pipe"))
+    }
+    if header.state.compare_exchange(WAITING, ARMED, Ordering::AcqRel, Ordering::Acquire).is_err() {
+        return Err(IoError::new(ErrorKind::AlreadyExists, "shared memory pipe already has a writer"))
+    }
+
+    let region = Arc::new(region);
+    let stop = Arc::new(AtomicBool::new(false));
+    {
+        let region = region.clone();
+        let stop = stop.clone();
+        thread::Builder::new().name("fused-shm-heartbeat".into()).spawn(move || {
+            while !stop.load(Ordering::Relaxed) {
+                region.header().heartbeat.fetch_add(1, Ordering::Relaxed);
+                thread::sleep(HEARTBEAT_INTERVAL);
+            }
+        })?;
+    }
+
+    Ok(ShmWriter { region, stop })
+}
+
+/// Reading end of the shared memory pipe.
+#[derive(Debug)]
+pub struct ShmReader {
+    region: Region,
+    fuse: Option<Fuse>,
+    heartbeat: Option<(u64, Instant)>,
+    heartbeat_timeout: Duration,
+    done: bool,
+}
+
+impl ShmReader {
+    /// Sets time after which the writer that stopped sending heartbeats is considered dead.
+    pub fn set_heartbeat_timeout(&mut self, timeout: Duration) {
+        self.heartbeat_timeout = timeout;
+    }
+
+    /// Returns `true` if the writer is alive, tracking its heartbeat.
+    fn writer_alive(&mut self) -> bool {
+        let beat = self.region.header().heartbeat.load(Ordering::Relaxed);
+        match self.heartbeat {
+            Some((last, since)) if last == beat => since.elapsed() < self.heartbeat_timeout,
+            _ => {
+                self.heartbeat = Some((beat, Instant::now()));
+                true
+            }
+        }
+    }
+
+    /// Ends the stream blowing local fuse with given error so that the fused reader gets it at EOF.
+    fn finish(&mut self, err: Option<IoError>) {
+        self.done = true;
+        if let (Some(err), Some(fuse)) = (err, self.fuse.take()) {
+            if let Ok(mut guard) = fuse.arm() {
+                guard.result = Err(err);
+            }
+        }
+    }
+}
+
+impl Read for ShmReader {
+    fn read(&mut self, buf: &mut [u8]) -> Result<usize, IoError> {
+        if buf.is_empty() || self.done {
+            return Ok(0)
+        }
+
+        let mut backoff = Backoff::default();
+        loop {
+            let header = self.region.header();
+            let state = header.state.load(Ordering::Acquire);
+            let read = header.read.load(Ordering::Relaxed);
+            let available = header.written.load(Ordering::Acquire) - read;
+            if available > 0 {
+                let bytes = buf.len().min(available as usize);
+                self.region.copy_out(read, &mut buf[..bytes]);
+                header.read.store(read + bytes as u64, Ordering::Release);
+                return Ok(bytes)
+            }
+
+            match state {
+                CLOSED => self.finish(None),
+                BLOWN => {
+                    let err = FuseError::explicit(self.region.load_error());
+                    self.finish(Some(err))
+                }
+                POISONED => self.finish(Some(FuseError::new_io(ErrorKind::BrokenPipe, BlowReason::Panic { msg: None, location: None }, "writer process panicked"))),
+                ARMED if !self.writer_alive() => {
+                    self.finish(Some(FuseError::new_io(ErrorKind::BrokenPipe, BlowReason::Panic { msg: None, location: None }, "writer process stopped sending heartbeats")))
+                }
+                _ => {
+                    backoff.wait();
+                    continue
+                }
+            }
+            return Ok(0)
+        }
+    }
+}
+
+impl Drop for ShmReader {
+    fn drop(&mut self) {
+        self.region.header().reader_closed.store(1, Ordering::Release);
+    }
+}
+
+/// Writing end of the shared memory pipe holding its fuse armed.
+///
+/// Reader gets EOF once the writer was dropped and all data was consumed or `BrokenPipe` error if
+/// it was dropped due to panic. Writes block while the pipe is full and fail with `BrokenPipe` error
+/// once the reader was dropped.
+#[derive(Debug)]
+pub struct ShmWriter {
+    region: Arc<Region>,
+    stop: Arc<AtomicBool>,
+}
+
+impl ShmWriter {
+    /// Blows the fuse with given error.
+    ///
+    /// The reader end will fail with error of the same kind and message after reaching EOF.
+    /// Messages longer than 256 bytes are truncated.
+    pub fn blow(self, err: IoError) {
+        self.region.store_error(&err);
+        let _ = self.region.header().state.compare_exchange(ARMED, BLOWN, Ordering::AcqRel, Ordering::Acquire);
+    }
+}
+
+impl Write for ShmWriter {
+    fn write(&mut self, buf: &[u8]) -> Result<usize, IoError> {
+        if buf.is_empty() {
+            return Ok(0)
+        }
+
+        let header = self.region.header();
+        let capacity = self.region.capacity();
+        let mut backoff = Backoff::default();
+        loop {
+            if header.reader_closed.load(Ordering::Acquire) != 0 {
+                return Err(IoError::new(ErrorKind::BrokenPipe, "pipe reader was dropped"))
+            }
+            let written = header.written.load(Ordering::Relaxed);
+            let free = capacity - (written - header.read.load(Ordering::Acquire));
+            if free > 0 {
+                let bytes = buf.len().min(free as usize);
+                self.region.copy_in(written, &buf[..bytes]);
+                header.written.store(written + bytes as u64, Ordering::Release);
+                return Ok(bytes)
+            }
+            backoff.wait();
+        }
+    }
+
+    fn flush(&mut self) -> Result<(), IoError> {
+        Ok(())
+    }
+}
+
+impl Drop for ShmWriter {
+    fn drop(&mut self) {
+        self.stop.store(true, Ordering::Relaxed);
+        let state = if thread::panicking() { POISONED } else { CLOSED };
+        let _ = self.region.header().state.compare_exchange(ARMED, state, Ordering::AcqRel, Ordering::Acquire);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{child_process_command, child_process_test};
+    use std::path::PathBuf;
+
+    const CHILD_PATH: &str = "FUSED_READER_SHM_CHILD_PATH";
+
+    fn temp_path(name: &str) -> PathBuf {
+        std::env::temp_dir().join(format!("fused-reader-{}-{}", name, std::process::id()))
+    }
+
+    #[test]
+    fn test_shm_pipe() {
+        let path = temp_path("shm-pipe");
+        let mut reader = create(&path, 7).unwrap();
+        let mut writer = open(&path).unwrap();
+        assert_eq!(open(&path).unwrap_err().kind(), ErrorKind::AlreadyExists);
+
+        let writer = thread::spawn(move || {
+            writer.write_all(&[1; 100]).unwrap();
+        });
+
+        let mut data = Vec::new();
+        reader.read_to_end(&mut data).unwrap();
+        assert_eq!(data, vec![1; 100]);
+        writer.join().unwrap();
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn test_shm_pipe_blow() {
+        let path = temp_path("shm-pipe-blow");
+        let mut reader = create(&path, 16).unwrap();
+        let mut writer = open(&path).unwrap();
+
+        writer.write_all(&[1]).unwrap();
+        writer.blow(IoError::new(ErrorKind::UnexpectedEof, "uh! oh!"));
+
+        let mut data = Vec::new();
+        let err = reader.read_to_end(&mut data).unwrap_err();
+        assert_eq!(err.kind(), ErrorKind::UnexpectedEof);
+        assert_eq!(err.to_string(), "uh! oh!");
+        assert_eq!(&data, &[1]);
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn test_shm_pipe_panic() {
+        let path = temp_path("shm-pipe-panic");
+        let mut reader = create(&path, 16).unwrap();
+        let writer_path = path.clone();
+
+        thread::spawn(move || {
+            let _writer = open(&writer_path).unwrap();
+            panic!("boom");
+        });
+
+        let mut data = Vec::new();
+        assert_eq!(reader.read_to_end(&mut data).unwrap_err().kind(), ErrorKind::BrokenPipe);
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn test_shm_pipe_writer_crash() {
+        let path = temp_path("shm-pipe-crash");
+        let mut reader = create(&path, 16).unwrap();
+        reader.reader.set_heartbeat_timeout(Duration::from_millis(300));
+
+        let status = child_process_command("shm_pipe::tests::shm_pipe_crashing_writer", CHILD_PATH, &path)
+            .status()
+            .unwrap();
+        assert!(!status.success());
+
+        let mut data = Vec::new();
+        assert_eq!(reader.read_to_end(&mut data).unwrap_err().kind(), ErrorKind::BrokenPipe);
+        assert_eq!(&data, &[1, 2, 3]);
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    // run in child process by `test_shm_pipe_writer_crash`
+    #[test]
+    #[ignore]
+    fn shm_pipe_crashing_writer() {
+        let path = match child_process_test(CHILD_PATH) {
+            Some(path) => path,
+            None => return,
+        };
+        let mut writer = open(path).unwrap();
+        writer.write_all(&[1, 2, 3]).unwrap();
+        std::process::abort();
+    }
+}
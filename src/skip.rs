@@ -0,0 +1,65 @@
+use std::io::{self, Read, Seek, SeekFrom, Error as IoError};
+
+use crate::FusedReader;
+
+impl<R: Read> FusedReader<R> {
+    /// Reads and discards up to `n` bytes returning number of bytes skipped.
+    ///
+    /// Returns less than `n` if the stream ended first and fails with the fuse error if it ended
+    /// because the fuse was blown.
+    pub fn skip(&mut self, n: u64) -> Result<u64, IoError> {
+        io::copy(&mut self.by_ref().take(n), &mut io::sink())
+    }
+}
+
+impl<R: Read + Seek> FusedReader<R> {
+    /// Skips up to `n` bytes by seeking instead of reading them.
+    ///
+    /// Works like `skip` but the inner reader needs to know where the stream ends.
+    pub fn skip_seek(&mut self, n: u64) -> Result<u64, IoError> {
+        let current = self.reader.stream_position()?;
+        let end = self.reader.seek(SeekFrom::End(0))?;
+        let target = current.saturating_add(n).min(end.max(current));
+        self.reader.seek(SeekFrom::Start(target))?;
+
+        let skipped = target - current;
+        self.advance(skipped);
+        if skipped < n {
+            if let Some(err) = self.eof_error() {
+                return Err(self.fuse_failed(err))
+            }
+        }
+        Ok(skipped)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::fuse;
+    use std::io::{Cursor, Error as IoError, ErrorKind, Read};
+
+    #[test]
+    fn test_skip() {
+        let (mut reader, fuse) = fuse(Cursor::new(vec![1, 2, 3, 4]));
+        fuse.arm().unwrap().blow(IoError::new(ErrorKind::InvalidData, "uh! oh!"));
+
+        assert_eq!(reader.skip(2).unwrap(), 2);
+        assert_eq!(reader.position(), 2);
+        let mut buf = [0; 1];
+        reader.read_exact(&mut buf).unwrap();
+        assert_eq!(buf, [3]);
+        assert_eq!(reader.skip(3).unwrap_err().kind(), ErrorKind::InvalidData);
+    }
+
+    #[test]
+    fn test_skip_seek() {
+        let (mut reader, fuse) = fuse(Cursor::new(vec![1, 2, 3, 4]));
+
+        assert_eq!(reader.skip_seek(3).unwrap(), 3);
+        assert_eq!(reader.position(), 3);
+        assert_eq!(reader.skip_seek(3).unwrap(), 1);
+
+        fuse.arm().unwrap().blow(IoError::new(ErrorKind::InvalidData, "uh! oh!"));
+        assert_eq!(reader.skip_seek(1).unwrap_err().kind(), ErrorKind::InvalidData);
+    }
+}
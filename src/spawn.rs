@@ -0,0 +1,50 @@
+use std::future::Future;
+use std::io::Error as IoError;
+
+use async_std::task::{self, JoinHandle};
+
+use crate::AsyncFuse;
+
+/// Spawns async-std task running `future` with the fuse armed.
+///
+/// The fuse is disarmed if the future completes with `Ok`, blown with the error if it completes
+/// with `Err`, and the reader end will fail with `BrokenPipe` error if the task panics or is
+/// cancelled.
+pub fn spawn_fused<F>(fuse: AsyncFuse, future: F) -> JoinHandle<()>
+    where F: Future<Output = Result<(), IoError>> + Send + 'static {
+    task::spawn(async move {
+        let guard = fuse.arm();
+        match future.await {
+            Ok(()) => guard.disarm(),
+            Err(err) => guard.blow(err),
+        }
+    })
+}
+
+#[cfg(all(test, unix))]
+mod tests {
+    use super::*;
+    use crate::fuse_async_read;
+    use async_std::io::{ReadExt, WriteExt};
+    use async_std::os::unix::net::UnixStream;
+    use std::io::ErrorKind;
+
+    #[test]
+    fn test_spawn_fused() {
+        task::block_on(async {
+            let (input, output) = UnixStream::pair().unwrap();
+            let (mut reader, fuse) = fuse_async_read(input);
+
+            let mut writer = output.clone();
+            spawn_fused(fuse, async move {
+                writer.write_all(&[1, 2]).await?;
+                Err(IoError::new(ErrorKind::InvalidData, "uh! oh!"))
+            }).await;
+            drop(output);
+
+            let mut data = Vec::new();
+            assert_eq!(reader.read_to_end(&mut data).await.unwrap_err().kind(), ErrorKind::InvalidData);
+            assert_eq!(data, vec![1, 2]);
+        });
+    }
+}
@@ -0,0 +1,169 @@
+use std::io::{Read, Write, Error as IoError, ErrorKind};
+use std::os::unix::io::{AsRawFd, RawFd};
+
+use crate::FusedReader;
+
+const CHUNK_SIZE: usize = 64 * 1024;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Method {
+    Splice,
+    Sendfile,
+    Generic,
+}
+
+/// Copies all data from fd-backed fused reader to fd-backed writer moving it in-kernel.
+///
+/// Uses `splice` if either end is an OS pipe and `sendfile` if the reader is a regular file,
+/// falling back to copying through a buffer with `read` and `write` otherwise. The fuse is checked
+/// before copying, like with `read`, and after reaching EOF; the fuse error is returned if it was
+/// blown.
+///
+/// Both file descriptors need to be in blocking mode.
+pub fn fused_copy_fd<R, W>(reader: &mut FusedReader<R>, writer: &mut W) -> Result<u64, IoError>
+    where R: Read + AsRawFd, W: Write + AsRawFd + ?Sized {
+    reader.check_read()?;
+    let input = reader.reader.as_raw_fd();
+    let output = writer.as_raw_fd();
+    let mut method = Method::Splice;
+    let mut buf = Vec::new();
+    let mut copied = 0;
+
+    loop {
+        let res = match method {
+            Method::Splice => splice(input, output),
+            Method::Sendfile => sendfile(input, output),
+            Method::Generic => {
+                buf.resize(CHUNK_SIZE, 0);
+                match reader.reader.read(&mut buf) {
+                    Ok(0) => Ok(0),
+                    Ok(bytes) => writer.write_all(&buf[..bytes]).map(|()| bytes),
+                    Err(err) => Err(err),
+                }
+            }
+        };
+
+        match res {
+            Ok(0) => break,
+            Ok(bytes) => {
+                copied += bytes as u64;
+                reader.advance(bytes as u64);
+            }
+            Err(err) if err.kind() == ErrorKind::Interrupted => (),
+            Err(err) if unsupported(&err) && method == Method::Splice => method = Method::Sendfile,
+            Err(err) if unsupported(&err) && method == Method::Sendfile => method = Method::Generic,
+            Err(err) => return Err(err),
+        }
+    }
+
+    reader.finish_read(0).map(|_| copied)
+}
+
+fn unsupported(err: &IoError) -> bool {
+    matches!(err.raw_os_error(), Some(libc::EINVAL) | Some(libc::ENOSYS) | Some(libc::EOPNOTSUPP))
+}
+
+fn splice(input: RawFd, output: RawFd) -> Result<usize, IoError> {
+    let res = unsafe {
+        libc::splice(input, std::ptr::null_mut(), output, std::ptr::null_mut(), CHUNK_SIZE, libc::SPLICE_F_MOVE)
+    };
+    if res < 0 {
+        return Err(IoError::last_os_error())
+    }
+    Ok(res as usize)
+}
+
+fn sendfile(input: RawFd, output: RawFd) -> Result<usize, IoError> {
+    let res = unsafe { libc::sendfile(output, input, std::ptr::null_mut(), CHUNK_SIZE) };
+    if res < 0 {
+        return Err(IoError::last_os_error())
+    }
+    Ok(res as usize)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::fuse;
+    use std::io::Seek;
+    use std::os::unix::net::UnixStream;
+    use std::thread;
+
+    #[test]
+    fn test_fused_copy_fd_splice() {
+        let (reader, mut writer) = std::io::pipe().unwrap();
+        let (mut reader, fuse) = fuse(reader);
+        let (mut output, mut sink) = std::io::pipe().unwrap();
+
+        thread::spawn(move || {
+            let _fuse = fuse.arm().unwrap();
+            writer.write_all(&[1; 100]).unwrap();
+            panic!("boom");
+        });
+        let collector = thread::spawn(move || {
+            let mut data = Vec::new();
+            output.read_to_end(&mut data).unwrap();
+            data
+        });
+
+        assert_eq!(fused_copy_fd(&mut reader, &mut sink).unwrap_err().kind(), ErrorKind::BrokenPipe);
+        assert_eq!(reader.position(), 100);
+        drop(sink);
+        assert_eq!(collector.join().unwrap(), vec![1; 100]);
+    }
+
+    #[test]
+    fn test_fused_copy_fd_fallback() {
+        let (input, mut writer) = UnixStream::pair().unwrap();
+        let (mut reader, fuse) = fuse(input);
+        let (mut output, mut sink) = UnixStream::pair().unwrap();
+
+        thread::spawn(move || {
+            let _fuse = fuse.arm().unwrap();
+            writer.write_all(&[1; 100]).unwrap();
+        });
+        let collector = thread::spawn(move || {
+            let mut data = Vec::new();
+            output.read_to_end(&mut data).unwrap();
+            data
+        });
+
+        assert_eq!(fused_copy_fd(&mut reader, &mut sink).unwrap(), 100);
+        drop(sink);
+        assert_eq!(collector.join().unwrap(), vec![1; 100]);
+    }
+
+    #[test]
+    fn test_fused_copy_fd_incomplete() {
+        let (input, mut writer) = UnixStream::pair().unwrap();
+        let (mut reader, fuse) = fuse(input);
+        reader.set_require_complete(true);
+        let (_output, mut sink) = UnixStream::pair().unwrap();
+
+        writer.write_all(&[1; 10]).unwrap();
+        drop(writer);
+        drop(fuse);
+
+        assert_eq!(fused_copy_fd(&mut reader, &mut sink).unwrap_err().kind(), ErrorKind::UnexpectedEof);
+        assert_eq!(reader.blow_reason(), Some(&crate::BlowReason::Incomplete));
+        assert_eq!(reader.position(), 10);
+    }
+
+    #[test]
+    fn test_fused_copy_fd_sendfile() {
+        let path = std::env::temp_dir().join(format!("fused-reader-sendfile-{}", std::process::id()));
+        let mut file = std::fs::OpenOptions::new().read(true).write(true).create(true).truncate(true).open(&path).unwrap();
+        std::fs::remove_file(&path).unwrap();
+        file.write_all(&[1; 100]).unwrap();
+        file.rewind().unwrap();
+
+        let (mut reader, _fuse) = fuse(file);
+        let (mut output, mut sink) = UnixStream::pair().unwrap();
+
+        assert_eq!(fused_copy_fd(&mut reader, &mut sink).unwrap(), 100);
+        drop(sink);
+        let mut data = Vec::new();
+        output.read_to_end(&mut data).unwrap();
+        assert_eq!(data, vec![1; 100]);
+    }
+}
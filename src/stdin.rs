@@ -0,0 +1,172 @@
+use std::cell::Cell;
+use std::io::{self, Read, Error as IoError, ErrorKind};
+use std::sync::mpsc::{sync_channel, Receiver, RecvTimeoutError};
+use std::thread;
+use std::time::Duration;
+
+use crate::{fuse, FusedReader, ReadTimeout};
+
+const CHUNK_SIZE: usize = 8 * 1024;
+const CHUNKS_IN_FLIGHT: usize = 16;
+
+/// Spawns a thread feeding process standard input to returned fused reader.
+///
+/// Reader gets EOF when stdin is closed, error if reading stdin failed and `BrokenPipe` error if
+/// the feeding thread died. Returned reader supports `ReadTimeout`.
+pub fn fused_stdin() -> FusedReader<StdinReader> {
+    spawn_feeder(io::stdin())
+}
+
+fn spawn_feeder<S: Read + Send + 'static>(mut source: S) -> FusedReader<StdinReader> {
+    let (sender, receiver) = sync_channel(CHUNKS_IN_FLIGHT);
+    let (reader, fuse) = fuse(StdinReader {
+        receiver,
+        chunk: Vec::new(),
+        pos: 0,
+        timeout: Cell::new(None),
+    });
+
+    thread::Builder::new().name("fused-stdin".into()).spawn(move || {
+        let fuse = match fuse.arm() {
+            Ok(fuse) => fuse,
+            Err(_) => return,
+        };
+        loop {
+            let mut chunk = vec![0; CHUNK_SIZE];
+            match source.read(&mut chunk) {
+                Ok(0) => return,
+                Ok(bytes) => {
+                    chunk.truncate(bytes);
+                    if sender.send(chunk).is_err() {
+                        // reader is gone
+                        return
+                    }
+                }
+                Err(err) if err.kind() == ErrorKind::Interrupted => (),
+                Err(err) => return fuse.blow(err),
+            }
+        }
+    }).expect("failed to spawn stdin feeding thread");
+
+    reader
+}
+
+/// Reader end of the standard input feeding thread.
+#[derive(Debug)]
+pub struct StdinReader {
+    receiver: Receiver<Vec<u8>>,
+    chunk: Vec<u8>,
+    pos: usize,
+    timeout: Cell<Option<Duration>>,
+}
+
+impl Read for StdinReader {
+    fn read(&mut self, buf: &mut [u8]) -> Result<usize, IoError> {
+        if self.pos == self.chunk.len() {
+            let chunk = match self.timeout.get() {
+                Some(timeout) => match self.receiver.recv_timeout(timeout) {
+                    Ok(chunk) => chunk,
+                    Err(RecvTimeoutError::Timeout) => return Err(IoError::new(ErrorKind::TimedOut, "timed out waiting on stdin")),
+                    Err(RecvTimeoutError::Disconnected) => return Ok(0),
+                },
+                None => match self.receiver.recv() {
+                    Ok(chunk) => chunk,
+                    Err(_) => return Ok(0),
+                },
+            };
+            self.chunk = chunk;
+            self.pos = 0;
+        }
+
+        let bytes = buf.len().min(self.chunk.len() - self.pos);
+        buf[..bytes].copy_from_slice(&self.chunk[self.pos..self.pos + bytes]);
+        self.pos += bytes;
+        Ok(bytes)
+    }
+}
+
+impl ReadTimeout for StdinReader {
+    fn read_timeout(&self) -> Result<Option<Duration>, IoError> {
+        Ok(self.timeout.get())
+    }
+
+    fn set_read_timeout(&self, timeout: Option<Duration>) -> Result<(), IoError> {
+        if timeout == Some(Duration::from_secs(0)) {
+            return Err(IoError::new(ErrorKind::InvalidInput, "cannot set a 0 duration timeout"))
+        }
+        self.timeout.set(timeout);
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Cursor;
+    use std::time::Instant;
+
+    struct FailingReader(Option<IoError>);
+
+    impl Read for FailingReader {
+        fn read(&mut self, buf: &mut [u8]) -> Result<usize, IoError> {
+            match self.0.take() {
+                Some(err) => Err(err),
+                None => {
+                    buf[0] = 1;
+                    Ok(1)
+                }
+            }
+        }
+    }
+
+    struct PanickingReader;
+
+    impl Read for PanickingReader {
+        fn read(&mut self, _buf: &mut [u8]) -> Result<usize, IoError> {
+            panic!("boom")
+        }
+    }
+
+    struct BlockingReader;
+
+    impl Read for BlockingReader {
+        fn read(&mut self, _buf: &mut [u8]) -> Result<usize, IoError> {
+            thread::sleep(Duration::from_secs(3600));
+            Ok(0)
+        }
+    }
+
+    #[test]
+    fn test_feeder_eof() {
+        let mut reader = spawn_feeder(Cursor::new(vec![1, 2, 3]));
+
+        let mut data = Vec::new();
+        reader.read_to_end(&mut data).unwrap();
+        assert_eq!(&data, &[1, 2, 3]);
+    }
+
+    #[test]
+    fn test_feeder_error() {
+        let mut reader = spawn_feeder(FailingReader(Some(IoError::new(ErrorKind::InvalidData, "uh! oh!"))));
+
+        let mut data = Vec::new();
+        assert_eq!(reader.read_to_end(&mut data).unwrap_err().kind(), ErrorKind::InvalidData);
+    }
+
+    #[test]
+    fn test_feeder_panic() {
+        let mut reader = spawn_feeder(PanickingReader);
+
+        let mut data = Vec::new();
+        assert_eq!(reader.read_to_end(&mut data).unwrap_err().kind(), ErrorKind::BrokenPipe);
+    }
+
+    #[test]
+    fn test_feeder_timeout() {
+        let mut reader = spawn_feeder(BlockingReader);
+
+        let mut buf = [0; 1];
+        let err = reader.read_exact_deadline(&mut buf, Instant::now() + Duration::from_millis(50)).unwrap_err();
+        assert_eq!(err.kind(), ErrorKind::TimedOut);
+    }
+}
@@ -0,0 +1,172 @@
+use std::io::Error as IoError;
+use std::pin::Pin;
+use std::task::{Context, Poll};
+
+use futures_core::stream::Stream;
+
+use crate::async_fuse::{AsyncFuse, AsyncFuseState, FuseCell, LocalState, SyncState};
+use crate::FuseStatus;
+
+/// Fuses stream so that if the task driving it dies while holding armed fuse the stream will yield final `BrokenPipe` error.
+pub fn fuse_stream<S>(stream: S) -> (FusedStream<S>, AsyncFuse) {
+    fuse_stream_with(stream)
+}
+
+/// Like `fuse_stream` but the fused stream and the fuse are not `Send`; see `LocalState`.
+pub fn fuse_stream_local<S>(stream: S) -> (FusedStream<S, LocalState>, AsyncFuse<LocalState>) {
+    fuse_stream_with(stream)
+}
+
+fn fuse_stream_with<S, C: FuseCell>(stream: S) -> (FusedStream<S, C>, AsyncFuse<C>) {
+    let state = AsyncFuseState::new();
+    (FusedStream {
+            stream,
+            fuse: state.clone(),
+            done: false,
+        },
+        AsyncFuse(state),
+    )
+}
+
+/// Stream that will yield final error if fuse was blown.
+#[derive(Debug)]
+pub struct FusedStream<S, C: FuseCell = SyncState> {
+    stream: S,
+    fuse: AsyncFuseState<C>,
+    done: bool,
+}
+
+impl<S, C: FuseCell> FusedStream<S, C> {
+    /// Checks status of the fuse.
+    ///
+    /// Once blown the fuse is reported as `FuseStatus::Blown` with a copy of its error until the
+    /// error is taken with `take_error`.
+    pub fn check_fuse(&self) -> FuseStatus {
+        self.fuse.check_fuse()
+    }
+
+    /// Takes the error the fuse was blown with, if any.
+    ///
+    /// The stream no longer yields the error at the end of the stream afterwards.
+    pub fn take_error(&mut self) -> Option<IoError> {
+        self.fuse.take_error()
+    }
+
+    /// Returns inner stream.
+    pub fn into_inner(self) -> S {
+        self.stream
+    }
+}
+
+impl<S, C, T, E> Stream for FusedStream<S, C> where S: Stream<Item = Result<T, E>>, C: FuseCell, E: From<IoError> {
+    type Item = Result<T, E>;
+
+    fn poll_next(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        // Safety: `stream` is structurally pinned and never moved out of pinned `self`
+        let this = unsafe { self.get_unchecked_mut() };
+        if this.done {
+            return Poll::Ready(None)
+        }
+        let stream = unsafe { Pin::new_unchecked(&mut this.stream) };
+
+        // let it stream to end before checking fuse
+        match stream.poll_next(cx) {
+            Poll::Ready(None) => {
+                this.done = true;
+                Poll::Ready(this.fuse.eof_error().map(|err| Err(err.into())))
+            }
+            other => other,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use futures::executor::block_on;
+    use futures::FutureExt;
+    use futures::stream::{self, StreamExt};
+    use futures::channel::mpsc;
+    use std::io::ErrorKind;
+    use std::thread;
+
+    #[test]
+    fn test_fused_stream_complete() {
+        let (tx, rx) = mpsc::unbounded::<Result<u8, IoError>>();
+        let (stream, fuse) = fuse_stream(rx);
+
+        thread::spawn(move || {
+            let fuse = fuse.arm();
+            tx.unbounded_send(Ok(1)).unwrap();
+            fuse.disarm();
+        });
+
+        let items = block_on(stream.collect::<Vec<_>>());
+        assert_eq!(items.len(), 1);
+        assert_eq!(items[0].as_ref().unwrap(), &1);
+    }
+
+    #[test]
+    fn test_fused_stream_panic() {
+        let (tx, rx) = mpsc::unbounded::<Result<u8, IoError>>();
+        let (stream, fuse) = fuse_stream(rx);
+
+        thread::spawn(move || {
+            let _fuse = fuse.arm();
+            tx.unbounded_send(Ok(1)).unwrap();
+            panic!("boom");
+        });
+
+        let items = block_on(stream.collect::<Vec<_>>());
+        assert_eq!(items.len(), 2);
+        assert_eq!(items[0].as_ref().unwrap(), &1);
+        assert_eq!(items[1].as_ref().unwrap_err().kind(), ErrorKind::BrokenPipe);
+    }
+
+    #[test]
+    fn test_fused_stream_cancelled() {
+        let (stream, fuse) = fuse_stream(stream::iter(vec![Ok::<u8, IoError>(1)]));
+
+        let task = async move {
+            let _fuse = fuse.arm();
+            futures::future::pending::<()>().await;
+        };
+        // task gets dropped before completing
+        assert!(task.now_or_never().is_none());
+
+        let items = block_on(stream.collect::<Vec<_>>());
+        assert_eq!(items.len(), 2);
+        assert_eq!(items[1].as_ref().unwrap_err().kind(), ErrorKind::BrokenPipe);
+    }
+
+    #[test]
+    fn test_fused_stream_blow() {
+        let (tx, rx) = mpsc::unbounded::<Result<u8, IoError>>();
+        let (stream, fuse) = fuse_stream(rx);
+
+        thread::spawn(move || {
+            let fuse = fuse.arm();
+            tx.unbounded_send(Ok(1)).unwrap();
+            fuse.blow(IoError::new(ErrorKind::UnexpectedEof, "uh! oh!"))
+        });
+
+        let items = block_on(stream.collect::<Vec<_>>());
+        assert_eq!(items.len(), 2);
+        assert_eq!(items[1].as_ref().unwrap_err().kind(), ErrorKind::UnexpectedEof);
+    }
+
+    #[test]
+    fn test_fused_stream_local() {
+        let (stream, fuse) = fuse_stream_local(stream::iter(vec![Ok::<u8, IoError>(1)]));
+
+        let task = async move {
+            let _fuse = fuse.arm();
+            futures::future::pending::<()>().await;
+        };
+        assert!(task.now_or_never().is_none());
+
+        let items = block_on(stream.collect::<Vec<_>>());
+        assert_eq!(items.len(), 2);
+        assert_eq!(items[1].as_ref().unwrap_err().kind(), ErrorKind::BrokenPipe);
+    }
+}
@@ -0,0 +1,122 @@
+use std::io::{BufRead, BufReader, Read, Error as IoError, ErrorKind};
+
+use crate::FusedReader;
+
+/// Text mode wrapper of `FusedReader` reading UTF-8 validated lines.
+///
+/// Data is validated line by line as it is read; invalid UTF-8 fails the read with `InvalidData`
+/// error telling the byte offset of the invalid sequence in the stream. Fuse errors are delivered
+/// at the end of the stream like with the `FusedReader`.
+#[derive(Debug)]
+pub struct FusedTextReader<R: Read> {
+    reader: BufReader<FusedReader<R>>,
+    offset: u64,
+    line: Vec<u8>,
+    // delivered after the partial line read before it
+    error: Option<IoError>,
+}
+
+impl<R: Read> FusedTextReader<R> {
+    /// Wraps fused reader.
+    pub fn new(reader: FusedReader<R>) -> FusedTextReader<R> {
+        FusedTextReader {
+            reader: BufReader::new(reader),
+            offset: 0,
+            line: Vec::new(),
+            error: None,
+        }
+    }
+
+    /// Reads next line including the line terminator and appends it to `buf`.
+    ///
+    /// Returns number of bytes read; `0` at the end of the stream. Nothing is appended if the line
+    /// is not valid UTF-8; reading continues with the next line. If reading fails after part of the
+    /// line was read the part is returned first and the error by the next call.
+    pub fn read_line(&mut self, buf: &mut String) -> Result<usize, IoError> {
+        if let Some(err) = self.error.take() {
+            return Err(err)
+        }
+        self.line.clear();
+        if let Err(err) = self.reader.read_until(b'\n', &mut self.line) {
+            if self.line.is_empty() {
+                return Err(err)
+            }
+            self.error = Some(err);
+        }
+        let bytes = self.line.len();
+        let offset = self.offset;
+        self.offset += bytes as u64;
+        let line = std::str::from_utf8(&self.line).map_err(|err| {
+            let offset = offset + err.valid_up_to() as u64;
+            IoError::new(ErrorKind::InvalidData, format!("stream contains invalid UTF-8 sequence at byte offset {}", offset))
+        })?;
+        buf.push_str(line);
+        Ok(bytes)
+    }
+
+    /// Reads all remaining lines appending them to `buf`.
+    ///
+    /// Returns number of bytes read.
+    pub fn read_to_string(&mut self, buf: &mut String) -> Result<usize, IoError> {
+        let mut read = 0;
+        loop {
+            match self.read_line(buf)? {
+                0 => return Ok(read),
+                bytes => read += bytes,
+            }
+        }
+    }
+
+    /// Number of bytes consumed from the stream so far, including lines that failed validation.
+    pub fn offset(&self) -> u64 {
+        self.offset
+    }
+
+    /// Returns inner fused reader.
+    ///
+    /// Data buffered but not read yet is lost.
+    pub fn into_inner(self) -> FusedReader<R> {
+        self.reader.into_inner()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::fuse;
+    use std::io::{Cursor, Write};
+    use std::thread;
+
+    #[test]
+    fn test_fused_text_reader() {
+        let (reader, mut writer) = pipe::pipe();
+        let (reader, fuse) = fuse(reader);
+        let mut reader = FusedTextReader::new(reader);
+
+        thread::spawn(move || {
+            let fuse = fuse.arm().unwrap();
+            writer.write_all("zażółć\ngęślą".as_bytes()).unwrap();
+            fuse.blow(IoError::new(ErrorKind::UnexpectedEof, "uh! oh!"));
+        });
+
+        let mut line = String::new();
+        assert_eq!(reader.read_line(&mut line).unwrap(), 11);
+        assert_eq!(line, "zażółć\n");
+        assert_eq!(reader.read_to_string(&mut line).unwrap_err().kind(), ErrorKind::UnexpectedEof);
+        assert_eq!(line, "zażółć\ngęślą");
+        assert_eq!(reader.offset(), 19);
+    }
+
+    #[test]
+    fn test_fused_text_reader_invalid() {
+        let (reader, _fuse) = fuse(Cursor::new(b"ok\nbad \xff\n".to_vec()));
+        let mut reader = FusedTextReader::new(reader);
+
+        let mut text = String::new();
+        let err = reader.read_to_string(&mut text).unwrap_err();
+        assert_eq!(err.kind(), ErrorKind::InvalidData);
+        assert!(err.to_string().ends_with("byte offset 7"));
+        assert_eq!(text, "ok\n");
+        assert_eq!(reader.offset(), 9);
+    }
+}
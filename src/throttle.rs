@@ -0,0 +1,274 @@
+use std::io::{Read, Write, IoSlice, Error as IoError};
+use std::thread;
+use std::time::{Duration, Instant};
+
+use crate::{FuseGuard, FusedReader, FuseStatus};
+
+/// Longest sleep of `ThrottledWriter` between heartbeats.
+const HEARTBEAT_INTERVAL: Duration = Duration::from_millis(10);
+
+/// Paces transfer of bytes to given rate.
+#[derive(Debug)]
+pub(crate) struct Throttle {
+    bytes_per_sec: u64,
+    start: Instant,
+    bytes: u64,
+}
+
+impl Throttle {
+    pub(crate) fn new(bytes_per_sec: u64) -> Throttle {
+        assert!(bytes_per_sec > 0, "throttle rate must be greater than zero");
+        Throttle {
+            bytes_per_sec,
+            start: Instant::now(),
+            bytes: 0,
+        }
+    }
+
+    /// Maximum number of bytes that should be transferred at once (100ms worth of data).
+    pub(crate) fn max_chunk(&self) -> usize {
+        (self.bytes_per_sec / 10).max(1) as usize
+    }
+
+    /// Time to wait before transfer can continue.
+    pub(crate) fn delay(&self) -> Duration {
+        let due = Duration::from_secs_f64(self.bytes as f64 / self.bytes_per_sec as f64);
+        due.saturating_sub(self.start.elapsed())
+    }
+
+    pub(crate) fn transferred(&mut self, bytes: usize) {
+        self.bytes += bytes as u64;
+    }
+}
+
+impl<'a> FuseGuard<'a> {
+    /// Wraps writer so that it writes no faster than given rate, keeping the fuse armed.
+    ///
+    /// Writes sleep as needed to keep the average rate at `bytes_per_sec`, sending heartbeats while
+    /// sleeping so that reader with `FusedReader::with_timeout` does not take the writer as stalled.
+    ///
+    /// Panics if `bytes_per_sec` is zero.
+    pub fn wrap_writer_throttled<W: Write>(self, writer: W, bytes_per_sec: u64) -> ThrottledWriter<'a, W> {
+        ThrottledWriter {
+            guard: self,
+            writer,
+            throttle: Throttle::new(bytes_per_sec),
+        }
+    }
+}
+
+/// Rate limited writer holding armed fuse.
+#[derive(Debug)]
+pub struct ThrottledWriter<'a, W: Write> {
+    // guard needs to be dropped before the writer so that reader sees blown fuse at EOF
+    guard: FuseGuard<'a>,
+    writer: W,
+    throttle: Throttle,
+}
+
+impl<'a, W: Write> ThrottledWriter<'a, W> {
+    /// Returns inner writer and the armed fuse guard.
+    pub fn into_parts(self) -> (W, FuseGuard<'a>) {
+        (self.writer, self.guard)
+    }
+
+    fn pace(&self) {
+        loop {
+            let delay = self.throttle.delay();
+            if delay.is_zero() {
+                return
+            }
+            self.guard.heartbeat();
+            thread::sleep(delay.min(HEARTBEAT_INTERVAL));
+        }
+    }
+}
+
+impl<'a, W: Write> Write for ThrottledWriter<'a, W> {
+    fn write(&mut self, buf: &[u8]) -> Result<usize, IoError> {
+        self.pace();
+        let len = buf.len().min(self.throttle.max_chunk());
+        let bytes = self.writer.write(&buf[..len])?;
+        self.throttle.transferred(bytes);
+        Ok(bytes)
+    }
+
+    fn write_vectored(&mut self, bufs: &[IoSlice<'_>]) -> Result<usize, IoError> {
+        self.pace();
+        let mut remaining = self.throttle.max_chunk();
+        let bufs: Vec<IoSlice<'_>> = bufs.iter().map_while(|buf| {
+            if remaining == 0 {
+                return None
+            }
+            let len = buf.len().min(remaining);
+            remaining -= len;
+            Some(IoSlice::new(&buf[..len]))
+        }).collect();
+        let bytes = self.writer.write_vectored(&bufs)?;
+        self.throttle.transferred(bytes);
+        Ok(bytes)
+    }
+
+    fn flush(&mut self) -> Result<(), IoError> {
+        self.writer.flush()
+    }
+}
+
+impl<R: Read> FusedReader<R> {
+    /// Wraps reader so that it reads no faster than given rate.
+    ///
+    /// Data is read before the rate is enforced so EOF and fuse errors are delivered without delay.
+    ///
+    /// Panics if `bytes_per_sec` is zero.
+    pub fn throttle(self, bytes_per_sec: u64) -> ThrottledReader<R> {
+        ThrottledReader {
+            reader: self,
+            throttle: Throttle::new(bytes_per_sec),
+        }
+    }
+}
+
+/// Rate limited fused reader.
+#[derive(Debug)]
+pub struct ThrottledReader<R: Read> {
+    reader: FusedReader<R>,
+    throttle: Throttle,
+}
+
+impl<R: Read> ThrottledReader<R> {
+    /// Checks status of the fuse; see `FusedReader::check_fuse`.
+    pub fn check_fuse(&self) -> FuseStatus {
+        self.reader.check_fuse()
+    }
+
+    /// Returns inner fused reader.
+    pub fn into_inner(self) -> FusedReader<R> {
+        self.reader
+    }
+}
+
+impl<R: Read> Read for ThrottledReader<R> {
+    fn read(&mut self, buf: &mut [u8]) -> Result<usize, IoError> {
+        let len = buf.len().min(self.throttle.max_chunk());
+        let bytes = self.reader.read(&mut buf[..len])?;
+        // no delay once the stream ended
+        if bytes > 0 {
+            self.throttle.transferred(bytes);
+            thread::sleep(self.throttle.delay());
+        }
+        Ok(bytes)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::fuse;
+    use pipe::pipe;
+    use std::io::{Read, ErrorKind};
+
+    #[test]
+    fn test_throttled_writer() {
+        let (reader, writer) = pipe();
+        let (mut reader, fuse) = fuse(reader);
+
+        let start = Instant::now();
+        thread::spawn(move || {
+            let guard = fuse.arm().unwrap();
+            let mut writer = guard.wrap_writer_throttled(writer, 1000);
+            writer.write_all(&[1; 300]).unwrap();
+        });
+
+        let mut data = Vec::new();
+        reader.read_to_end(&mut data).unwrap();
+        assert_eq!(data.len(), 300);
+        assert!(start.elapsed() >= Duration::from_millis(200));
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_throttled_writer_with_timeout() {
+        let (reader, writer) = std::os::unix::net::UnixStream::pair().unwrap();
+        let (reader, fuse) = fuse(reader);
+        let mut reader = reader.with_timeout(Duration::from_millis(50)).unwrap();
+
+        thread::spawn(move || {
+            let guard = fuse.arm().unwrap();
+            // one byte every 100ms
+            let mut writer = guard.wrap_writer_throttled(writer, 10);
+            writer.write_all(&[1; 3]).unwrap();
+        });
+
+        let mut data = Vec::new();
+        reader.read_to_end(&mut data).unwrap();
+        assert_eq!(data.len(), 3);
+    }
+
+    #[test]
+    fn test_throttled_writer_vectored() {
+        let (mut reader, writer) = crate::ring_pipe::pipe();
+        let (_reader, fuse) = fuse(std::io::empty());
+
+        let guard = fuse.arm().unwrap();
+        let mut writer = guard.wrap_writer_throttled(writer, 20);
+        let bufs = [IoSlice::new(&[1]), IoSlice::new(&[2, 3, 4])];
+        assert_eq!(writer.write_vectored(&bufs).unwrap(), 2);
+        drop(writer);
+
+        let mut data = Vec::new();
+        reader.read_to_end(&mut data).unwrap();
+        assert_eq!(&data, &[1, 2]);
+    }
+
+    #[test]
+    fn test_throttled_writer_panic() {
+        let (reader, writer) = pipe();
+        let (mut reader, fuse) = fuse(reader);
+
+        thread::spawn(move || {
+            let guard = fuse.arm().unwrap();
+            let mut writer = guard.wrap_writer_throttled(writer, 1000);
+            writer.write_all(&[1; 10]).unwrap();
+            panic!("boom");
+        });
+
+        let mut data = Vec::new();
+        assert_eq!(reader.read_to_end(&mut data).unwrap_err().kind(), ErrorKind::BrokenPipe);
+    }
+
+    #[test]
+    fn test_throttled_reader() {
+        let (reader, mut writer) = pipe();
+        let (reader, fuse) = fuse(reader);
+        let mut reader = reader.throttle(1000);
+
+        thread::spawn(move || {
+            let _fuse = fuse.arm().unwrap();
+            writer.write_all(&[1; 300]).unwrap();
+        });
+
+        let start = Instant::now();
+        let mut data = Vec::new();
+        reader.read_to_end(&mut data).unwrap();
+        assert_eq!(data.len(), 300);
+        assert!(start.elapsed() >= Duration::from_millis(200));
+    }
+
+    #[test]
+    fn test_throttled_reader_blow() {
+        let (reader, writer) = pipe();
+        let (reader, fuse) = fuse(reader);
+        let mut reader = reader.throttle(1);
+
+        thread::spawn(move || {
+            let fuse = fuse.arm().unwrap();
+            fuse.blow(IoError::new(ErrorKind::UnexpectedEof, "uh! oh!"));
+            drop(writer);
+        });
+
+        let start = Instant::now();
+        let mut data = Vec::new();
+        assert_eq!(reader.read_to_end(&mut data).unwrap_err().kind(), ErrorKind::UnexpectedEof);
+        assert!(start.elapsed() < Duration::from_secs(1));
+    }
+}
@@ -0,0 +1,237 @@
+use std::convert::TryFrom;
+use std::io::{Read, Error as IoError, ErrorKind};
+
+use crate::{FuseError, FusedReader};
+
+const CHUNK_SIZE: usize = 8 * 1024;
+
+/// Legacy encodings `TranscodingReader` can convert to UTF-8.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Encoding {
+    /// UTF-16 little endian, as produced by many Windows tools.
+    Utf16Le,
+    /// ISO 8859-1.
+    Latin1,
+}
+
+impl Encoding {
+    /// Decodes complete characters from `input` appending them to `output` as UTF-8.
+    ///
+    /// Returns number of bytes decoded or offset of invalid sequence in `input`.
+    fn decode(self, input: &[u8], output: &mut Vec<u8>) -> Result<usize, usize> {
+        match self {
+            Encoding::Latin1 => {
+                for &byte in input {
+                    output.extend(char::from(byte).encode_utf8(&mut [0; 4]).as_bytes());
+                }
+                Ok(input.len())
+            }
+            Encoding::Utf16Le => {
+                let unit = |at: usize| u16::from_le_bytes([input[at], input[at + 1]]) as u32;
+                let mut decoded = 0;
+                while decoded + 2 <= input.len() {
+                    let (code, len) = match unit(decoded) {
+                        high @ 0xD800..=0xDBFF => {
+                            if decoded + 4 > input.len() {
+                                break
+                            }
+                            match unit(decoded + 2) {
+                                low @ 0xDC00..=0xDFFF => (0x10000 + ((high - 0xD800) << 10) + (low - 0xDC00), 4),
+                                _ => return Err(decoded),
+                            }
+                        }
+                        0xDC00..=0xDFFF => return Err(decoded),
+                        code => (code, 2),
+                    };
+                    let c = char::try_from(code).map_err(|_| decoded)?;
+                    output.extend(c.encode_utf8(&mut [0; 4]).as_bytes());
+                    decoded += len;
+                }
+                Ok(decoded)
+            }
+        }
+    }
+}
+
+/// Fused reader converting stream in legacy encoding to UTF-8.
+///
+/// Invalid sequences fail the read with `InvalidData` error telling their byte offset in the source
+/// stream, after all data decoded before them was read; all the following reads fail with the same
+/// error. Fuse errors are delivered at the end of the stream like with the `FusedReader`.
+#[derive(Debug)]
+pub struct TranscodingReader<R: Read> {
+    reader: FusedReader<R>,
+    encoding: Encoding,
+    // source bytes not decoded yet and their offset in the stream
+    input: Vec<u8>,
+    offset: u64,
+    output: Vec<u8>,
+    consumed: usize,
+    // invalid sequence the stream is failed with once the data decoded before it was read
+    error: Option<IoError>,
+}
+
+impl<R: Read> TranscodingReader<R> {
+    /// Wraps fused reader of stream in given encoding.
+    pub fn new(reader: FusedReader<R>, encoding: Encoding) -> TranscodingReader<R> {
+        TranscodingReader {
+            reader,
+            encoding,
+            input: Vec::new(),
+            offset: 0,
+            output: Vec::new(),
+            consumed: 0,
+            error: None,
+        }
+    }
+
+    /// Returns inner fused reader.
+    ///
+    /// Data buffered but not read yet is lost.
+    pub fn into_inner(self) -> FusedReader<R> {
+        self.reader
+    }
+
+    fn invalid(&self, at: usize) -> IoError {
+        let offset = self.offset + at as u64;
+        IoError::new(ErrorKind::InvalidData, format!("stream contains invalid {:?} sequence at byte offset {}", self.encoding, offset))
+    }
+
+    /// Reads and decodes next chunk of the source stream; returns `false` at the end of the stream.
+    fn fill(&mut self) -> Result<bool, IoError> {
+        self.output.clear();
+        self.consumed = 0;
+
+        let start = self.input.len();
+        self.input.resize(start + CHUNK_SIZE, 0);
+        let bytes = loop {
+            match self.reader.read(&mut self.input[start..]) {
+                Ok(bytes) => break bytes,
+                Err(err) if err.kind() == ErrorKind::Interrupted => (),
+                Err(err) => {
+                    self.input.truncate(start);
+                    return Err(err)
+                }
+            }
+        };
+        self.input.truncate(start + bytes);
+        if bytes == 0 {
+            if !self.input.is_empty() {
+                // stream ended in the middle of a character
+                self.error = Some(self.invalid(0));
+            }
+            return Ok(false)
+        }
+
+        let decoded = match self.encoding.decode(&self.input, &mut self.output) {
+            Ok(decoded) => decoded,
+            Err(at) => {
+                self.error = Some(self.invalid(at));
+                at
+            }
+        };
+        self.input.drain(..decoded);
+        self.offset += decoded as u64;
+        Ok(true)
+    }
+}
+
+impl<R: Read> Read for TranscodingReader<R> {
+    fn read(&mut self, buf: &mut [u8]) -> Result<usize, IoError> {
+        while self.consumed == self.output.len() {
+            if let Some(err) = &self.error {
+                return Err(FuseError::duplicate(err))
+            }
+            if !self.fill()? && self.error.is_none() {
+                return Ok(0)
+            }
+        }
+
+        let bytes = buf.len().min(self.output.len() - self.consumed);
+        buf[..bytes].copy_from_slice(&self.output[self.consumed..self.consumed + bytes]);
+        self.consumed += bytes;
+        Ok(bytes)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::fuse;
+    use std::io::{Cursor, Write};
+    use std::thread;
+
+    fn utf16le(text: &str) -> Vec<u8> {
+        text.encode_utf16().flat_map(|unit| unit.to_le_bytes()).collect()
+    }
+
+    #[test]
+    fn test_transcoding_reader_utf16le() {
+        let (reader, mut writer) = pipe::pipe();
+        let (reader, fuse) = fuse(reader);
+        let mut reader = TranscodingReader::new(reader, Encoding::Utf16Le);
+
+        thread::spawn(move || {
+            let fuse = fuse.arm().unwrap();
+            let data = utf16le("zażółć 🦀");
+            // split in the middle of a code unit of the surrogate pair
+            writer.write_all(&data[..15]).unwrap();
+            writer.write_all(&data[15..]).unwrap();
+            fuse.blow(IoError::new(ErrorKind::UnexpectedEof, "uh! oh!"));
+        });
+
+        let mut text = Vec::new();
+        assert_eq!(reader.read_to_end(&mut text).unwrap_err().kind(), ErrorKind::UnexpectedEof);
+        assert_eq!(String::from_utf8(text).unwrap(), "zażółć 🦀");
+    }
+
+    #[test]
+    fn test_transcoding_reader_invalid() {
+        let mut data = utf16le("ok");
+        data.extend(&[0x00, 0xDC]);
+        let (reader, _fuse) = fuse(Cursor::new(data));
+        let mut reader = TranscodingReader::new(reader, Encoding::Utf16Le);
+
+        let mut text = Vec::new();
+        let err = reader.read_to_end(&mut text).unwrap_err();
+        assert_eq!(err.kind(), ErrorKind::InvalidData);
+        assert!(err.to_string().ends_with("byte offset 4"));
+        assert_eq!(text, b"ok");
+    }
+
+    #[test]
+    fn test_transcoding_reader_invalid_sticky() {
+        let mut data = utf16le("ok");
+        data.extend(&[0x00, 0xDC]);
+        data.extend(utf16le("more"));
+        let (reader, _fuse) = fuse(Cursor::new(data));
+        let mut reader = TranscodingReader::new(reader, Encoding::Utf16Le);
+
+        assert_eq!(reader.read(&mut [0; 16]).unwrap(), 2);
+        for _ in 0..3 {
+            let err = reader.read(&mut [0; 16]).unwrap_err();
+            assert_eq!(err.kind(), ErrorKind::InvalidData);
+            assert!(err.to_string().ends_with("byte offset 4"));
+        }
+        assert!(reader.input.len() <= CHUNK_SIZE);
+    }
+
+    #[test]
+    fn test_transcoding_reader_truncated_sticky() {
+        let (reader, _fuse) = fuse(Cursor::new(vec![b'o', 0, b'k']));
+        let mut reader = TranscodingReader::new(reader, Encoding::Utf16Le);
+
+        let mut text = Vec::new();
+        assert_eq!(reader.read_to_end(&mut text).unwrap_err().kind(), ErrorKind::InvalidData);
+        assert_eq!(text, b"o");
+        assert_eq!(reader.read(&mut [0; 16]).unwrap_err().kind(), ErrorKind::InvalidData);
+    }
+
+    #[test]
+    fn test_transcoding_reader_latin1() {
+        let (reader, _fuse) = fuse(Cursor::new(b"caf\xe9".to_vec()));
+        let mut text = String::new();
+        TranscodingReader::new(reader, Encoding::Latin1).read_to_string(&mut text).unwrap();
+        assert_eq!(text, "café");
+    }
+}
@@ -0,0 +1,216 @@
+use std::io::{Read, Write, Error as IoError, ErrorKind};
+use std::os::unix::io::AsRawFd;
+
+use io_uring::{opcode, squeue, types, IoUring};
+
+use crate::{FusedReader, FuseStatus};
+
+const CHUNK_SIZE: usize = 64 * 1024;
+const READ: u64 = 0;
+const WRITE: u64 = 1;
+const CANCEL: u64 = 2;
+// how often to check the fuse while waiting for the reader
+const FUSE_POLL: types::Timespec = types::Timespec::new().nsec(50_000_000);
+// offset meaning current file position
+const CURRENT_POSITION: u64 = u64::MAX;
+
+/// Copies all data from fd-backed fused reader to fd-backed writer using io_uring.
+///
+/// Reading of the next chunk is submitted together with writing of the previous one so both sides
+/// are kept busy. While waiting for data the fuse is checked periodically; if it was blown or the
+/// writer end panicked outstanding requests are cancelled and the fuse error is returned, even if
+/// the reader file descriptor was not closed. After reaching EOF the fuse is checked and the fuse
+/// error is returned if it was blown. Fails with `WriteZero` error if the writer stopped accepting
+/// data.
+///
+/// Only readers backed by a file descriptor are supported; `ring_pipe` is an in-memory pipe that
+/// io_uring can not operate on, copy from it with `fused_copy_buf` instead.
+///
+/// Requires Linux 5.11 or newer.
+pub fn fused_copy_uring<R, W>(reader: &mut FusedReader<R>, writer: &mut W) -> Result<u64, IoError>
+    where R: Read + AsRawFd, W: Write + AsRawFd + ?Sized {
+    let input = types::Fd(reader.reader.as_raw_fd());
+    let output = types::Fd(writer.as_raw_fd());
+    let mut ring = IoUring::new(8)?;
+    let mut bufs = [vec![0; CHUNK_SIZE], vec![0; CHUNK_SIZE]];
+    // buffer being read into; the other one is being written
+    let mut reading = 0;
+    let mut filled = None;
+    let mut unwritten: Option<(usize, usize)> = None;
+    let mut eof = false;
+    let mut copied = 0;
+
+    loop {
+        let mut read_pending = false;
+        let mut write_pending = false;
+
+        if !eof && filled.is_none() {
+            let buf = &mut bufs[reading];
+            let read = opcode::Read::new(input, buf.as_mut_ptr(), buf.len() as u32)
+                .offset(CURRENT_POSITION)
+                .build()
+                .user_data(READ);
+            push(&mut ring, &read)?;
+            read_pending = true;
+        }
+        if let Some((start, end)) = unwritten {
+            let buf = &bufs[reading ^ 1][start..end];
+            let write = opcode::Write::new(output, buf.as_ptr(), buf.len() as u32)
+                .offset(CURRENT_POSITION)
+                .build()
+                .user_data(WRITE);
+            push(&mut ring, &write)?;
+            write_pending = true;
+        }
+        if !read_pending && !write_pending {
+            break
+        }
+
+        let mut error = None;
+        let mut cancelled = false;
+        while read_pending || write_pending {
+            match ring.submitter().submit_with_args(1, &types::SubmitArgs::new().timespec(&FUSE_POLL)) {
+                Ok(_) => (),
+                Err(err) if err.raw_os_error() == Some(libc::ETIME) => {
+                    if read_pending && error.is_none() {
+                        error = match reader.check_fuse() {
+                            FuseStatus::Blown(err) => Some(reader.fuse_failed(err)),
+                            FuseStatus::Poisoned(_) => reader.eof_error().map(|err| reader.fuse_failed(err)),
+                            FuseStatus::Unarmed | FuseStatus::Armed | FuseStatus::Completed => None,
+                        };
+                    }
+                }
+                Err(err) if err.kind() == ErrorKind::Interrupted || err.raw_os_error() == Some(libc::EBUSY) => (),
+                Err(err) => {
+                    // kernel may still use the buffers
+                    std::mem::forget(bufs);
+                    return Err(err)
+                }
+            }
+
+            for cqe in ring.completion() {
+                let res = cqe.result();
+                match cqe.user_data() {
+                    READ => {
+                        read_pending = false;
+                        match res {
+                            0 => eof = true,
+                            res if res > 0 => {
+                                filled = Some(res as usize);
+                                reader.advance(res as u64);
+                            }
+                            res if res == -libc::EINTR => (),
+                            res => { error.get_or_insert(IoError::from_raw_os_error(-res)); }
+                        }
+                    }
+                    WRITE => {
+                        write_pending = false;
+                        match res {
+                            0 => { error.get_or_insert(IoError::new(ErrorKind::WriteZero, "failed to write whole buffer")); }
+                            res if res > 0 => {
+                                if let Some((start, _)) = unwritten.as_mut() {
+                                    *start += res as usize;
+                                }
+                                copied += res as u64;
+                            }
+                            res if res == -libc::EINTR => (),
+                            res => { error.get_or_insert(IoError::from_raw_os_error(-res)); }
+                        }
+                    }
+                    _ => (),
+                }
+            }
+
+            if error.is_some() && !cancelled {
+                cancelled = true;
+                for (pending, user_data) in [(read_pending, READ), (write_pending, WRITE)] {
+                    if pending {
+                        push(&mut ring, &opcode::AsyncCancel::new(user_data).build().user_data(CANCEL))?;
+                    }
+                }
+            }
+        }
+
+        if let Some(err) = error {
+            return Err(err)
+        }
+        if unwritten.is_some_and(|(start, end)| start == end) {
+            unwritten = None;
+        }
+        if let (None, Some(bytes)) = (unwritten, filled) {
+            unwritten = Some((0, bytes));
+            filled = None;
+            reading ^= 1;
+        }
+    }
+
+    match reader.eof_error() {
+        Some(err) => Err(reader.fuse_failed(err)),
+        None => Ok(copied),
+    }
+}
+
+fn push(ring: &mut IoUring, entry: &squeue::Entry) -> Result<(), IoError> {
+    // buffers referenced by the entries outlive the requests as all completions are awaited
+    unsafe { ring.submission().push(entry) }.map_err(|_| IoError::other("io_uring submission queue is full"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::fuse;
+    use std::os::unix::net::UnixStream;
+    use std::sync::mpsc::channel;
+    use std::thread;
+    use std::time::{Duration, Instant};
+
+    fn collect(mut output: impl Read + Send + 'static) -> thread::JoinHandle<Vec<u8>> {
+        thread::spawn(move || {
+            let mut data = Vec::new();
+            output.read_to_end(&mut data).unwrap();
+            data
+        })
+    }
+
+    #[test]
+    fn test_fused_copy_uring() {
+        let (reader, mut writer) = std::io::pipe().unwrap();
+        let (mut reader, fuse) = fuse(reader);
+        let (output, mut sink) = std::io::pipe().unwrap();
+
+        thread::spawn(move || {
+            let _fuse = fuse.arm().unwrap();
+            writer.write_all(&vec![1; CHUNK_SIZE * 3 + 7]).unwrap();
+        });
+        let collector = collect(output);
+
+        assert_eq!(fused_copy_uring(&mut reader, &mut sink).unwrap(), CHUNK_SIZE as u64 * 3 + 7);
+        assert_eq!(reader.position(), CHUNK_SIZE as u64 * 3 + 7);
+        drop(sink);
+        assert_eq!(collector.join().unwrap(), vec![1; CHUNK_SIZE * 3 + 7]);
+    }
+
+    #[test]
+    fn test_fused_copy_uring_cancel() {
+        let (input, mut writer) = UnixStream::pair().unwrap();
+        let (mut reader, fuse) = fuse(input);
+        let (output, mut sink) = UnixStream::pair().unwrap();
+        let (keep, kept) = channel();
+
+        thread::spawn(move || {
+            let fuse = fuse.arm().unwrap();
+            writer.write_all(&[1; 10]).unwrap();
+            fuse.blow(IoError::new(ErrorKind::InvalidData, "uh! oh!"));
+            // writer end of the stream stays open
+            keep.send(writer).unwrap();
+        });
+        let collector = collect(output);
+
+        let start = Instant::now();
+        assert_eq!(fused_copy_uring(&mut reader, &mut sink).unwrap_err().kind(), ErrorKind::InvalidData);
+        assert!(start.elapsed() < Duration::from_secs(5));
+        drop(sink);
+        assert_eq!(collector.join().unwrap(), vec![1; 10]);
+        drop(kept);
+    }
+}
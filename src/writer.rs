@@ -0,0 +1,154 @@
+use std::io::{IoSlice, Write, Error as IoError, ErrorKind};
+use std::sync::atomic::Ordering;
+
+use crate::{eof_error, new_fuse_state, BlowReason, Fuse, FuseError, FuseState, FuseStatus};
+
+/// Fuses writer so that if reader thread dies while holding armed fuse the writer will get
+/// `BrokenPipe` error.
+///
+/// This is the reverse of `fuse`: the consumer arms the fuse and the producer writing into the
+/// stream learns about the consumer failure instead of writing into a dead pipe. The consumer
+/// signals it is done with `FuseGuard::complete`; a guard released otherwise, e.g. when the
+/// consumer returns early, fails the writer with `BrokenPipe` error too.
+pub fn fuse_writer<W: Write>(writer: W) -> (FusedWriter<W>, Fuse) {
+    let writer_fuse = new_fuse_state();
+    writer_fuse.require_complete.store(true, Ordering::Release);
+    let reader_fuse = writer_fuse.clone();
+    (FusedWriter {
+            writer,
+            fuse: writer_fuse,
+            error: None,
+        },
+        Fuse::from_state(reader_fuse),
+    )
+}
+
+/// Writer that will fail with I/O error if fuse was blown.
+///
+/// The fuse is checked before every write and flush and again when the inner writer fails; once
+/// blown all following calls fail with the same error.
+#[derive(Debug)]
+pub struct FusedWriter<W: Write> {
+    writer: W,
+    fuse: FuseState,
+    error: Option<IoError>,
+}
+
+impl<W: Write> FusedWriter<W> {
+    /// Checks status of the fuse.
+    ///
+    /// Once blown the fuse is reported as `FuseStatus::Blown` by every call.
+    pub fn check_fuse(&self) -> FuseStatus {
+        match self.error.as_ref().map(FuseError::duplicate).or_else(|| eof_error(&self.fuse).map(reader_error)) {
+            Some(err) => FuseStatus::Blown(err),
+            None => crate::check_fuse(&self.fuse),
+        }
+    }
+
+    /// Returns inner writer.
+    pub fn into_inner(self) -> W {
+        self.writer
+    }
+
+    fn fuse_error(&mut self) -> Result<(), IoError> {
+        if self.error.is_none() {
+            self.error = eof_error(&self.fuse).map(reader_error);
+        }
+        match &self.error {
+            Some(err) => Err(FuseError::duplicate(err)),
+            None => Ok(()),
+        }
+    }
+}
+
+/// Describes panic of the reader end from the point of view of the writer.
+fn reader_error(err: IoError) -> IoError {
+    match FuseError::from_io(&err).map(FuseError::reason) {
+        Some(reason @ BlowReason::Panic { location, .. }) => {
+            let message = match location {
+                Some(location) => format!("reader end dropped due to panic at {}", location),
+                None => "reader end dropped due to panic".to_owned(),
+            };
+            FuseError::new_io(ErrorKind::BrokenPipe, reason.clone(), message)
+        }
+        _ => err,
+    }
+}
+
+impl<W: Write> Write for FusedWriter<W> {
+    fn write(&mut self, buf: &[u8]) -> Result<usize, IoError> {
+        self.fuse_error()?;
+        // the writer may fail because of what blew the fuse
+        self.writer.write(buf).or_else(|err| self.fuse_error().and(Err(err)))
+    }
+
+    fn write_vectored(&mut self, bufs: &[IoSlice<'_>]) -> Result<usize, IoError> {
+        self.fuse_error()?;
+        self.writer.write_vectored(bufs).or_else(|err| self.fuse_error().and(Err(err)))
+    }
+
+    fn flush(&mut self) -> Result<(), IoError> {
+        self.fuse_error()?;
+        self.writer.flush().or_else(|err| self.fuse_error().and(Err(err)))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::thread;
+
+    #[test]
+    fn test_fused_writer_vectored() {
+        let (mut writer, fuse) = fuse_writer(Vec::new());
+        assert_eq!(writer.write_vectored(&[IoSlice::new(&[1]), IoSlice::new(&[2, 3])]).unwrap(), 3);
+
+        fuse.arm().unwrap().blow(IoError::new(ErrorKind::InvalidData, "uh! oh!"));
+        assert_eq!(writer.write_vectored(&[IoSlice::new(&[4])]).unwrap_err().kind(), ErrorKind::InvalidData);
+        assert_eq!(writer.into_inner(), vec![1, 2, 3]);
+    }
+
+    #[test]
+    fn test_fused_writer_reader_panic() {
+        let (mut writer, fuse) = fuse_writer(Vec::new());
+        writer.write_all(&[1]).unwrap();
+
+        thread::spawn(move || {
+            let _fuse = fuse.arm().unwrap();
+            panic!("boom");
+        }).join().unwrap_err();
+
+        assert_eq!(writer.write_all(&[2]).unwrap_err().kind(), ErrorKind::BrokenPipe);
+        assert_eq!(writer.flush().unwrap_err().kind(), ErrorKind::BrokenPipe);
+        assert_eq!(writer.into_inner(), vec![1]);
+    }
+
+    #[test]
+    fn test_fused_writer_blow() {
+        let (mut writer, fuse) = fuse_writer(Vec::new());
+        fuse.arm().unwrap().blow(IoError::new(ErrorKind::InvalidData, "uh! oh!"));
+
+        assert_eq!(writer.write_all(&[1]).unwrap_err().kind(), ErrorKind::InvalidData);
+        assert!(matches!(writer.check_fuse(), FuseStatus::Blown(err) if err.kind() == ErrorKind::InvalidData));
+        assert!(writer.into_inner().is_empty());
+    }
+
+    #[test]
+    fn test_fused_writer_reader_released() {
+        let (mut writer, fuse) = fuse_writer(Vec::new());
+        // consumer gives up without a panic
+        drop(fuse.arm().unwrap());
+
+        assert_eq!(writer.write_all(&[1]).unwrap_err().kind(), ErrorKind::BrokenPipe);
+        assert!(matches!(writer.check_fuse(), FuseStatus::Blown(_)));
+    }
+
+    #[test]
+    fn test_fused_writer_reader_complete() {
+        let (mut writer, fuse) = fuse_writer(Vec::new());
+        fuse.arm().unwrap().complete();
+
+        writer.write_all(&[1]).unwrap();
+        assert_eq!(writer.into_inner(), vec![1]);
+    }
+}